@@ -1175,6 +1175,17 @@ pub fn is_operator_byte(c: u8) -> bool {
     }
 }
 
+/// The name of the identifier which a literal with `suffix` (`5px`) is applied to. The name can
+/// not be written in source code so it only ever refers to the macro registered for `suffix`.
+pub fn literal_suffix_name(suffix: &str) -> String {
+    format!("#{}", suffix)
+}
+
+/// Returns the suffix if `name` were created by `literal_suffix_name`
+pub fn literal_suffix(name: &str) -> Option<&str> {
+    name.strip_prefix('#')
+}
+
 pub fn is_constructor(s: &str) -> bool {
     s.rsplit('.')
         .next()
@@ -1508,6 +1519,20 @@ impl<Id> OwnedExpr<Id> {
             )
         }
     }
+
+    /// Clones the expression into a new arena
+    pub fn ast_clone(&self) -> OwnedExpr<Id>
+    where
+        for<'ast> SpannedExpr<'ast, Id>: AstClone<'ast, Id>,
+    {
+        let expr = {
+            mk_ast_arena!(arena);
+            let expr = self.expr().ast_clone((*arena).borrow());
+            RootExpr::new(arena.clone(), arena.alloc(expr))
+        };
+        // `arena` has been dropped so `expr` holds the only reference to it
+        expr.try_into_send().ok().unwrap()
+    }
 }
 
 pub trait AstClone<'ast, Id> {
@@ -1628,3 +1653,30 @@ impl_ast_clone! {
     BytePos,
     Symbol,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::pos::{self, Span};
+
+    #[test]
+    fn owned_expr_ast_clone() {
+        let expr = {
+            mk_ast_arena!(arena);
+            let expr = pos::spanned(Span::default(), Expr::Literal(Literal::Int(1)));
+            RootExpr::new(arena.clone(), arena.alloc(expr))
+        };
+        let expr: OwnedExpr<Symbol> = expr.try_into_send().ok().unwrap();
+
+        let cloned = expr.ast_clone();
+        match cloned.expr().value {
+            Expr::Literal(Literal::Int(1)) => (),
+            _ => panic!("Unexpected clone"),
+        }
+        match expr.expr().value {
+            Expr::Literal(Literal::Int(1)) => (),
+            _ => panic!("Original expression was modified"),
+        }
+    }
+}
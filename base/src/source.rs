@@ -6,19 +6,32 @@ use itertools::Itertools;
 
 use crate::pos::{ByteOffset, BytePos, Column, Line, Location, RawIndex, Span};
 
-use codespan_reporting::files::{Files, SimpleFile};
+use codespan_reporting::files::{self, Files, SimpleFile};
 
 pub type FileId = BytePos;
 
+/// Source text which is shared with the `CodeMap` instead of being copied into it, for instance
+/// the contents of a memory mapped file.
+pub type SharedSource = Arc<dyn AsRef<str> + Send + Sync>;
+
+#[derive(Clone)]
+struct FileSource(SharedSource);
+
+impl AsRef<str> for FileSource {
+    fn as_ref(&self) -> &str {
+        (*self.0).as_ref()
+    }
+}
+
 pub struct FileMap {
-    file: SimpleFile<String, String>,
+    file: SimpleFile<String, FileSource>,
     span_start: FileId,
 }
 
 impl fmt::Debug for FileMap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("FileMap")
-            .field("file", &self.file.source())
+            .field("file", &self.source())
             .field("span", &self.span())
             .finish()
     }
@@ -27,14 +40,14 @@ impl fmt::Debug for FileMap {
 impl FileMap {
     pub fn new(name: String, source: String) -> Self {
         Self {
-            file: SimpleFile::new(name, source),
+            file: SimpleFile::new(name, FileSource(Arc::new(source))),
             span_start: BytePos(1),
         }
     }
 
-    fn with_index(name: String, source: String, span_start: FileId) -> Self {
+    fn with_index(name: String, source: SharedSource, span_start: FileId) -> Self {
         FileMap {
-            file: SimpleFile::new(name, source),
+            file: SimpleFile::new(name, FileSource(source)),
             span_start,
         }
     }
@@ -59,17 +72,56 @@ impl FileMap {
     }
 
     pub fn source(&self) -> &str {
-        self.file.source()
+        self.file.source().as_ref()
+    }
+
+    /// Returns the source text without copying it, letting it outlive the `FileMap`
+    pub fn shared_source(&self) -> SharedSource {
+        self.file.source().0.clone()
     }
 
     pub fn name(&self) -> &str {
         self.file.name()
     }
+
+    /// Returns the location of `byte_index` where tabs advance to the next multiple of
+    /// `tab_width`.
+    fn location_with_tab_width(
+        &self,
+        byte_index: usize,
+        tab_width: usize,
+    ) -> Option<files::Location> {
+        let line_index = self.line_index((), byte_index)?;
+        let line_start = self.line_range((), line_index)?.start;
+        let line_prefix = self.source().get(line_start..byte_index)?;
+        let tab_width = tab_width.max(1);
+        let column = line_prefix.chars().fold(0, |column, c| {
+            if c == '\t' {
+                (column / tab_width + 1) * tab_width
+            } else {
+                column + 1
+            }
+        });
+        Some(files::Location {
+            line_number: line_index + 1,
+            column_number: column + 1,
+        })
+    }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 pub struct CodeMap {
     files: Vec<Arc<FileMap>>,
+    tab_width: usize,
+}
+
+impl Default for CodeMap {
+    fn default() -> Self {
+        CodeMap {
+            files: Vec::new(),
+            tab_width: 1,
+        }
+    }
 }
 
 impl CodeMap {
@@ -77,7 +129,23 @@ impl CodeMap {
         Self::default()
     }
 
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    /// Sets the number of columns a tab character advances to when the `CodeMap` reports
+    /// locations (default: 1, same as any other character). Only affects how locations are
+    /// rendered so the files are left untouched.
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width;
+    }
+
     pub fn add_filemap(&mut self, filename: String, source: String) -> Arc<FileMap> {
+        self.add_shared_filemap(filename, Arc::new(source))
+    }
+
+    /// Adds a file whose source is referenced by the `CodeMap` rather than copied into it
+    pub fn add_shared_filemap(&mut self, filename: String, source: SharedSource) -> Arc<FileMap> {
         let start_index = self
             .files
             .last()
@@ -89,6 +157,13 @@ impl CodeMap {
         file_map
     }
 
+    /// Removes the file containing `file_id`. The remaining files keep their positions so spans
+    /// into them stay valid.
+    pub fn remove(&mut self, file_id: FileId) -> Option<Arc<FileMap>> {
+        let i = self.find_index(file_id)?;
+        Some(self.files.remove(i))
+    }
+
     pub fn to_usize(&self, pos: BytePos) -> Option<usize> {
         self.get(pos)?.to_usize(pos)
     }
@@ -119,7 +194,7 @@ impl CodeMap {
             if src.len() <= (max - min).to_usize() {
                 let start_index = self.files[i].span().start();
                 let name = self.files[i].name().to_owned();
-                let new_file = Arc::new(FileMap::with_index(name, src, start_index));
+                let new_file = Arc::new(FileMap::with_index(name, Arc::new(src), start_index));
                 self.files[i] = new_file.clone();
                 new_file
             } else {
@@ -145,7 +220,7 @@ impl CodeMap {
                         };
                         let new_file = Arc::new(FileMap::with_index(
                             file.name().to_owned(),
-                            src,
+                            Arc::new(src),
                             start_index,
                         ));
                         self.files.insert(j, new_file.clone());
@@ -182,7 +257,7 @@ impl<'a> Files<'a> for FileMap {
     }
 
     fn source(&self, _file_id: Self::FileId) -> Option<&str> {
-        Some(self.file.source())
+        Some(FileMap::source(self))
     }
 
     fn line_index(&self, file_id: Self::FileId, byte_index: usize) -> Option<usize> {
@@ -214,6 +289,11 @@ impl<'a> Files<'a> for CodeMap {
     fn line_range(&self, file_id: FileId, line_index: usize) -> Option<Range<usize>> {
         self.get(file_id)?.line_range((), line_index)
     }
+
+    fn location(&self, file_id: FileId, byte_index: usize) -> Option<files::Location> {
+        self.get(file_id)?
+            .location_with_tab_width(byte_index, self.tab_width)
+    }
 }
 
 pub trait Source {
@@ -415,4 +495,37 @@ mod tests {
     fn empty_comment_iter() {
         assert_eq!(CommentIter { src: "" }.next(), None);
     }
+
+    #[test]
+    fn location_with_tab_width() {
+        let mut code_map = CodeMap::new();
+        let file_map = code_map.add_filemap("test".into(), "a\n\tb\n  \tc".into());
+        let file_id = file_map.span().start();
+        let location = |code_map: &CodeMap, byte_index| {
+            let location = Files::location(code_map, file_id, byte_index).unwrap();
+            (location.line_number, location.column_number)
+        };
+        assert_eq!(location(&code_map, 3), (2, 2));
+        assert_eq!(location(&code_map, 8), (3, 4));
+
+        code_map.set_tab_width(4);
+        assert_eq!(location(&code_map, 3), (2, 5));
+        assert_eq!(location(&code_map, 8), (3, 5));
+
+        // The file is shared with any previously created errors and is left untouched
+        assert!(Arc::ptr_eq(code_map.find_file("test").unwrap(), &file_map));
+    }
+
+    #[test]
+    fn shared_filemap_is_not_copied() {
+        let source: SharedSource = Arc::new(String::from("let x = 1\nx"));
+        let mut code_map = CodeMap::new();
+        let file_map = code_map.add_shared_filemap("test".into(), source.clone());
+        assert_eq!(file_map.source(), "let x = 1\nx");
+        assert_eq!(file_map.source().as_ptr(), (*source).as_ref().as_ptr());
+
+        code_map.set_tab_width(4);
+        let file_map = code_map.find_file("test").unwrap();
+        assert!(Arc::ptr_eq(&file_map.shared_source(), &source));
+    }
 }
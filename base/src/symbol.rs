@@ -27,7 +27,7 @@ struct SymbolInner {
     name: NameBuf,
 }
 
-#[derive(Debug, Default, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
 pub struct SymbolData<N = NameBuf> {
     pub global: bool,
     pub location: Option<(u32, u32)>,
@@ -595,7 +595,7 @@ impl SymbolInner {
 /// `Symbols` is a bidirectional mapping between `Symbol`s and their name as represented in a
 /// source file.
 /// Used to make identifiers within a single module point to the same symbol
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct Symbols {
     indexes:
         hashbrown::HashMap<SymbolData<&'static Name>, Symbol, BuildHasherDefault<fnv::FnvHasher>>,
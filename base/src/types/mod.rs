@@ -2432,6 +2432,49 @@ where
     }
 }
 
+/// The quantified type variables and implicit arguments (constraints) of a type
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TypeScheme<Id = Symbol> {
+    /// The variables bound by the `forall` quantifiers of the type
+    pub params: Vec<Generic<Id>>,
+    /// The types of the implicit arguments which must be resolved at each use of the binding
+    pub constraints: Vec<ArcType<Id>>,
+    /// The type which remains after removing all quantifiers and implicit arguments
+    pub typ: ArcType<Id>,
+}
+
+impl<Id> TypeScheme<Id>
+where
+    Id: Clone,
+{
+    /// Splits `typ` into its quantified variables, implicit arguments and the remaining type.
+    ///
+    /// `forall a . [Show a] -> a -> String` => `([a], [Show a], a -> String)`
+    pub fn new(typ: &ArcType<Id>) -> Self {
+        let mut params = Vec::new();
+        let mut constraints = Vec::new();
+        let mut typ = typ;
+        loop {
+            match **typ {
+                Type::Forall(ref forall_params, ref inner) => {
+                    params.extend(forall_params.iter().cloned());
+                    typ = inner;
+                }
+                Type::Function(ArgType::Implicit, ref arg, ref ret) => {
+                    constraints.push(arg.clone());
+                    typ = ret;
+                }
+                _ => break,
+            }
+        }
+        TypeScheme {
+            params,
+            constraints,
+            typ: typ.clone(),
+        }
+    }
+}
+
 impl<Id> ArcType<Id> {
     fn with_flags(typ: Type<Id, ArcType<Id>>, flags: Flags) -> ArcType<Id> {
         let typ = Arc::new(ArcTypeInner { typ, flags });
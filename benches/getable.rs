@@ -0,0 +1,49 @@
+#[macro_use]
+extern crate gluon_codegen;
+
+use criterion::{black_box, criterion_group, criterion_main, Bencher, Criterion};
+
+use gluon::{
+    new_vm,
+    vm::api::{FunctionRef, Getable, OpaqueValue},
+    RootedThread, ThreadExt,
+};
+
+#[derive(Getable, Pushable, VmType)]
+struct Point {
+    x: i32,
+    name: String,
+    y: f64,
+}
+
+// Benchmarks the conversion of large arrays of records into rust values
+
+fn struct_array(b: &mut Bencher) {
+    let vm = new_vm();
+    let text = r#"
+    let id x : Array { x : Int, name : String, y : Float } -> Array { x : Int, name : String, y : Float } = x
+    id
+    "#;
+    vm.load_script("id", text).unwrap();
+    let mut id: FunctionRef<fn(Vec<Point>) -> OpaqueValue<RootedThread, Vec<Point>>> =
+        vm.get_global("id").unwrap();
+    let points = (0..100_000)
+        .map(|i| Point {
+            x: i,
+            name: "point".into(),
+            y: 1.0,
+        })
+        .collect();
+    let array = id.call(points).unwrap();
+    b.iter(|| {
+        let result = Vec::<Point>::from_value(&vm, array.get_variant());
+        black_box(result)
+    })
+}
+
+fn getable_benchmark(c: &mut Criterion) {
+    c.bench_function("struct array", struct_array);
+}
+
+criterion_group!(getable, getable_benchmark);
+criterion_main!(getable);
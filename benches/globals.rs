@@ -0,0 +1,77 @@
+use criterion::{criterion_group, criterion_main, Bencher, Criterion};
+
+use gluon::{new_vm, query::CompilationBase, RootedThread, ThreadExt};
+
+const MODULES: usize = 50;
+
+fn module_source(i: usize) -> String {
+    format!(
+        r#"
+let add x y = x + y
+let twice f x = f (f x)
+let values = [{i}, add {i} 1, twice (add 2) {i}]
+{{ add, twice, values, name = "module {i}" }}
+"#,
+        i = i
+    )
+}
+
+fn modules() -> Vec<(String, String)> {
+    (0..MODULES)
+        .map(|i| (format!("globals_module_{}", i), module_source(i)))
+        .collect()
+}
+
+/// A vm which has already loaded the prelude so that only the modules themselves are measured
+fn setup_vm() -> RootedThread {
+    let vm = new_vm();
+    vm.run_expr::<()>("setup", "()")
+        .unwrap_or_else(|err| panic!("{}", err));
+    vm
+}
+
+fn load_sequentially(b: &mut Bencher) {
+    let modules = modules();
+    b.iter_with_setup(setup_vm, |vm| {
+        for (name, source) in &modules {
+            vm.load_script(name, source)
+                .unwrap_or_else(|err| panic!("{}", err));
+        }
+        vm
+    })
+}
+
+fn load_through_globals(b: &mut Bencher) {
+    let modules = modules();
+    b.iter_with_setup(
+        || {
+            let vm = setup_vm();
+            {
+                let mut db = vm.get_database_mut();
+                for (name, source) in &modules {
+                    db.add_module(name.clone(), source);
+                }
+            }
+            vm
+        },
+        |vm| {
+            let globals = vm.get_database().globals();
+            assert!(globals.values().all(|global| global.is_ok()));
+            vm
+        },
+    )
+}
+
+fn globals_benchmark(c: &mut Criterion) {
+    let _ = env_logger::try_init();
+
+    c.bench_function("load_50_modules_sequentially", load_sequentially);
+    c.bench_function("load_50_modules_through_globals", load_through_globals);
+}
+
+criterion_group!(
+    name = globals;
+    config = Criterion::default().sample_size(10).configure_from_args();
+    targets = globals_benchmark
+);
+criterion_main!(globals);
@@ -0,0 +1,103 @@
+//! Collection of the modules and global bindings an expression depends on.
+use std::collections::BTreeSet;
+
+use crate::base::{
+    ast::{self, Expr, Pattern, PatternField, SpannedExpr, Visitor},
+    fnv::FnvMap,
+    pos::{BytePos, Span},
+    symbol::Symbol,
+};
+
+/// The modules and global bindings referenced by an expression
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Dependencies {
+    /// The modules which are imported
+    pub imports: BTreeSet<String>,
+    /// The fields of imported modules which are used, as the full path (`std.int.abs`)
+    pub globals: BTreeSet<String>,
+}
+
+struct FindDependencies {
+    source_span: Span<BytePos>,
+    dependencies: Dependencies,
+    /// Variables bound directly to an imported module (`let string = import! std.string`)
+    aliases: FnvMap<Symbol, String>,
+}
+
+impl FindDependencies {
+    fn module_name(&self, expr: &SpannedExpr<Symbol>) -> Option<String> {
+        match &expr.value {
+            Expr::Ident(id) if id.name.is_global() => Some(id.name.definition_name().to_string()),
+            Expr::Ident(id) => self.aliases.get(&id.name).cloned(),
+            Expr::MacroExpansion { replacement, .. } => self.module_name(replacement),
+            _ => None,
+        }
+    }
+
+    fn add_global(&mut self, module: &str, field: &Symbol) {
+        self.dependencies
+            .globals
+            .insert(format!("{}.{}", module, field.declared_name()));
+    }
+}
+
+impl<'a, 'ast> Visitor<'a, 'ast> for FindDependencies {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+        match &expr.value {
+            Expr::Ident(id) if id.name.is_global() && self.source_span.contains(expr.span) => {
+                self.dependencies
+                    .imports
+                    .insert(id.name.definition_name().to_string());
+            }
+            Expr::Projection(module, field, _) if self.source_span.contains(expr.span) => {
+                if let Some(module) = self.module_name(module) {
+                    self.add_global(&module, field);
+                }
+                ast::walk_expr(self, expr)
+            }
+            Expr::LetBindings(binds, _) => {
+                let source_span = self.source_span;
+                for bind in binds
+                    .iter()
+                    .filter(|bind| source_span.contains(bind.name.span))
+                {
+                    let module = match self.module_name(&bind.expr) {
+                        Some(module) => module,
+                        None => continue,
+                    };
+                    match &bind.name.value {
+                        Pattern::Record { fields, .. } => {
+                            for field in &**fields {
+                                if let PatternField::Value { name, .. } = field {
+                                    self.add_global(&module, &name.value);
+                                }
+                            }
+                        }
+                        Pattern::Ident(id) => {
+                            self.aliases.insert(id.name.clone(), module);
+                        }
+                        _ => (),
+                    }
+                }
+                ast::walk_expr(self, expr)
+            }
+            _ => ast::walk_expr(self, expr),
+        }
+    }
+}
+
+/// Returns the modules imported by `expr` and the fields of those modules which are used.
+///
+/// Only imports within `source_span` are reported which lets imports added by the implicit
+/// prelude be ignored.
+pub fn dependencies(source_span: Span<BytePos>, expr: &SpannedExpr<Symbol>) -> Dependencies {
+    let mut visitor = FindDependencies {
+        source_span,
+        dependencies: Dependencies::default(),
+        aliases: FnvMap::default(),
+    };
+    visitor.visit_expr(expr);
+    visitor.dependencies
+}
@@ -0,0 +1,134 @@
+//! Detection of references to bindings marked with `#[deprecated]`.
+use std::{fmt, sync::Arc};
+
+use codespan_reporting::diagnostic::Diagnostic;
+
+use crate::base::{
+    ast::{self, Expr, SpannedExpr, Visitor},
+    error::{AsDiagnostic, Errors},
+    fnv::FnvMap,
+    metadata::{Metadata, MetadataEnv},
+    pos::{self, BytePos, Spanned},
+    source::{CodeMap, FileId},
+    symbol::Symbol,
+};
+
+/// Warning emitted when a binding carrying a `#[deprecated]` attribute is referenced
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Deprecated {
+    pub name: String,
+    pub message: Option<String>,
+}
+
+impl fmt::Display for Deprecated {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Use of deprecated binding `{}`", self.name)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl AsDiagnostic for Deprecated {
+    fn as_diagnostic(&self, _map: &CodeMap) -> Diagnostic<FileId> {
+        Diagnostic::warning().with_message(self.to_string())
+    }
+}
+
+fn deprecation_message(metadata: &Metadata) -> Option<Option<String>> {
+    metadata.get_attribute("deprecated").map(|arguments| {
+        let message = arguments.trim().trim_matches('"');
+        if message.is_empty() {
+            None
+        } else {
+            Some(message.to_string())
+        }
+    })
+}
+
+struct FindDeprecated<'b> {
+    env: &'b dyn MetadataEnv,
+    metadata_map: &'b FnvMap<Symbol, Arc<Metadata>>,
+    warnings: Errors<Spanned<Deprecated, BytePos>>,
+}
+
+impl FindDeprecated<'_> {
+    fn metadata(&self, id: &Symbol) -> Option<Arc<Metadata>> {
+        self.metadata_map
+            .get(id)
+            .cloned()
+            .or_else(|| self.env.get_metadata(id))
+    }
+
+    fn projected_metadata(&self, expr: &SpannedExpr<Symbol>) -> Option<Arc<Metadata>> {
+        match &expr.value {
+            Expr::Ident(id) => self.metadata(&id.name),
+            Expr::Projection(expr, field, _) => self
+                .projected_metadata(expr)
+                .and_then(|metadata| metadata.module.get(field.declared_name()).cloned()),
+            _ => None,
+        }
+    }
+
+    fn check(&mut self, expr: &SpannedExpr<Symbol>, name: &Symbol) {
+        if let Some(message) = self
+            .projected_metadata(expr)
+            .and_then(|metadata| deprecation_message(&metadata))
+        {
+            self.warnings.push(pos::spanned(
+                expr.span,
+                Deprecated {
+                    name: name.declared_name().to_string(),
+                    message,
+                },
+            ));
+        }
+    }
+}
+
+impl<'a, 'ast> Visitor<'a, 'ast> for FindDeprecated<'_> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+        match &expr.value {
+            Expr::Ident(id) => self.check(expr, &id.name),
+            Expr::Projection(_, field, _) => {
+                self.check(expr, field);
+                ast::walk_expr(self, expr)
+            }
+            Expr::Infix { op, .. } => {
+                if let Some(message) = self
+                    .metadata(&op.value.name)
+                    .and_then(|metadata| deprecation_message(&metadata))
+                {
+                    self.warnings.push(pos::spanned(
+                        op.span,
+                        Deprecated {
+                            name: op.value.name.declared_name().to_string(),
+                            message,
+                        },
+                    ));
+                }
+                ast::walk_expr(self, expr)
+            }
+            _ => ast::walk_expr(self, expr),
+        }
+    }
+}
+
+/// Returns a warning for each reference in `expr` to a binding which is marked as
+/// `#[deprecated]`. The (optional) argument of the attribute is used as the message.
+pub fn deprecated_uses(
+    env: &dyn MetadataEnv,
+    metadata_map: &FnvMap<Symbol, Arc<Metadata>>,
+    expr: &SpannedExpr<Symbol>,
+) -> Errors<Spanned<Deprecated, BytePos>> {
+    let mut visitor = FindDeprecated {
+        env,
+        metadata_map,
+        warnings: Errors::new(),
+    };
+    visitor.visit_expr(expr);
+    visitor.warnings
+}
@@ -17,6 +17,8 @@ extern crate gluon_base as base;
 #[macro_use]
 extern crate gluon_codegen;
 
+pub mod dependencies;
+pub mod deprecation;
 pub mod kindcheck;
 pub mod metadata;
 mod recursion_check;
@@ -26,6 +28,7 @@ mod typ;
 pub mod typecheck;
 pub mod unify;
 pub mod unify_type;
+pub mod unused;
 
 mod implicits;
 
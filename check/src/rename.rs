@@ -22,6 +22,29 @@ pub fn rename<'s, 'ast>(
     symbols: &mut SymbolModule,
     ast_arena: ast::ArenaRef<'s, 'ast, Symbol>,
     expr: &mut SpannedExpr<'ast, Symbol>,
+) {
+    rename_(source, symbols, ast_arena, expr, false)
+}
+
+/// Renames `expr` like `rename` but the created symbols do not contain the source location of
+/// their definition. Instead each definition is numbered in the order it is encountered which
+/// makes the symbols (and anything compiled from them) independent of formatting changes which do
+/// not otherwise affect the module.
+pub fn rename_canonical<'s, 'ast>(
+    source: &'s (dyn Source + 's),
+    symbols: &mut SymbolModule,
+    ast_arena: ast::ArenaRef<'s, 'ast, Symbol>,
+    expr: &mut SpannedExpr<'ast, Symbol>,
+) {
+    rename_(source, symbols, ast_arena, expr, true)
+}
+
+fn rename_<'s, 'ast>(
+    source: &'s (dyn Source + 's),
+    symbols: &mut SymbolModule,
+    ast_arena: ast::ArenaRef<'s, 'ast, Symbol>,
+    expr: &mut SpannedExpr<'ast, Symbol>,
+    canonical_symbols: bool,
 ) {
     enum TailCall {
         TailCall,
@@ -36,6 +59,8 @@ pub fn rename<'s, 'ast>(
         env: Environment,
         ast_arena: ast::ArenaRef<'s, 'ast, Symbol>,
         hole: ArcType,
+        canonical_symbols: bool,
+        definitions: u32,
     }
 
     impl<'a, 'b, 's, 'ast> RenameVisitor<'a, 'b, 's, 'ast> {
@@ -84,12 +109,22 @@ pub fn rename<'s, 'ast>(
             }
         }
 
+        /// Returns the location which is stored in the symbol of a definition at `span`
+        fn definition_location(&mut self, span: Span<BytePos>) -> Option<(u32, u32)> {
+            if self.canonical_symbols {
+                self.definitions += 1;
+                Some((0, self.definitions))
+            } else {
+                self.source
+                    .location(span.start())
+                    .map(|location| (location.line.0 + 1, location.column.0 + 1))
+            }
+        }
+
         // Renames the symbol to be unique in this module
         fn stack_var(&mut self, id: Symbol, span: Span<BytePos>) -> Symbol {
             let mut location = self
-                .source
-                .location(span.start())
-                .map(|location| (location.line.0 + 1, location.column.0 + 1))
+                .definition_location(span)
                 .unwrap_or_else(|| (span.start().0, 0));
             let new_id = self.symbols.symbol(SymbolData {
                 global: false,
@@ -243,7 +278,7 @@ pub fn rename<'s, 'ast>(
                     return TailCall::TailCall;
                 }
                 Expr::Lambda(ref mut lambda) => {
-                    let location = self.source.location(expr.span.start()).unwrap_or_else(|| ice!("Lambda without source location"));
+                    let location = self.definition_location(expr.span).unwrap_or_else(|| ice!("Lambda without source location"));
                     let name = format!(
                         "{}.{}",
                         self.symbols.module(),
@@ -251,7 +286,7 @@ pub fn rename<'s, 'ast>(
                     );
                     lambda.id.name = self.symbols.symbol(SymbolData {
                         global: false,
-                        location: Some((location.line.0 + 1, location.column.0 + 1)),
+                        location: Some(location),
                         name,
                     });
 
@@ -384,6 +419,8 @@ pub fn rename<'s, 'ast>(
         },
         ast_arena,
         hole: Type::hole(),
+        canonical_symbols,
+        definitions: 0,
     };
     visitor.visit_expr(expr);
 }
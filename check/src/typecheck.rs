@@ -3,6 +3,7 @@
 //! checking of types are done in the `unify_type` and `kindcheck` modules.
 use std::{
     borrow::{BorrowMut, Cow},
+    convert::TryFrom,
     mem,
     sync::Arc,
 };
@@ -138,6 +139,7 @@ pub struct Typecheck<'a, 'ast> {
     unbound_variables: ScopedMap<Symbol, ArcKind>,
     refined_variables: ScopedMap<u32, ()>,
     pub(crate) ast_arena: ast::ArenaRef<'a, 'ast, Symbol>,
+    strict_conversions: bool,
 }
 
 impl<'a> TypeContext<Symbol, RcType> for Typecheck<'a, '_> {
@@ -179,9 +181,16 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
             refined_variables: ScopedMap::new(),
             subs,
             ast_arena,
+            strict_conversions: false,
         }
     }
 
+    /// Reject literals passed to extern functions which would not fit in the rust type of the
+    /// argument (as described by the `#[lossy_arguments(..)]` attribute of the function)
+    pub fn set_strict_conversions(&mut self, strict_conversions: bool) {
+        self.strict_conversions = strict_conversions;
+    }
+
     pub(crate) fn error<E>(&mut self, span: Span<BytePos>, error: E) -> RcType
     where
         E: Into<HelpError<Symbol, RcType>>,
@@ -195,6 +204,53 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
         self.subs.error()
     }
 
+    fn check_lossy_arguments(
+        &mut self,
+        func: &SpannedExpr<'ast, Symbol>,
+        args: &[SpannedExpr<'ast, Symbol>],
+    ) {
+        let metadata = match self.expr_metadata(func) {
+            Some(metadata) => metadata,
+            None => return,
+        };
+        let conversions = match metadata.get_attribute("lossy_arguments") {
+            Some(conversions) => conversions,
+            None => return,
+        };
+        for (conversion, arg) in conversions.split(',').map(str::trim).zip(args) {
+            let literal = match arg.value {
+                Expr::Literal(Literal::Int(i)) if !int_fits(conversion, i) => i.to_string(),
+                Expr::Literal(Literal::Float(f))
+                    if conversion == "f32" && f64::from(f.into_inner() as f32) != *f =>
+                {
+                    f.to_string()
+                }
+                _ => continue,
+            };
+            self.error(
+                arg.span,
+                TypeError::Message(format!("`{}` does not fit in a `{}`", literal, conversion)),
+            );
+        }
+    }
+
+    fn expr_metadata(&self, expr: &SpannedExpr<'ast, Symbol>) -> Option<Arc<Metadata>> {
+        match &expr.value {
+            Expr::Ident(id) => self
+                .implicit_resolver
+                .metadata
+                .get(&id.name)
+                .cloned()
+                .or_else(|| self.environment.get_metadata(&id.name)),
+            Expr::Projection(expr, field, _) => self
+                .expr_metadata(expr)?
+                .module
+                .get(field.definition_name())
+                .cloned(),
+            _ => None,
+        }
+    }
+
     fn bool(&mut self) -> RcType {
         let typ = self.environment.get_bool().clone();
         self.translate_arc_type(&typ)
@@ -711,6 +767,9 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                 if let CowVec::Owned(implicit_vec) = implicit_vec {
                     *implicit_args = self.ast_arena.alloc_extend(implicit_vec);
                 }
+                if self.strict_conversions {
+                    self.check_lossy_arguments(func, args);
+                }
 
                 typ
             }
@@ -3316,6 +3375,20 @@ fn expr_check_span(e: &SpannedExpr<Symbol>) -> Span<BytePos> {
     }
 }
 
+/// Returns false if converting `i` to the rust integer type `conversion` would lose information
+fn int_fits(conversion: &str, i: i64) -> bool {
+    match conversion {
+        "i16" => i16::try_from(i).is_ok(),
+        "i32" => i32::try_from(i).is_ok(),
+        "isize" => isize::try_from(i).is_ok(),
+        "u16" => u16::try_from(i).is_ok(),
+        "u32" => u32::try_from(i).is_ok(),
+        "u64" => u64::try_from(i).is_ok(),
+        "usize" => usize::try_from(i).is_ok(),
+        _ => true,
+    }
+}
+
 fn generalize_binding<'ast>(
     generalizer: &mut TypeGeneralizer<'_, '_, 'ast>,
     resolved_type: &mut RcType,
@@ -0,0 +1,147 @@
+//! Detection of top-level bindings which are neither exported nor used by an exported binding.
+use crate::base::{
+    ast::{self, Expr, Pattern, PatternField, SpannedExpr, SpannedPattern, Visitor},
+    fnv::FnvSet,
+    metadata::BaseMetadata,
+    pos::{self, BytePos, Span, Spanned},
+    symbol::Symbol,
+};
+
+struct References<'r> {
+    references: &'r mut FnvSet<Symbol>,
+}
+
+impl<'a, 'ast> Visitor<'a, 'ast> for References<'_> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+        match &expr.value {
+            Expr::Ident(id) => {
+                self.references.insert(id.name.clone());
+            }
+            Expr::Infix { op, .. } => {
+                self.references.insert(op.value.name.clone());
+                ast::walk_expr(self, expr)
+            }
+            Expr::Record { exprs, .. } => {
+                // Punned fields (`{ x }`) refer to the variable with the same name
+                self.references.extend(
+                    exprs
+                        .iter()
+                        .filter(|field| field.value.is_none())
+                        .map(|field| field.name.value.clone()),
+                );
+                ast::walk_expr(self, expr)
+            }
+            _ => ast::walk_expr(self, expr),
+        }
+    }
+}
+
+fn references(expr: &SpannedExpr<Symbol>) -> FnvSet<Symbol> {
+    let mut references = FnvSet::default();
+    References {
+        references: &mut references,
+    }
+    .visit_expr(expr);
+    references
+}
+
+fn pattern_idents(pattern: &SpannedPattern<Symbol>, idents: &mut Vec<Spanned<Symbol, BytePos>>) {
+    match &pattern.value {
+        Pattern::Ident(id) => idents.push(pos::spanned(pattern.span, id.name.clone())),
+        Pattern::As(name, pattern) => {
+            idents.push(name.clone());
+            pattern_idents(pattern, idents);
+        }
+        Pattern::Record { fields, .. } => {
+            for field in &**fields {
+                match field {
+                    PatternField::Value {
+                        value: Some(pattern),
+                        ..
+                    } => pattern_idents(pattern, idents),
+                    PatternField::Value { name, value: None } => idents.push(name.clone()),
+                    PatternField::Type { .. } => (),
+                }
+            }
+        }
+        Pattern::Tuple { elems: args, .. } | Pattern::Constructor(_, args) => {
+            for arg in &**args {
+                pattern_idents(arg, idents);
+            }
+        }
+        Pattern::Literal(_) | Pattern::Error => (),
+    }
+}
+
+/// Returns true if `metadata` contains `#[allow(unused)]`
+fn allows_unused(metadata: &BaseMetadata) -> bool {
+    metadata.attributes().any(|attribute| {
+        attribute.name == "allow"
+            && attribute.arguments.as_ref().map_or(false, |args| {
+                args.split(',').any(|arg| arg.trim() == "unused")
+            })
+    })
+}
+
+/// Returns the top-level value bindings of `expr` which are neither part of the module's exported
+/// expression nor reachable from it.
+///
+/// Only bindings whose names lie within `source_span` are reported which lets bindings added by
+/// the implicit prelude be ignored. Bindings marked with `#[allow(unused)]` are never reported and
+/// everything they refer to is considered used.
+pub fn unused_bindings(
+    source_span: Span<BytePos>,
+    expr: &SpannedExpr<Symbol>,
+) -> Vec<Spanned<Symbol, BytePos>> {
+    let mut definitions = Vec::new();
+    let mut allowed = Vec::new();
+
+    let mut export = expr;
+    loop {
+        match &export.value {
+            Expr::LetBindings(binds, body) => {
+                for bind in binds {
+                    if allows_unused(&bind.metadata) {
+                        allowed.push(&bind.expr);
+                        continue;
+                    }
+                    let mut names = Vec::new();
+                    if source_span.contains(bind.name.span) {
+                        pattern_idents(&bind.name, &mut names);
+                    }
+                    definitions.push((names, references(&bind.expr)));
+                }
+                export = body;
+            }
+            Expr::TypeBindings(_, body) => export = body,
+            Expr::MacroExpansion { replacement, .. } => export = replacement,
+            _ => break,
+        }
+    }
+
+    let mut reachable = references(export);
+    for expr in allowed {
+        reachable.extend(references(expr));
+    }
+    loop {
+        let mut changed = false;
+        for (names, references) in &definitions {
+            if names.iter().any(|name| reachable.contains(&name.value)) {
+                for reference in references {
+                    changed |= reachable.insert(reference.clone());
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    definitions
+        .into_iter()
+        .flat_map(|(names, _)| names)
+        .filter(|name| !reachable.contains(&name.value))
+        .collect()
+}
@@ -25,6 +25,79 @@ fn get_gluon_meta_items(attr: &syn::Attribute) -> Option<Vec<syn::NestedMeta>> {
     }
 }
 
+/// Returns the name given with `#[serde(rename = "...")]`, which is the name `make_source` uses
+fn get_serde_rename(attrs: &[syn::Attribute]) -> Option<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("serde"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(List(meta)) => Some(meta.nested),
+            _ => None,
+        })
+        .flatten()
+        .find_map(|meta_item| match meta_item {
+            syn::NestedMeta::Meta(NameValue(ref m)) if m.path.is_ident("rename") => match m.lit {
+                syn::Lit::Str(ref lit) => Some(lit.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+}
+
+/// Returns an error if `gluon` and `serde` are both set to different names, since the derived
+/// types would then disagree with the ones generated by `make_source`
+fn check_rename<T>(
+    item: T,
+    attr_name: &str,
+    gluon: Option<String>,
+    serde: Option<String>,
+) -> syn::Result<()>
+where
+    T: ToTokens,
+{
+    match (gluon, serde) {
+        (Some(gluon), Some(serde)) if gluon != serde => Err(syn::Error::new_spanned(
+            item,
+            format!(
+                "`#[gluon({} = \"{}\")]` does not match `#[serde(rename = \"{}\")]`",
+                attr_name, gluon, serde
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the renamed fields and variants of `input` with `check_rename`
+pub fn check_renames(input: &syn::DeriveInput) -> syn::Result<()> {
+    let fields: Vec<&syn::Field> = match input.data {
+        syn::Data::Struct(ref ast) => ast.fields.iter().collect(),
+        syn::Data::Enum(ref ast) => {
+            for variant in &ast.variants {
+                check_rename(
+                    variant,
+                    "tag",
+                    Variant::from_ast(variant).tag,
+                    get_serde_rename(&variant.attrs),
+                )?;
+            }
+            ast.variants
+                .iter()
+                .flat_map(|variant| variant.fields.iter())
+                .collect()
+        }
+        syn::Data::Union(_) => Vec::new(),
+    };
+    for field in fields {
+        check_rename(
+            field,
+            "rename",
+            Field::from_ast(field).rename,
+            get_serde_rename(&field.attrs),
+        )?;
+    }
+    Ok(())
+}
+
 pub enum CrateName {
     Some(syn::Path),
     GluonVm,
@@ -46,6 +119,7 @@ impl Container {
 
         let mut crate_name = CrateName::None;
         let mut vm_type = None;
+        let mut module = None;
         let mut newtype = false;
         let mut skip = false;
         let mut clone = false;
@@ -74,6 +148,11 @@ impl Container {
                         vm_type = Some(get_lit_str(&m.path, &m.path, &m.lit).unwrap().value())
                     }
 
+                    // Parse `#[gluon(module = "foo")]`
+                    Meta(NameValue(ref m)) if m.path.is_ident("module") => {
+                        module = Some(get_lit_str(&m.path, &m.path, &m.lit).unwrap().value())
+                    }
+
                     Meta(Path(ref w)) if w.is_ident("skip") => {
                         skip = true;
                     }
@@ -103,6 +182,21 @@ impl Container {
             }
         }
 
+        // Resolve `#[gluon(vm_type = "self.Foo")]` against `#[gluon(module = "...")]`
+        let vm_type = vm_type.map(|vm_type| {
+            if vm_type.starts_with("self.") {
+                match module {
+                    Some(ref module) => format!("{}.{}", module, &vm_type["self.".len()..]),
+                    None => panic!(
+                        "`#[gluon(vm_type = \"{}\")]` requires a `#[gluon(module = \"...\")]` attribute",
+                        vm_type
+                    ),
+                }
+            } else {
+                vm_type
+            }
+        });
+
         Container {
             crate_name,
             vm_type,
@@ -114,6 +208,136 @@ impl Container {
     }
 }
 
+pub struct Field {
+    pub rename: Option<String>,
+    pub skip: bool,
+}
+
+impl Field {
+    pub fn from_ast(field: &syn::Field) -> Field {
+        use syn::NestedMeta::*;
+
+        let mut rename = None;
+        let mut skip = false;
+
+        for meta_items in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("gluon"))
+            .filter_map(get_gluon_meta_items)
+        {
+            for meta_item in meta_items {
+                match meta_item {
+                    // Parse `#[gluon(rename = "foo")]`
+                    Meta(NameValue(ref m)) if m.path.is_ident("rename") => {
+                        rename = Some(get_lit_str(&m.path, &m.path, &m.lit).unwrap().value())
+                    }
+
+                    // Parse `#[gluon(skip)]`
+                    Meta(Path(ref w)) if w.is_ident("skip") => {
+                        if field.ident.is_none() {
+                            panic!("`#[gluon(skip)]` can only be used on named fields");
+                        }
+                        skip = true;
+                    }
+
+                    Meta(meta_item) => {
+                        let path = meta_item
+                            .path()
+                            .into_token_stream()
+                            .to_string()
+                            .replace(' ', "");
+                        panic!("unexpected gluon field attribute: `{}`", path)
+                    }
+
+                    Lit(_) => {
+                        panic!("Unexpected literal in gluon field attribute",);
+                    }
+                }
+            }
+        }
+
+        Field { rename, skip }
+    }
+
+    /// Returns true if `field` is left out of the gluon representation with `#[gluon(skip)]`
+    pub fn is_skipped(field: &syn::Field) -> bool {
+        Field::from_ast(field).skip
+    }
+
+    /// Returns the name `field` has in gluon, which is the name of the rust field unless it is
+    /// renamed with `#[gluon(rename = "...")]`
+    pub fn name(field: &syn::Field) -> String {
+        Field::from_ast(field).rename.unwrap_or_else(|| {
+            field
+                .ident
+                .as_ref()
+                .expect("Struct fields always have names")
+                .to_string()
+        })
+    }
+}
+
+pub struct Variant {
+    pub tag: Option<String>,
+}
+
+impl Variant {
+    pub fn from_ast(variant: &syn::Variant) -> Variant {
+        use syn::NestedMeta::*;
+
+        let mut tag = None;
+
+        for meta_items in variant
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("gluon"))
+            .filter_map(get_gluon_meta_items)
+        {
+            for meta_item in meta_items {
+                match meta_item {
+                    // Parse `#[gluon(tag = "Foo")]`
+                    Meta(NameValue(ref m)) if m.path.is_ident("tag") => {
+                        tag = Some(get_lit_str(&m.path, &m.path, &m.lit).unwrap().value())
+                    }
+
+                    Meta(meta_item) => {
+                        let path = meta_item
+                            .path()
+                            .into_token_stream()
+                            .to_string()
+                            .replace(' ', "");
+                        panic!("unexpected gluon variant attribute: `{}`", path)
+                    }
+
+                    Lit(_) => {
+                        panic!("Unexpected literal in gluon variant attribute",);
+                    }
+                }
+            }
+        }
+
+        Variant { tag }
+    }
+
+    /// Returns the name of the constructor `variant` has in gluon, which is the name of the rust
+    /// variant unless it is changed with `#[gluon(tag = "...")]`
+    pub fn name(variant: &syn::Variant) -> String {
+        Variant::from_ast(variant)
+            .tag
+            .unwrap_or_else(|| variant.ident.to_string())
+    }
+}
+
+/// Returns the field which a `#[gluon(newtype)]` struct wraps
+pub fn newtype_field(fields: &syn::Fields) -> &syn::Field {
+    let mut iter = fields.iter();
+    match (iter.next(), iter.next()) {
+        (Some(field), None) => field,
+        _ => panic!("`#[gluon(newtype)]` can only be used on structs with exactly one field"),
+    }
+}
+
 fn get_lit_str<'a>(
     attr_name: &Path,
     _meta_item_name: &Path,
@@ -1,7 +1,7 @@
 use proc_macro2::{Span, TokenStream};
 use syn::{
-    self, Data, DataEnum, DataStruct, DeriveInput, Field, Fields, FieldsNamed, FieldsUnnamed,
-    Generics, Ident, Variant,
+    self, spanned::Spanned, Data, DataEnum, DataStruct, DeriveInput, Field, Fields, FieldsNamed,
+    FieldsUnnamed, Generics, Ident, Variant,
 };
 
 use crate::{
@@ -11,6 +11,9 @@ use crate::{
 
 pub fn derive(input: TokenStream) -> TokenStream {
     let derive_input = syn::parse2(input).expect("Input is checked by rustc");
+    if let Err(err) = attr::check_renames(&derive_input) {
+        return err.to_compile_error();
+    }
 
     let container = attr::Container::from_ast(&derive_input);
 
@@ -36,13 +39,33 @@ fn derive_struct(
     ident: Ident,
     generics: Generics,
 ) -> TokenStream {
-    let cons = match ast.fields {
-        Fields::Named(FieldsNamed { named, .. }) => gen_struct_cons(&ident, named),
-        Fields::Unnamed(FieldsUnnamed { unnamed, .. }) => gen_tuple_struct_cons(&ident, unnamed),
-        Fields::Unit => quote! { #ident },
+    let (cons, from_values) = match ast.fields {
+        ref fields if container.newtype => (
+            gen_newtype_cons(&ident, attr::newtype_field(fields)),
+            TokenStream::new(),
+        ),
+        Fields::Named(FieldsNamed { named, .. }) => (
+            gen_struct_cons(&ident, named.iter().cloned()),
+            gen_struct_from_values(&ident, named),
+        ),
+        Fields::Unnamed(FieldsUnnamed { unnamed, .. }) => {
+            (gen_tuple_struct_cons(&ident, unnamed), TokenStream::new())
+        }
+        Fields::Unit => (quote! { #ident }, TokenStream::new()),
     };
 
-    gen_impl(container, ident, generics, cons)
+    gen_impl(container, ident, generics, cons, from_values)
+}
+
+fn gen_newtype_cons(ident: &Ident, field: &Field) -> TokenStream {
+    let field_ty = &field.ty;
+    let value = quote! {
+        <#field_ty as _gluon_api::Getable<'__vm, '__value>>::from_value(vm, variants)
+    };
+    match field.ident {
+        Some(ref field_ident) => quote! { #ident { #field_ident: #value } },
+        None => quote! { #ident ( #value ) },
+    }
 }
 
 fn gen_struct_cons<I>(ident: &Ident, fields: I) -> TokenStream
@@ -57,7 +80,10 @@ where
             .ident
             .as_ref()
             .expect("Struct fields always have names");
-        let quoted_ident = format!("{}", quote! { #ident });
+        if attr::Field::is_skipped(&field) {
+            return gen_skipped_field(&field);
+        }
+        let quoted_ident = attr::Field::name(&field);
 
         quote! {
             #ident: if let Some(val) = data.lookup_field(vm, #quoted_ident) {
@@ -78,6 +104,75 @@ where
     }
 }
 
+fn gen_struct_from_values<I>(ident: &Ident, fields: I) -> TokenStream
+where
+    I: IntoIterator<Item = Field>,
+{
+    // all elements of an array have the same type so the field names only need to be looked up
+    // in the first element, the remaining elements can then be accessed directly by index
+    let (skipped, fields): (Vec<_>, Vec<_>) = fields
+        .into_iter()
+        .partition(|field| attr::Field::is_skipped(field));
+    let skipped_fields = skipped.iter().map(gen_skipped_field);
+
+    let ((field_idents, quoted_idents), field_types): ((Vec<_>, Vec<_>), Vec<_>) = fields
+        .into_iter()
+        .map(|field| {
+            let quoted_ident = attr::Field::name(&field);
+            (
+                (
+                    field.ident.expect("Struct fields always have names"),
+                    quoted_ident,
+                ),
+                field.ty,
+            )
+        })
+        .unzip();
+    let field_count = field_idents.len();
+    let indexes = 0..field_count;
+
+    quote! {
+        fn from_values<__I>(vm: &'__vm _gluon_thread::Thread, values: __I) -> Vec<Self>
+        where
+            __I: IntoIterator<Item = _GluonVariants<'__value>>,
+        {
+            let mut values = values.into_iter().peekable();
+            let offsets: [usize; #field_count] = match values.peek() {
+                Some(variants) => {
+                    let data = match variants.as_ref() {
+                        _gluon_api::ValueRef::Data(data) => data,
+                        val => panic!("Unexpected value: '{:?}'. Do the type definitions match?", val),
+                    };
+                    [#(
+                        data.lookup_field_index(vm, #quoted_idents).unwrap_or_else(|| {
+                            panic!("Cannot find the field '{}'. Do the type definitions match?", #quoted_idents)
+                        }),
+                    )*]
+                }
+                None => return Vec::new(),
+            };
+
+            values
+                .map(|variants| {
+                    let data = match variants.as_ref() {
+                        _gluon_api::ValueRef::Data(data) => data,
+                        val => panic!("Unexpected value: '{:?}'. Do the type definitions match?", val),
+                    };
+                    #ident {
+                        #(
+                            #field_idents: <#field_types as _gluon_api::Getable<'__vm, '__value>>::from_value(
+                                vm,
+                                data.get_variant(offsets[#indexes]).expect("Field index out of bounds"),
+                            ),
+                        )*
+                        #(#skipped_fields,)*
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
 fn gen_tuple_struct_cons<I>(ident: &Ident, fields: I) -> TokenStream
 where
     I: IntoIterator<Item = Field>,
@@ -154,7 +249,7 @@ fn derive_enum(
         };
     }
 
-    gen_impl(container, ident, generics, cons)
+    gen_impl(container, ident, generics, cons, TokenStream::new())
 }
 
 fn gen_impl(
@@ -162,6 +257,7 @@ fn gen_impl(
     ident: Ident,
     generics: Generics,
     push_impl: TokenStream,
+    from_values_impl: TokenStream,
 ) -> TokenStream {
     // lifetime bounds like '__vm: 'a, 'a: '__vm (which implies => 'a == '__vm)
     // writing bounds like this is a lot easier than actually replacing all lifetimes
@@ -220,6 +316,8 @@ fn gen_impl(
                 fn from_value(vm: &'__vm _gluon_thread::Thread, variants: _GluonVariants<'__value>) -> Self {
                     #push_impl
                 }
+
+                #from_values_impl
             }
         };
     }
@@ -285,7 +383,10 @@ where
             .ident
             .as_ref()
             .expect("Struct fields always have names");
-        let quoted_field_ident = format!("{}", quote! { #field_ident });
+        if attr::Field::is_skipped(field) {
+            return gen_skipped_field(field);
+        }
+        let quoted_field_ident = attr::Field::name(field);
         quote! {
             #field_ident: if let Some(val) = inner_data.lookup_field(vm, #quoted_field_ident) {
                 <#field_ty as _gluon_api::Getable<'__vm, '__value>>::from_value(vm, val)
@@ -308,6 +409,19 @@ where
     }}
 }
 
+/// Fields marked with `#[gluon(skip)]` do not exist in the gluon value so they are initialized
+/// with their `Default` value instead
+fn gen_skipped_field(field: &Field) -> TokenStream {
+    let field_ty = &field.ty;
+    let field_ident = field
+        .ident
+        .as_ref()
+        .expect("Struct fields always have names");
+    quote_spanned! {field_ty.span()=>
+        #field_ident: <#field_ty as ::std::default::Default>::default()
+    }
+}
+
 fn create_getable_bounds(generics: &Generics) -> Vec<TokenStream> {
     map_type_params(generics, |ty| {
         quote! {
@@ -77,6 +77,10 @@
 //! `#[gluon(vm_type = "<gluon_type>")]` attribute, where the gluon type is the fully qualified type name.
 //! The gluon type must be registered before a binding using the mapped rust type is first loaded.
 //!
+//! A `vm_type` starting with `self.` is resolved relative to the module given with the
+//! `#[gluon(module = "<module>")]` attribute, so `#[gluon(module = "types", vm_type = "self.Either")]`
+//! is the same as `#[gluon(vm_type = "types.Either")]`.
+//!
 //! If the rust type has type parameters, they have to implement `VmType` as well.
 //! All lifetimes have to be `'static`.
 //!
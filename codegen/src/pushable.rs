@@ -8,12 +8,15 @@ use syn::{
 };
 
 use crate::{
-    attr::{Container, CrateName},
+    attr::{self, Container, CrateName},
     shared::{map_type_params, split_for_impl},
 };
 
 pub fn derive(input: TokenStream) -> TokenStream {
     let derive_input = syn::parse2(input).expect("Input is checked by rustc");
+    if let Err(err) = attr::check_renames(&derive_input) {
+        return err.to_compile_error();
+    }
 
     let container = Container::from_ast(&derive_input);
 
@@ -39,7 +42,20 @@ fn derive_struct(
     ident: Ident,
     generics: Generics,
 ) -> TokenStream {
-    let (field_idents, field_types) = get_info_from_fields(&ast.fields);
+    if container.newtype {
+        let field = attr::newtype_field(&ast.fields);
+        let ty = &field.ty;
+        let member = match field.ident {
+            Some(ref field_ident) => syn::Member::Named(field_ident.clone()),
+            None => syn::Member::Unnamed(0.into()),
+        };
+        let push_impl = quote! {
+            <#ty as _gluon_api::Pushable<'__vm>>::vm_push(self.#member, ctx)?;
+        };
+        return gen_impl(&container, &ident, generics, push_impl);
+    }
+
+    let (field_idents, field_names, field_types) = get_info_from_fields(&ast.fields);
     let field_idents2 = &field_idents;
 
     // Treat newtype structs as just their inner type
@@ -56,12 +72,12 @@ fn derive_struct(
 
     // destructure the struct so the the fields can be accessed by the push implementation
     let destructured = match &ast.fields {
-        Fields::Named(_) => quote! { let #ident { #(#field_idents2),* } = self; },
+        Fields::Named(_) => quote! { let #ident { #(#field_idents2,)* .. } = self; },
         Fields::Unnamed(_) => quote! { let #ident ( #(#field_idents2),* ) = self; },
         Fields::Unit => quote! {},
     };
 
-    let push_impl = gen_push_impl(None, &field_idents, &field_types);
+    let push_impl = gen_push_impl(None, &field_idents, &field_names, &field_types);
 
     gen_impl(
         &container,
@@ -80,19 +96,19 @@ fn derive_enum(
     // generate a correct implementation for each variant, destructuring the enum
     // to get access to the values
     let match_arms = ast.variants.iter().enumerate().map(|(tag, variant)| {
-        let (field_idents, field_types) = get_info_from_fields(&variant.fields);
+        let (field_idents, field_names, field_types) = get_info_from_fields(&variant.fields);
         let field_idents2 = &field_idents;
         let variant_ident = &variant.ident;
 
         let pattern = match &variant.fields {
-            Fields::Named(_) => quote! { #ident::#variant_ident{ #(#field_idents2),* } },
+            Fields::Named(_) => quote! { #ident::#variant_ident{ #(#field_idents2,)* .. } },
             Fields::Unnamed(_) => quote! { #ident::#variant_ident( #(#field_idents2),* ) },
             Fields::Unit => quote! { #ident::#variant_ident },
         };
 
         match &variant.fields {
             Fields::Named(_) => {
-                let push_impl = gen_push_impl(None, &field_idents, &field_types);
+                let push_impl = gen_push_impl(None, &field_idents, &field_names, &field_types);
                 quote! {
                     #pattern => {
                         #push_impl
@@ -101,7 +117,7 @@ fn derive_enum(
                 }
             }
             _ => {
-                let push_impl = gen_push_impl(Some(tag), &field_idents, &field_types);
+                let push_impl = gen_push_impl(Some(tag), &field_idents, &field_names, &field_types);
                 quote! {
                     #pattern => {
                         #push_impl
@@ -175,6 +191,7 @@ fn gen_impl(
 fn gen_push_impl(
     tag: Option<usize>,
     field_idents: &[Cow<Ident>],
+    field_names: &[String],
     field_types: &[&Type],
 ) -> TokenStream {
     debug_assert!(field_idents.len() == field_types.len());
@@ -192,9 +209,8 @@ fn gen_push_impl(
             ctx.context().push_new_data(#tag as _gluon_types::VmTag, #fields_len)?
         },
         None => {
-            let field_ident_strs = field_idents.iter().map(|i| i.to_string());
             quote! { {
-                let field_names = [#(vm.global_env().intern(#field_ident_strs)?),*];
+                let field_names = [#(vm.global_env().intern(#field_names)?),*];
                 ctx.context().push_new_record(#fields_len, &field_names)?;
             } }
         }
@@ -215,26 +231,31 @@ fn create_pushable_bounds(generics: &Generics) -> Vec<TokenStream> {
     })
 }
 
-fn get_info_from_fields(fields: &Fields) -> (Vec<Cow<Ident>>, Vec<&Type>) {
+fn get_info_from_fields(fields: &Fields) -> (Vec<Cow<Ident>>, Vec<String>, Vec<&Type>) {
     // get all the fields if there are any
     let fields = match fields {
         Fields::Named(FieldsNamed { named, .. }) => named,
         Fields::Unnamed(FieldsUnnamed { unnamed, .. }) => unnamed,
-        Fields::Unit => return (Vec::new(), Vec::new()),
+        Fields::Unit => return (Vec::new(), Vec::new(), Vec::new()),
     };
 
-    fields
+    let ((idents, names), types) = fields
         .iter()
+        .filter(|field| !attr::Field::is_skipped(field))
         .enumerate()
         .map(|(idx, field)| {
             // if the fields belong to a struct we use the field name,
             // otherwise generate one from the index of the tuple element
-            let ident = match &field.ident {
-                Some(ident) => Cow::Borrowed(ident),
-                None => Cow::Owned(Ident::new(&format!("_{}", idx), Span::call_site())),
+            let (ident, name) = match &field.ident {
+                Some(ident) => (Cow::Borrowed(ident), attr::Field::name(field)),
+                None => {
+                    let name = format!("_{}", idx);
+                    (Cow::Owned(Ident::new(&name, Span::call_site())), name)
+                }
             };
 
-            (ident, &field.ty)
+            ((ident, name), &field.ty)
         })
-        .unzip()
+        .unzip();
+    (idents, names, types)
 }
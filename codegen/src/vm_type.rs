@@ -2,12 +2,15 @@ use proc_macro2::{Ident, Span, TokenStream};
 use syn::{self, Data, DeriveInput, Fields, GenericParam, Generics};
 
 use crate::{
-    attr::{Container, CrateName},
+    attr::{self, Container, CrateName, Field, Variant},
     shared::{map_type_params, split_for_impl},
 };
 
 pub fn derive(input: TokenStream) -> TokenStream {
     let derive_input = syn::parse2(input).expect("Input is checked by rustc");
+    if let Err(err) = attr::check_renames(&derive_input) {
+        return err.to_compile_error();
+    }
 
     let container = Container::from_ast(&derive_input);
 
@@ -64,18 +67,29 @@ fn gen_impl(container: &Container, ident: Ident, generics: Generics, data: &Data
             }
         }
         None => match *data {
+            // The alias created for newtypes below refers directly to the wrapped type
+            Data::Struct(ref struct_) if container.newtype => {
+                let typ = &attr::newtype_field(&struct_.fields).ty;
+                quote! {
+                    <#typ as _gluon_api::VmType>::make_type(vm)
+                }
+            }
             Data::Struct(ref struct_) => match struct_.fields {
                 Fields::Named(ref fields) => {
-                    let fields = fields.named.iter().map(|field| {
-                        let ident = field.ident.as_ref().unwrap().to_string();
-                        let typ = &field.ty;
-                        quote! {
-                            _gluon_base::types::Field {
-                                name: _gluon_base::symbol::Symbol::from(#ident),
-                                typ: <#typ as _gluon_api::VmType>::make_type(vm),
+                    let fields = fields
+                        .named
+                        .iter()
+                        .filter(|field| !Field::is_skipped(field))
+                        .map(|field| {
+                            let ident = Field::name(field);
+                            let typ = &field.ty;
+                            quote! {
+                                _gluon_base::types::Field {
+                                    name: _gluon_base::symbol::Symbol::from(#ident),
+                                    typ: <#typ as _gluon_api::VmType>::make_type(vm),
+                                }
                             }
-                        }
-                    });
+                        });
                     quote! {
                         _gluon_base::types::Type::record(
                             vec![],
@@ -92,9 +106,12 @@ fn gen_impl(container: &Container, ident: Ident, generics: Generics, data: &Data
                     } else {
                         let fields = fields.unnamed.iter().map(|field| &field.ty);
                         quote! {
-                            _gluon_base::types::Type::tuple(vec![#(
-                                <#fields as _gluon_api::VmType>::make_type(vm)
-                            ),*])
+                            vm.global_env().type_cache().tuple(
+                                &mut _gluon_base::symbol::Symbols::new(),
+                                vec![#(
+                                    <#fields as _gluon_api::VmType>::make_type(vm)
+                                ),*],
+                            )
                         }
                     }
                 }
@@ -102,19 +119,23 @@ fn gen_impl(container: &Container, ident: Ident, generics: Generics, data: &Data
             },
             Data::Enum(ref enum_) => {
                 let variants = enum_.variants.iter().map(|variant| {
-                    let ident = variant.ident.to_string();
+                    let ident = Variant::name(variant);
                     match variant.fields {
                         Fields::Named(ref fields) => {
-                            let fields = fields.named.iter().map(|field| {
-                                let ident = field.ident.as_ref().unwrap().to_string();
-                                let typ = &field.ty;
-                                quote! {
-                                    _gluon_base::types::Field {
-                                        name: _gluon_base::symbol::Symbol::from(#ident),
-                                        typ: <#typ as _gluon_api::VmType>::make_type(vm),
+                            let fields = fields
+                                .named
+                                .iter()
+                                .filter(|field| !Field::is_skipped(field))
+                                .map(|field| {
+                                    let ident = Field::name(field);
+                                    let typ = &field.ty;
+                                    quote! {
+                                        _gluon_base::types::Field {
+                                            name: _gluon_base::symbol::Symbol::from(#ident),
+                                            typ: <#typ as _gluon_api::VmType>::make_type(vm),
+                                        }
                                     }
-                                }
-                            });
+                                });
                             quote! {{
                                 let ctor_name = _gluon_base::symbol::Symbol::from(#ident);
                                 let typ = _gluon_base::types::Type::record(
@@ -291,3 +291,46 @@ fn tuple_struct_derive() {
         panic!("{}", why);
     }
 }
+
+#[derive(Debug, PartialEq, Getable, VmType)]
+struct Point {
+    x: i32,
+    name: String,
+    y: f64,
+}
+
+#[test]
+fn struct_array() {
+    let vm = new_vm();
+
+    let script = r#"
+        [{ x = 1, name = "a", y = 1.5 }, { x = 2, name = "b", y = 2.5 }]
+    "#;
+    let (points, _) = vm
+        .run_expr::<Vec<Point>>("test", script)
+        .unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!(
+        points,
+        [
+            Point {
+                x: 1,
+                name: "a".into(),
+                y: 1.5
+            },
+            Point {
+                x: 2,
+                name: "b".into(),
+                y: 2.5
+            }
+        ]
+    );
+
+    let script = r#"
+        let empty : Array { x : Int, name : String, y : Float } = []
+        empty
+    "#;
+    let (points, _) = vm
+        .run_expr::<Vec<Point>>("test_empty", script)
+        .unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!(points, []);
+}
@@ -78,6 +78,31 @@ fn normal_struct() {
     }
 }
 
+#[derive(Pushable, VmType)]
+#[gluon(module = "types", vm_type = "self.Struct")]
+struct RelativeStruct {
+    string: String,
+    number: u32,
+    vec: Vec<f64>,
+}
+
+#[test]
+fn relative_vm_type() {
+    let vm = new_vm();
+
+    let src = r#"
+        type Struct = { string: String, number: Int, vec: Array Float }
+        { Struct }
+    "#;
+
+    vm.load_script("types", &src).unwrap();
+
+    assert_eq!(
+        <RelativeStruct as api::VmType>::make_type(&vm),
+        <Struct as api::VmType>::make_type(&vm)
+    );
+}
+
 #[derive(Pushable, VmType)]
 #[gluon(vm_type = "types.GenericStruct")]
 struct GenericStruct<T> {
@@ -161,12 +186,7 @@ fn new_lifetime_struct<'a>(_: ()) -> LifetimeStruct<'a> {
 fn lifetime_struct() {
     let vm = new_vm();
 
-    // make_source doesn't work with borrowed strings
-    let src = r#"
-        type LifetimeStruct = { string: String, other: Float }
-        { LifetimeStruct }
-    "#;
-
+    let src = api::typ::make_source::<LifetimeStruct<'static>>(&vm).unwrap();
     vm.load_script("types", &src).unwrap();
     import::add_extern_module(&vm, "functions", load_lifetime_struct_mod);
 
@@ -186,6 +206,19 @@ fn lifetime_struct() {
     }
 }
 
+#[test]
+fn lifetime_struct_source() {
+    let vm = new_vm();
+
+    let expected = r#"
+        type LifetimeStruct = { string : String, other : Float }
+        { LifetimeStruct }
+    "#;
+    let src = api::typ::make_source::<LifetimeStruct<'static>>(&vm).unwrap();
+    let normalize = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ");
+    assert_eq!(normalize(&src), normalize(expected));
+}
+
 #[derive(Pushable, VmType, Serialize, Deserialize)]
 #[gluon(vm_type = "types.Enum")]
 enum Enum {
@@ -250,3 +283,330 @@ fn normal_enum() {
         panic!("{}", why);
     }
 }
+
+#[derive(Pushable, Getable, VmType, Serialize, Deserialize)]
+#[gluon(vm_type = "types.Renamed")]
+struct Renamed {
+    #[gluon(rename = "count")]
+    #[serde(rename = "count")]
+    number: i32,
+}
+
+fn load_renamed_mod(vm: &Thread) -> vm::Result<ExternModule> {
+    let module = record! {
+        new_renamed => primitive!(1, new_renamed),
+        increment => primitive!(1, increment),
+    };
+
+    ExternModule::new(vm, module)
+}
+
+fn new_renamed(_: ()) -> Renamed {
+    Renamed { number: 1 }
+}
+
+fn increment(renamed: Renamed) -> Renamed {
+    Renamed {
+        number: renamed.number + 1,
+    }
+}
+
+#[test]
+fn renamed_field() {
+    let vm = new_vm();
+
+    let src = api::typ::make_source::<Renamed>(&vm).unwrap();
+    vm.load_script("types", &src).unwrap();
+    import::add_extern_module(&vm, "functions", load_renamed_mod);
+
+    let script = r#"
+        let { Renamed } = import! types
+        let { new_renamed, increment } = import! functions
+        let { assert } = import! std.test
+
+        let { count } = increment (new_renamed ())
+        assert (count == 2)
+    "#;
+
+    if let Err(why) = vm.run_expr::<()>("test", script) {
+        panic!("{}", why);
+    }
+}
+
+#[derive(Debug, PartialEq, Pushable, Getable, VmType, Serialize, Deserialize)]
+#[gluon(vm_type = "types.Tagged")]
+enum Tagged {
+    #[gluon(tag = "Empty")]
+    #[serde(rename = "Empty")]
+    Nothing,
+    Value(i32),
+}
+
+fn load_tagged_mod(vm: &Thread) -> vm::Result<ExternModule> {
+    let module = record! {
+        new_tagged => primitive!(1, new_tagged),
+    };
+
+    ExternModule::new(vm, module)
+}
+
+fn new_tagged(tag: u32) -> Tagged {
+    match tag {
+        0 => Tagged::Nothing,
+        _ => Tagged::Value(42),
+    }
+}
+
+#[test]
+fn enum_variant_tag() {
+    let vm = new_vm();
+
+    let src = api::typ::make_source::<Tagged>(&vm).unwrap();
+    vm.load_script("types", &src).unwrap();
+    import::add_extern_module(&vm, "functions", load_tagged_mod);
+
+    let script = r#"
+        let { Tagged } = import! types
+        let { new_tagged } = import! functions
+        let { assert } = import! std.test
+
+        let _ =
+            match new_tagged 0 with
+            | Empty -> ()
+            | Value _ -> assert False
+        match new_tagged 1 with
+        | Empty -> assert False
+        | Value x -> assert (x == 42)
+    "#;
+
+    if let Err(why) = vm.run_expr::<()>("test", script) {
+        panic!("{}", why);
+    }
+
+    let (value, _) = vm
+        .run_expr::<Tagged>(
+            "test_get",
+            r#"
+                let { Tagged } = import! types
+                Empty
+            "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(value, Tagged::Nothing);
+}
+
+#[derive(Debug, PartialEq, Pushable, Getable, VmType, Serialize, Deserialize)]
+struct Skipped {
+    number: i32,
+    #[gluon(skip)]
+    #[serde(skip)]
+    marker: ::std::marker::PhantomData<String>,
+}
+
+fn load_skipped_mod(vm: &Thread) -> vm::Result<ExternModule> {
+    let module = record! {
+        double_skipped => primitive!(1, double_skipped),
+    };
+
+    ExternModule::new(vm, module)
+}
+
+fn double_skipped(skipped: Skipped) -> Skipped {
+    Skipped {
+        number: skipped.number * 2,
+        marker: skipped.marker,
+    }
+}
+
+#[test]
+fn skipped_field() {
+    let vm = new_vm();
+
+    let src = api::typ::make_source::<Skipped>(&vm).unwrap();
+    assert!(!src.contains("marker"), "{}", src);
+    vm.load_script("types", &src).unwrap();
+    import::add_extern_module(&vm, "functions", load_skipped_mod);
+
+    let script = r#"
+        let { Skipped } = import! types
+        let { double_skipped } = import! functions
+
+        double_skipped { number = 21 }
+    "#;
+
+    let (skipped, _) = vm
+        .run_expr::<Skipped>("test", script)
+        .unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!(
+        skipped,
+        Skipped {
+            number: 42,
+            marker: ::std::marker::PhantomData,
+        }
+    );
+}
+
+#[derive(Debug, PartialEq, Pushable, Getable, VmType, Serialize, Deserialize)]
+struct Point(f64, f64);
+
+fn load_point_mod(vm: &Thread) -> vm::Result<ExternModule> {
+    let module = record! {
+        swap_point => primitive!(1, swap_point),
+    };
+
+    ExternModule::new(vm, module)
+}
+
+fn swap_point(Point(x, y): Point) -> Point {
+    Point(y, x)
+}
+
+#[test]
+fn tuple_struct() {
+    let vm = new_vm();
+
+    let src = api::typ::make_source::<Point>(&vm).unwrap();
+    assert!(src.contains("(Float, Float)"), "{}", src);
+    vm.load_script("types", &src).unwrap();
+    import::add_extern_module(&vm, "functions", load_point_mod);
+
+    let script = r#"
+        let { Point } = import! types
+        let { swap_point } = import! functions
+
+        let (x, y) = swap_point (1.0, 2.0)
+        let p : Point = (x, y #Float+ 1.0)
+        p
+    "#;
+
+    let (point, _) = vm
+        .run_expr::<Point>("test", script)
+        .unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!(point, Point(2.0, 2.0));
+}
+
+#[derive(Pushable, Getable, VmType, Serialize, Deserialize)]
+#[gluon(vm_type = "types.Optional")]
+struct Optional {
+    name: Option<String>,
+}
+
+#[derive(Pushable, Getable, VmType)]
+#[gluon(vm_type = "types.Fallible")]
+struct Fallible {
+    name: Option<String>,
+    result: Result<i32, String>,
+}
+
+fn load_optional_mod(vm: &Thread) -> vm::Result<ExternModule> {
+    let module = record! {
+        toggle_optional => primitive!(1, toggle_optional),
+    };
+
+    ExternModule::new(vm, module)
+}
+
+fn load_fallible_mod(vm: &Thread) -> vm::Result<ExternModule> {
+    let module = record! {
+        toggle_fallible => primitive!(1, toggle_fallible),
+    };
+
+    ExternModule::new(vm, module)
+}
+
+fn toggle_name(name: Option<String>) -> Option<String> {
+    match name {
+        Some(_) => None,
+        None => Some("default".to_string()),
+    }
+}
+
+fn toggle_optional(optional: Optional) -> Optional {
+    Optional {
+        name: toggle_name(optional.name),
+    }
+}
+
+fn toggle_fallible(fallible: Fallible) -> Fallible {
+    Fallible {
+        name: toggle_name(fallible.name),
+        result: match fallible.result {
+            Ok(i) => Err(i.to_string()),
+            Err(s) => Ok(s.len() as i32),
+        },
+    }
+}
+
+#[test]
+fn option_field() {
+    let vm = new_vm();
+
+    let src = api::typ::make_source::<Optional>(&vm).unwrap();
+    assert!(src.contains("Option String"), "{}", src);
+    vm.load_script("types", &src).unwrap();
+    import::add_extern_module(&vm, "functions", load_optional_mod);
+
+    let script = r#"
+        let { Optional } = import! types
+        let { toggle_optional } = import! functions
+        let { assert } = import! std.test
+
+        let name_or_empty name =
+            match name with
+            | Some name -> name
+            | None -> ""
+
+        let { name } = toggle_optional { name = None }
+        let _ = assert (name_or_empty name == "default")
+        let { name } = toggle_optional { name = Some "name" }
+        assert (name_or_empty name == "")
+    "#;
+
+    if let Err(why) = vm.run_expr::<()>("test", script) {
+        panic!("{}", why);
+    }
+}
+
+#[test]
+fn option_and_result_fields() {
+    let vm = new_vm();
+
+    let src = r#"
+        let { Result } = import! std.types
+        type Fallible = { name : Option String, result : Result String Int }
+        { Fallible }
+    "#;
+    vm.load_script("types", &src).unwrap();
+    import::add_extern_module(&vm, "functions", load_fallible_mod);
+
+    let script = r#"
+        let { Fallible } = import! types
+        let { Result } = import! std.types
+        let { toggle_fallible } = import! functions
+        let { assert } = import! std.test
+
+        let name_or_empty name =
+            match name with
+            | Some name -> name
+            | None -> ""
+        let is_err_with : Result String Int -> String -> Bool = \result expected ->
+            match result with
+            | Ok _ -> False
+            | Err s -> s == expected
+        let is_ok_with : Result String Int -> Int -> Bool = \result expected ->
+            match result with
+            | Ok i -> i == expected
+            | Err _ -> False
+
+        let { name, result } = toggle_fallible { name = Some "name", result = Ok 12 }
+        let _ = assert (name_or_empty name == "")
+        let _ = assert (is_err_with result "12")
+        let { name, result } = toggle_fallible { name = None, result = Err "abc" }
+        let _ = assert (name_or_empty name == "default")
+        assert (is_ok_with result 3)
+    "#;
+
+    if let Err(why) = vm.run_expr::<()>("test", script) {
+        panic!("{}", why);
+    }
+}
@@ -49,6 +49,38 @@ fn enum_() {
     );
 }
 
+#[derive(VmType)]
+#[allow(unused)]
+enum TaggedEnum {
+    #[gluon(tag = "Empty")]
+    Nothing,
+    Value(u32),
+}
+
+#[test]
+fn enum_variant_tag() {
+    let vm = new_vm();
+
+    assert_eq!(
+        TaggedEnum::make_type(&vm).to_string(),
+        "| Empty\n| Value Int"
+    );
+}
+
+#[derive(VmType)]
+#[allow(unused)]
+struct RenamedField {
+    #[gluon(rename = "count")]
+    number: u32,
+}
+
+#[test]
+fn renamed_field() {
+    let vm = new_vm();
+
+    assert_eq!(RenamedField::make_type(&vm).to_string(), "{ count : Int }");
+}
+
 #[derive(VmType)]
 #[allow(unused)]
 struct NewtypeInner(Struct);
@@ -83,3 +115,24 @@ fn newtype() {
         _ => panic!(),
     }
 }
+
+#[derive(VmType, Deserialize)]
+#[gluon(newtype)]
+#[allow(unused)]
+struct UserId(u32);
+
+#[test]
+fn newtype_source() {
+    let vm = new_vm();
+
+    match &*UserId::make_type(&vm) {
+        Type::Alias(alias) => {
+            assert_eq!(alias.name.declared_name(), "UserId");
+            assert_eq!(alias.unresolved_type().to_string(), "Int");
+        }
+        _ => panic!(),
+    }
+
+    let src = api::typ::make_source::<UserId>(&vm).unwrap();
+    assert!(src.contains("type UserId = Int"), "{}", src);
+}
@@ -6,6 +6,8 @@ extern crate gluon_vm;
 
 mod init;
 
+use std::collections::HashMap;
+
 use gluon::{
     import,
     vm::{self, ExternModule},
@@ -101,3 +103,104 @@ fn newtype() {
         })
     );
 }
+
+#[derive(Debug, PartialEq, Getable, Pushable, VmType)]
+#[gluon(newtype)]
+struct UserId(u32);
+
+#[derive(Debug, PartialEq, Getable, Pushable, VmType)]
+#[gluon(newtype)]
+struct GroupId {
+    id: u32,
+}
+
+fn load_user_id_mod(vm: &Thread) -> vm::Result<ExternModule> {
+    let module = record! {
+        user_id => primitive!(1, user_id),
+        group_id => primitive!(1, group_id),
+    };
+
+    ExternModule::new(vm, module)
+}
+
+fn user_id(id: u32) -> UserId {
+    UserId(id)
+}
+
+fn group_id(user: UserId) -> GroupId {
+    GroupId { id: user.0 * 10 }
+}
+
+#[test]
+fn newtype_attribute() {
+    let vm = new_vm();
+    import::add_extern_module(&vm, "user_id", load_user_id_mod);
+
+    // `#[gluon(newtype)]` structs are pushed and read as the wrapped type
+    let script = r#"
+        let { user_id, group_id } = import! user_id
+
+        let id : Int = user_id 3
+        group_id (id #Int+ 1)
+    "#;
+
+    let (group, _) = vm
+        .run_expr::<GroupId>("test", script)
+        .unwrap_or_else(|why| panic!("{}", why));
+
+    assert_eq!(group, GroupId { id: 40 });
+}
+
+#[derive(Debug, PartialEq, Getable, Pushable, VmType)]
+struct Counts {
+    counts: HashMap<String, u32>,
+}
+
+fn load_counts_mod(vm: &Thread) -> vm::Result<ExternModule> {
+    let module = record! {
+        make_counts => primitive!(1, make_counts),
+    };
+
+    ExternModule::new(vm, module)
+}
+
+fn make_counts(_: ()) -> Counts {
+    Counts {
+        counts: vec![("a".to_string(), 1), ("b".to_string(), 2)]
+            .into_iter()
+            .collect(),
+    }
+}
+
+#[test]
+fn hash_map_field() {
+    let vm = new_vm();
+    import::add_extern_module_with_deps(&vm, "counts", load_counts_mod, vec!["std.map".into()]);
+    // `Counts` refers to `std.map.Map` so it must be loaded before the expected type is created
+    vm.run_expr::<()>("load", "let _ = import! std.map in ()")
+        .unwrap_or_else(|why| panic!("{}", why));
+
+    // The field is a `std.map.Map String Int` on the gluon side
+    let script = r#"
+        let { insert_string } = import! std.map
+        let { make_counts } = import! counts
+
+        let c = make_counts ()
+        { counts = insert_string "c" 3 c.counts }
+    "#;
+
+    let (counts, _) = vm
+        .run_expr::<Counts>("test", script)
+        .unwrap_or_else(|why| panic!("{}", why));
+
+    assert_eq!(
+        counts.counts,
+        vec![
+            ("a".to_string(), 1),
+            ("b".to_string(), 2),
+            ("c".to_string(), 3)
+        ]
+        .into_iter()
+        .collect()
+    );
+}
@@ -9,7 +9,7 @@ use {
 use self::types::pretty_print as pretty_types;
 use base::{
     ast::{
-        Do, Expr, Literal, Pattern, PatternField, SpannedExpr, SpannedPattern, ValueBinding,
+        self, Do, Expr, Literal, Pattern, PatternField, SpannedExpr, SpannedPattern, ValueBinding,
         ValueBindings,
     },
     kind::Kind,
@@ -146,6 +146,16 @@ where
         let span = Span::new(previous_end, expr.span.start());
         let comments = self.comments(span);
         let doc = match expr.value {
+            // Suffixed literals (`5px`) are kept as they were written
+            Expr::App { ref func, .. }
+                if match func.value {
+                    Expr::Ident(ref id) => ast::literal_suffix(id.name.as_ref()).is_some(),
+                    _ => false,
+                } =>
+            {
+                arena.text(self.source.src_slice(expr.span))
+            }
+
             Expr::App {
                 ref implicit_args,
                 ref func,
@@ -41,8 +41,8 @@ use crate::{
 };
 
 pub use crate::{
-    infix::Error as InfixError, layout::Error as LayoutError, token::Error as TokenizeError,
-    token::Token,
+    infix::Error as InfixError, layout::Error as LayoutError, token::is_identifier,
+    token::Error as TokenizeError, token::Token,
 };
 
 lalrpop_mod!(
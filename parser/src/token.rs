@@ -27,6 +27,7 @@ pub enum Token<S> {
     IntLiteral(i64),
     ByteLiteral(u8),
     FloatLiteral(NotNan<f64>),
+    LiteralSuffix(S),
     DocComment(Comment<S>),
 
     Rec,
@@ -85,6 +86,7 @@ where
             IntLiteral(_) => "IntLiteral",
             ByteLiteral(_) => "ByteLiteral",
             FloatLiteral(_) => "FloatLiteral",
+            LiteralSuffix(_) => "LiteralSuffix",
             DocComment { .. } => "DocComment",
 
             Rec => "Rec",
@@ -146,6 +148,7 @@ impl<S> Token<S> {
             IntLiteral(x) => IntLiteral(x),
             ByteLiteral(x) => ByteLiteral(x),
             FloatLiteral(x) => FloatLiteral(x),
+            LiteralSuffix(s) => LiteralSuffix(f(s)),
             DocComment(Comment { typ, content }) => DocComment(Comment {
                 typ,
                 content: f(content),
@@ -299,6 +302,37 @@ fn is_ident_continue(ch: u8) -> bool {
     }
 }
 
+fn keyword<S>(ident: &str) -> Option<Token<S>> {
+    Some(match ident {
+        "rec" => Token::Rec,
+        "else" => Token::Else,
+        "forall" => Token::Forall,
+        "if" => Token::If,
+        "in" => Token::In,
+        "let" => Token::Let,
+        "do" => Token::Do,
+        "seq" => Token::Seq,
+        "match" => Token::Match,
+        "then" => Token::Then,
+        "type" => Token::Type,
+        "with" => Token::With,
+        _ => return None,
+    })
+}
+
+/// Returns true if `s` can be used as an identifier, that is, it is not empty, only consists of
+/// identifier characters and is not a keyword
+pub fn is_identifier(s: &str) -> bool {
+    match s.as_bytes().split_first() {
+        Some((&first, rest)) => {
+            is_ident_start(first)
+                && rest.iter().all(|&ch| is_ident_continue(ch))
+                && keyword::<&str>(s).is_none()
+        }
+        None => false,
+    }
+}
+
 fn is_digit(ch: u8) -> bool {
     (ch as char).is_digit(10)
 }
@@ -349,6 +383,8 @@ pub struct Tokenizer<'input> {
     input: &'input str,
     chars: CharLocations<'input>,
     start_index: BytePos,
+    /// Where the last token ended if it was a literal which may be followed by a suffix
+    literal_end: Option<BytePos>,
     pub errors: Errors<SpError>,
 }
 
@@ -363,6 +399,7 @@ impl<'input> Tokenizer<'input> {
             input: input.src(),
             chars,
             start_index: input.start_index(),
+            literal_end: None,
             errors: Errors::new(),
         }
     }
@@ -659,13 +696,6 @@ impl<'input> Tokenizer<'input> {
             Some((_, b'.')) => {
                 self.bump(); // Skip b'.'
                 let (end, float) = self.take_while(start, is_digit);
-                match self.lookahead() {
-                    Some((next, ch)) if is_ident_start(ch) => {
-                        let ch = self.chars.chars.as_str_suffix().restore_char(&[ch]);
-                        self.recover(end, next, UnexpectedChar(ch), ())?;
-                    }
-                    _ => (),
-                }
                 pos::spanned2(
                     start,
                     end,
@@ -727,16 +757,6 @@ impl<'input> Tokenizer<'input> {
                     self.recover(start, end, NonParseableInt, Token::ByteLiteral(0))?
                 }
             }
-            Some((start, ch)) if is_ident_start(ch) => {
-                let ch = self.chars.chars.as_str_suffix().restore_char(&[ch]);
-                self.recover(start, start, UnexpectedChar(ch), ())?;
-
-                if let Ok(val) = int.parse() {
-                    pos::spanned2(start, end, Token::IntLiteral(val))
-                } else {
-                    self.recover(start, end, NonParseableInt, Token::IntLiteral(0))?
-                }
-            }
             None | Some(_) => {
                 if let Ok(val) = int.parse() {
                     pos::spanned2(start, end, Token::IntLiteral(val))
@@ -759,30 +779,21 @@ impl<'input> Tokenizer<'input> {
             _ => (),
         }
 
-        let token = match ident {
-            "rec" => Token::Rec,
-            "else" => Token::Else,
-            "forall" => Token::Forall,
-            "if" => Token::If,
-            "in" => Token::In,
-            "let" => Token::Let,
-            "do" => Token::Do,
-            "seq" => Token::Seq,
-            "match" => Token::Match,
-            "then" => Token::Then,
-            "type" => Token::Type,
-            "with" => Token::With,
-            src => Token::Identifier(src),
-        };
+        let token = keyword(ident).unwrap_or(Token::Identifier(ident));
 
         Ok(pos::spanned2(start, end, token))
     }
-}
 
-impl<'input> Iterator for Tokenizer<'input> {
-    type Item = Result<SpannedToken<'input>, SpError>;
+    fn literal_suffix(&mut self, start: Location) -> Result<SpannedToken<'input>, SpError> {
+        let (end, suffix) = self.take_while(start, is_ident_continue);
+        // Suffixes can't be macros
+        if let Some((pos, b'!')) = self.lookahead() {
+            self.recover(pos, pos, UnexpectedChar('!'), ())?;
+        }
+        Ok(pos::spanned2(start, end, Token::LiteralSuffix(suffix)))
+    }
 
-    fn next(&mut self) -> Option<Result<SpannedToken<'input>, SpError>> {
+    fn next_token(&mut self) -> Option<Result<SpannedToken<'input>, SpError>> {
         while let Some((start, ch)) = self.bump() {
             return match ch {
                 b',' => Some(Ok(pos::spanned2(start, self.next_loc(), Token::Comma))),
@@ -827,6 +838,9 @@ impl<'input> Iterator for Tokenizer<'input> {
                         Token::AttributeOpen,
                     )))
                 }
+                ch if is_ident_start(ch) && self.literal_end == Some(start.absolute) => {
+                    Some(self.literal_suffix(start))
+                }
                 ch if is_ident_start(ch) => Some(self.identifier(start)),
                 ch if is_digit(ch) || (ch == b'-' && self.test_lookahead(is_digit)) => {
                     Some(self.numeric_literal(start))
@@ -853,6 +867,25 @@ impl<'input> Iterator for Tokenizer<'input> {
     }
 }
 
+impl<'input> Iterator for Tokenizer<'input> {
+    type Item = Result<SpannedToken<'input>, SpError>;
+
+    fn next(&mut self) -> Option<Result<SpannedToken<'input>, SpError>> {
+        let token = self.next_token();
+        // An identifier directly after one of these literals is lexed as its suffix (`5px`)
+        self.literal_end = match &token {
+            Some(Ok(token)) => match token.value {
+                Token::StringLiteral(_) | Token::IntLiteral(_) | Token::FloatLiteral(_) => {
+                    Some(token.span.end().absolute)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        token
+    }
+}
+
 /// Converts partial hex literal (i.e. part after `0x` or `-0x`) to 64 bit signed integer.
 ///
 /// This is basically a copy and adaptation of `std::num::from_str_radix`.
@@ -1303,11 +1336,55 @@ mod test {
         );
     }
 
+    #[test]
+    fn literal_suffixes() {
+        test(
+            r#"12.3a 5px"#,
+            vec![
+                (r#"~~~~     "#, FloatLiteral(NotNan::new(12.3).unwrap())),
+                (r#"    ~    "#, LiteralSuffix("a")),
+                (r#"      ~  "#, IntLiteral(5)),
+                (r#"       ~~"#, LiteralSuffix("px")),
+            ],
+        );
+    }
+
     #[test]
     fn float_literals_unexpected_char() {
+        // A letter directly after a literal used to be an error but is now lexed as a suffix
+        assert_eq!(
+            tokenizer(r#"12.3a"#).nth(1),
+            Some(Ok(pos::spanned2(loc(4), loc(5), LiteralSuffix("a"))))
+        );
         assert_eq!(
-            tokenizer(r#"12.3a"#).next(),
-            Some(error(loc(4), UnexpectedChar('a')))
+            tokenizer(r#"12.3a!"#).nth(1),
+            Some(error(loc(5), UnexpectedChar('!')))
+        );
+        assert_eq!(
+            tokenizer(r#"5px!"#).nth(1),
+            Some(error(loc(3), UnexpectedChar('!')))
+        );
+    }
+
+    #[test]
+    fn literal_suffix_after_whitespace() {
+        test(
+            r#"5 px"#,
+            vec![(r#"~   "#, IntLiteral(5)), (r#"  ~~"#, Identifier("px"))],
+        );
+    }
+
+    #[test]
+    fn string_literal_suffixes() {
+        test(
+            r#""abc"sql"#,
+            vec![
+                (
+                    r#"~~~~~   "#,
+                    Token::StringLiteral(StringLiteral::Escaped("abc")),
+                ),
+                (r#"     ~~~"#, LiteralSuffix("sql")),
+            ],
         );
     }
 
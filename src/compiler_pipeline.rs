@@ -29,13 +29,14 @@ use crate::{
         symbol::{Name, NameBuf, Symbol, SymbolModule},
         types::{ArcType, NullInterner, Type, TypeCache},
     },
-    check::{metadata, rename},
+    check::{deprecation, metadata, rename},
     query::{env, AsyncCompilation, Compilation},
     vm::{
         compiler::CompiledModule,
         core::{self, interpreter, CoreExpr},
         macros::MacroExpander,
         thread::{RootedThread, RootedValue, Thread, ThreadInternal, VmRoot},
+        types::Instruction,
     },
     Error, ModuleCompiler, Result,
 };
@@ -99,7 +100,7 @@ pub fn parse_expr_inner<'ast>(
     file: &str,
     expr_str: &str,
 ) -> SalvageResult<SpannedExpr<'ast, Symbol>, InFile<parser::Error>> {
-    let map = compiler.add_filemap(file, expr_str);
+    let map = compiler.add_source_filemap(file, expr_str);
     parser::parse_partial_expr(
         arena,
         &mut SymbolModule::new(file.into(), &mut compiler.symbols),
@@ -195,6 +196,8 @@ impl<'s> MacroExpandable for &'s mut OwnedExpr<Symbol> {
         }
 
         let result = {
+            let settings = compiler.compiler_settings();
+
             struct Forker<'a, 'b, 'c>(
                 salsa::Forker<&'b mut salsa::OwnedDb<'a, dyn Compilation + 'c>>,
             );
@@ -208,7 +211,10 @@ impl<'s> MacroExpandable for &'s mut OwnedExpr<Symbol> {
             let spawner = thread.spawner();
 
             let (arena, expr) = self.arena_expr();
-            let mut macros = MacroExpander::new(thread, &mut forker, spawner);
+            let mut macros = MacroExpander::new(thread, &mut forker, spawner)
+                .with_expected_type(compiler.expected_type.clone())
+                .with_module(file);
+            *macros.state_entry(crate::SETTINGS_STATE) = settings;
             macros.run(&mut compiler.symbols, arena, expr).await;
             macros.finish()
         };
@@ -305,12 +311,17 @@ where
         file: &str,
         expr_str: &str,
     ) -> SalvageResult<Renamed<Self::Expr>> {
-        let source = compiler.get_or_insert_filemap(file, expr_str);
+        let source = compiler.get_or_insert_source_filemap(file, expr_str);
+        let canonical_symbols = compiler.compiler_settings().canonical_symbols;
         let mut symbols = SymbolModule::new(String::from(file), &mut compiler.symbols);
 
-        self.expr
-            .borrow_mut()
-            .with_arena(|arena, expr| rename::rename(&*source, &mut symbols, arena.borrow(), expr));
+        self.expr.borrow_mut().with_arena(|arena, expr| {
+            if canonical_symbols {
+                rename::rename_canonical(&*source, &mut symbols, arena.borrow(), expr)
+            } else {
+                rename::rename(&*source, &mut symbols, arena.borrow(), expr)
+            }
+        });
         Ok(Renamed { expr: self.expr })
     }
 }
@@ -490,6 +501,11 @@ impl<E> TypecheckValue<E> {
             metadata,
         }
     }
+
+    /// Discards the results of typechecking, returning only the (typechecked) expression
+    pub fn into_parsed(self) -> E {
+        self.expr
+    }
 }
 
 #[async_trait::async_trait]
@@ -532,39 +548,89 @@ where
         expr_str: &str,
         expected_type: Option<&ArcType>,
     ) -> SalvageResult<TypecheckValue<Self::Expr>> {
-        join_result!(
-            self.reparse_infix(compiler, thread, file, expr_str).await,
-            |expr| expr
-                .typecheck_expected(compiler, thread, file, expr_str, expected_type)
-                .await,
-        )
+        // Lets macros see the expected type of the whole expression
+        compiler.expected_type = expected_type.cloned();
+        let reparsed = self.reparse_infix(compiler, thread, file, expr_str).await;
+        compiler.expected_type = None;
+        join_result!(reparsed, |expr| expr
+            .typecheck_expected(compiler, thread, file, expr_str, expected_type)
+            .await,)
     }
 }
 
 fn typecheck_expr(
     expr: &mut OwnedExpr<Symbol>,
     compiler: &mut ModuleCompiler<'_, '_>,
-    thread: &Thread,
+    type_cache: &TypeCache<Symbol, ArcType>,
     file: &str,
     expected_type: Option<&ArcType>,
     metadata_map: &mut FnvMap<Symbol, Arc<Metadata>>,
 ) -> Result<ArcType> {
     use crate::check::typecheck::Typecheck;
     let env = env(&*compiler.database);
+    let strict_conversions = compiler.compiler_settings().strict_conversions;
     let (arena, expr) = expr.arena_expr();
     let mut tc = Typecheck::new(
         file.into(),
         &mut compiler.symbols,
         &env,
-        &thread.global_env().type_cache(),
+        type_cache,
         metadata_map,
         arena.borrow(),
     );
+    tc.set_strict_conversions(strict_conversions);
 
     tc.typecheck_expr_expected(expr, expected_type)
         .map_err(|err| InFile::new(compiler.database.state().code_map.clone(), err).into())
 }
 
+/// Runs every stage up to and including typechecking without a thread, using only the types of
+/// the modules which have already been loaded (see `CompilerDatabase::check_expr`)
+pub(crate) fn typecheck_without_thread(
+    compiler: &mut ModuleCompiler<'_, '_>,
+    type_cache: &TypeCache<Symbol, ArcType>,
+    file: &str,
+    expr_str: &str,
+) -> Result<ArcType> {
+    let mut expr = parse_expr(compiler, type_cache, file, expr_str).map_err(|err| err.error)?;
+
+    let mut metadata_map = {
+        let (arena, expr) = expr.arena_expr();
+        if compiler.compiler_settings().implicit_prelude
+            && !expr_str.starts_with("//@NO-IMPLICIT-PRELUDE")
+        {
+            compiler.include_implicit_prelude(arena.borrow(), type_cache, file, expr);
+        }
+
+        crate::import::expand_loaded_imports(&*compiler.database, file, arena.borrow(), expr)
+            .map_err(|errors| InFile::new(compiler.code_map().clone(), errors))?;
+
+        let source = compiler.get_or_insert_source_filemap(file, expr_str);
+        let mut symbols = SymbolModule::new(String::from(file), &mut compiler.symbols);
+        if compiler.database.compiler_settings().canonical_symbols {
+            rename::rename_canonical(&*source, &mut symbols, arena.borrow(), expr)
+        } else {
+            rename::rename(&*source, &mut symbols, arena.borrow(), expr)
+        }
+
+        let env = env(&*compiler.database);
+        let (_, metadata_map) = metadata::metadata(&env, expr);
+
+        crate::parser::reparse_infix(arena.borrow(), &metadata_map, &compiler.symbols, expr)
+            .map_err(|err| InFile::new(compiler.code_map().clone(), err))?;
+        metadata_map
+    };
+
+    typecheck_expr(
+        &mut expr,
+        compiler,
+        type_cache,
+        file,
+        None,
+        &mut metadata_map,
+    )
+}
+
 #[async_trait::async_trait]
 impl<E> Typecheckable for InfixReparsed<E>
 where
@@ -591,7 +657,7 @@ where
         let typ = match typecheck_expr(
             expr.borrow_mut(),
             compiler,
-            thread,
+            thread.global_env().type_cache(),
             file,
             expected_type,
             &mut metadata_map,
@@ -623,6 +689,19 @@ where
             (metadata, metadata_map)
         };
 
+        if compiler.compiler_settings().deprecation_warnings {
+            let env = env(&*compiler.database);
+            let warnings = deprecation::deprecated_uses(&env, &metadata_map, expr.borrow().expr());
+            let mut state = compiler.state();
+            if warnings.has_errors() {
+                let warnings = InFile::new(state.code_map.clone(), warnings);
+                warn!("{}", warnings);
+                state.warnings.insert(file.into(), warnings);
+            } else {
+                state.warnings.remove(file);
+            }
+        }
+
         Ok(TypecheckValue {
             expr,
             typ,
@@ -659,6 +738,38 @@ impl<E> CompileValue<E> {
             module,
         }
     }
+
+    /// Returns the bytecode of the top-level function of the compiled module
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.module.function.instructions
+    }
+
+    /// Runs the compiled module on `vm` without registering it as a module or running the
+    /// resulting value if it is an `IO` action.
+    pub async fn execute<'vm, T>(self, vm: T, name: &str) -> Result<ExecuteValue<RootedThread, E>>
+    where
+        T: Send + Sync + VmRoot<'vm>,
+    {
+        let CompileValue {
+            expr,
+            core_expr: _,
+            typ,
+            mut module,
+            metadata,
+        } = self;
+        let module_id = Symbol::from(format!("@{}", name));
+        module.function.id = module_id.clone();
+        let closure = vm.global_env().new_global_thunk(&vm, module)?;
+
+        let value = vm.call_thunk_top(&closure).await.map_err(Error::from)?;
+        Ok(ExecuteValue {
+            id: module_id,
+            expr,
+            typ,
+            value,
+            metadata,
+        })
+    }
 }
 
 #[async_trait::async_trait]
@@ -790,7 +901,7 @@ where
             debug!("Optimization returned: {}", core_expr);
 
             let source = compiler
-                .get_filemap(filename)
+                .get_source_filemap(filename)
                 .expect("Filemap does not exist");
 
             let name = Name::new(filename);
@@ -932,27 +1043,8 @@ where
         T: Send + Sync + VmRoot<'vm>,
         'vm: 'async_trait,
     {
-        let CompileValue {
-            expr,
-            core_expr: _,
-            typ,
-            mut module,
-            metadata,
-        } = self;
         let run_io = compiler.database.compiler_settings().run_io;
-        let module_id = Symbol::from(format!("@{}", name));
-        module.function.id = module_id.clone();
-        let closure = vm.global_env().new_global_thunk(&vm, module)?;
-
-        let vm1 = vm.clone();
-        let value = vm1.call_thunk_top(&closure).await.map_err(Error::from)?;
-        let v = ExecuteValue {
-            id: module_id,
-            expr,
-            typ,
-            value,
-            metadata,
-        };
+        let v = self.execute(vm.clone(), name).await?;
         if run_io {
             crate::compiler_pipeline::run_io(vm, v).await
         } else {
@@ -1026,7 +1118,7 @@ where
 
     async fn run_expr<T>(
         self,
-        _compiler: &mut ModuleCompiler<'_, '_>,
+        compiler: &mut ModuleCompiler<'_, '_>,
         vm: T,
         filename: &str,
         _expr_str: &str,
@@ -1046,6 +1138,15 @@ where
             return Err(format!("filenames do not match `{}` != `{}`", filename, module_id).into());
         }
 
+        // The imported modules may not have been loaded since the module was compiled
+        for global in &module.module.module_globals {
+            compiler
+                .database
+                .import(global.definition_name().into())
+                .await
+                .map_err(|salvage| salvage.error)?;
+        }
+
         let typ = module.typ;
         let metadata = module.metadata;
         let closure = vm.global_env().new_global_thunk(&vm, module.module)?;
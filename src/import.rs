@@ -3,8 +3,9 @@
 use std::{
     any::{Any, TypeId},
     borrow::Cow,
+    collections::BTreeSet,
     fs::File,
-    io::Read,
+    io::{self, Read},
     mem,
     ops::{Deref, DerefMut},
     path::PathBuf,
@@ -22,17 +23,24 @@ use {
 };
 
 use crate::base::{
-    ast::{self, expr_to_path, Expr, Literal, SpannedExpr},
-    filename_to_module, pos,
+    ast::{
+        self, expr_to_path, Expr, ExprField, Literal, MutVisitor, SpannedExpr, TypedIdent, Visitor,
+    },
+    error::Errors,
+    filename_to_module,
+    pos::{self, BytePos, Spanned},
     source::FileId,
     symbol::{Symbol, Symbols},
-    types::ArcType,
+    types::{ArcType, Type},
 };
 
 use crate::vm::{
     self,
     gc::Trace,
-    macros::{Error as MacroError, LazyMacroResult, Macro, MacroExpander, MacroFuture},
+    macros::{
+        Error as MacroError, LazyMacroResult, Macro, MacroDependencies, MacroDoc, MacroExpander,
+        MacroFuture,
+    },
     thread::{RootedThread, Thread},
     vm::VmEnv,
     ExternLoader, ExternModule,
@@ -40,7 +48,7 @@ use crate::vm::{
 
 use crate::{
     compiler_pipeline::{Salvage, SalvageResult},
-    query::{AsyncCompilation, Compilation, CompilerDatabase},
+    query::{AsyncCompilation, Compilation, CompilerDatabase, ExternLoaderQuery, ModuleTextQuery},
     IoError, ModuleCompiler, ThreadExt,
 };
 
@@ -48,12 +56,37 @@ quick_error! {
     /// Error type for the import macro
     #[derive(Debug, Clone, Eq, PartialEq, Hash)]
     pub enum Error {
-        /// The importer found a cyclic dependency when loading files
-        CyclicDependency(module: String, cycle: Vec<String>) {
+        /// The module is an extern module which is not in `Settings::allowed_extern_modules`
+        NotPermitted(module: String) {
+            display("Module '{}' is not permitted to be imported", module)
+        }
+        /// The argument to `import!` was an expression instead of a module path or string literal
+        NonLiteralArgument {
             display(
-                "Module '{}' occurs in a cyclic dependency: `{}`",
+                "`import!` expects a module path such as `std.list` or a string literal such as \
+                `\"std/list.glu\"`. Module paths must be literals and can not be computed by an \
+                expression"
+            )
+        }
+        /// A relative import (`import! "./sibling.glu"`) could not be resolved against the
+        /// importing module
+        InvalidRelativeImport(path: String) {
+            display(
+                "The relative import `{}` can only be used inside a module and may not refer to \
+                modules outside of the module root",
+                path
+            )
+        }
+        /// `import_all!` did not find any modules under the prefix
+        NoSubmodules(prefix: String) {
+            display("Could not find any modules under '{}'", prefix)
+        }
+        /// `import_all!` found a module whose name can't be used as a field of the record
+        InvalidFieldName(module: String, field: String) {
+            display(
+                "Module '{}' can not be imported by `import_all!` as `{}` is not a valid identifier",
                 module,
-                cycle.iter().chain(Some(module)).format(" -> ")
+                field
             )
         }
         /// Generic message error
@@ -77,6 +110,49 @@ impl base::error::AsDiagnostic for Error {
     }
 }
 
+quick_error! {
+    /// Error returned when the source of an imported module could not be loaded
+    #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+    pub enum ImportError {
+        /// The module could not be found in the standard library or any of the import paths
+        NotFound(module: String, searched: Vec<String>) {
+            display(
+                "Could not find module '{}'. Searched {}.",
+                module,
+                searched.iter().map(|p| format!("`{}`", p)).format(", ")
+            )
+        }
+        /// The file of the module exists but could not be opened due to missing permissions
+        PermissionDenied(module: String, path: String) {
+            display("Permission denied when opening `{}` for module '{}'", path, module)
+        }
+        /// The module imports itself, directly or through the modules in `cycle`
+        Cyclic(module: String, cycle: Vec<String>) {
+            display(
+                "Module '{}' occurs in a cyclic dependency: `{}`",
+                module,
+                cycle.iter().chain(Some(module)).format(" -> ")
+            )
+        }
+    }
+}
+
+impl base::error::AsDiagnostic for ImportError {
+    fn as_diagnostic(
+        &self,
+        _map: &base::source::CodeMap,
+    ) -> codespan_reporting::diagnostic::Diagnostic<FileId> {
+        let diagnostic =
+            codespan_reporting::diagnostic::Diagnostic::error().with_message(self.to_string());
+        match self {
+            ImportError::NotFound(..) => diagnostic.with_notes(vec![
+                "Add the directory containing the module with `Import::add_path`".into(),
+            ]),
+            ImportError::PermissionDenied(..) | ImportError::Cyclic(..) => diagnostic,
+        }
+    }
+}
+
 include!(concat!(env!("OUT_DIR"), "/std_modules.rs"));
 
 #[async_trait]
@@ -89,6 +165,22 @@ pub trait Importer: Any + Clone + Sync + Send {
     ) -> SalvageResult<ArcType, crate::Error>;
 }
 
+/// Provides the source code of modules from somewhere other than the import paths, for instance
+/// an in-memory filesystem or files embedded in the executable.
+#[async_trait]
+pub trait ModuleSourceProvider: Send + Sync {
+    /// Returns the source of `module` (stored in `filename`) or `None` if this provider does not
+    /// contain the module, in which case the next provider is queried.
+    fn get(&self, module: &str, filename: &str) -> Option<Result<String, Error>>;
+
+    /// Asynchronous version of `get` which `import!` awaits before loading a module, letting
+    /// providers which fetch sources from, for instance, the network avoid blocking.
+    /// `get` is still used if the module is loaded without going through `import!`.
+    async fn async_get(&self, module: &str, filename: &str) -> Option<Result<String, Error>> {
+        self.get(module, filename)
+    }
+}
+
 #[derive(Clone)]
 pub struct DefaultImporter;
 #[async_trait]
@@ -126,6 +218,7 @@ impl Drop for DatabaseMut {
         // The compiler moves back to only be owned by the import so we need to remove the thread
         // to break the cycle
         self.thread = None;
+        self.global_env = None;
         self.compiler.take();
     }
 }
@@ -150,7 +243,7 @@ pub(crate) trait ImportApi: Send + Sync {
         use_standard_lib: bool,
         module: &str,
         filename: &str,
-    ) -> Result<Cow<'static, str>, Error>;
+    ) -> Result<Cow<'static, str>, MacroError>;
     async fn load_module(
         &self,
         compiler: &mut ModuleCompiler<'_, '_>,
@@ -163,6 +256,7 @@ pub(crate) trait ImportApi: Send + Sync {
         forker: salsa::ForkState,
         thread: RootedThread,
     ) -> salsa::Snapshot<CompilerDatabase>;
+    fn submodules(&self, db: &CompilerDatabase, prefix: &str) -> Vec<String>;
 }
 
 #[async_trait]
@@ -175,7 +269,7 @@ where
         use_standard_lib: bool,
         module: &str,
         filename: &str,
-    ) -> Result<Cow<'static, str>, Error> {
+    ) -> Result<Cow<'static, str>, MacroError> {
         Self::get_module_source(self, use_standard_lib, module, filename)
     }
     async fn load_module(
@@ -199,12 +293,16 @@ where
     ) -> salsa::Snapshot<CompilerDatabase> {
         Self::fork(self, forker, thread)
     }
+    fn submodules(&self, db: &CompilerDatabase, prefix: &str) -> Vec<String> {
+        Self::submodules(self, db, prefix)
+    }
 }
 
 /// Macro which rewrites occurances of `import! "filename"` to a load of that file if it is not
 /// already loaded and then a global access to the loaded module
 pub struct Import<I = DefaultImporter> {
     pub paths: RwLock<Vec<PathBuf>>,
+    pub providers: RwLock<Vec<Arc<dyn ModuleSourceProvider>>>,
     pub importer: I,
 
     pub compiler: Mutex<CompilerDatabase>,
@@ -245,6 +343,7 @@ impl<I> Import<I> {
     pub fn new(importer: I) -> Import<I> {
         Import {
             paths: RwLock::new(vec![PathBuf::from(".")]),
+            providers: RwLock::new(Vec::new()),
             compiler: CompilerDatabase::new_base(None).into(),
             importer: importer,
         }
@@ -259,6 +358,22 @@ impl<I> Import<I> {
         *self.paths.write().unwrap() = paths;
     }
 
+    /// Adds a provider of module sources. Providers are queried in the order they were added
+    /// before the standard library and the import paths, which are only used if no provider
+    /// returns the module.
+    pub fn add_source_provider(&self, provider: Arc<dyn ModuleSourceProvider>) {
+        self.providers.write().unwrap().push(provider);
+    }
+
+    /// Sets a callback which is called with the name of a module whenever that module is added,
+    /// changed or removed, or otherwise has its revision bumped (see
+    /// `CompilerDatabase::module_revision`).
+    ///
+    /// The callback is called while the database is locked so it must not access the database.
+    pub fn set_module_changed_callback(&self, callback: Box<dyn Fn(&str) + Send + Sync>) {
+        self.compiler.lock().unwrap().state().module_changed = Some(callback.into());
+    }
+
     pub fn modules(&self, compiler: &mut ModuleCompiler<'_, '_>) -> Vec<Cow<'static, str>> {
         STD_LIBS
             .iter()
@@ -288,6 +403,7 @@ impl<I> Import<I> {
             }
         };
 
+        compiler.global_env = Some(thread.global_env().clone());
         compiler.thread = Some(thread);
 
         compiler
@@ -305,14 +421,119 @@ impl<I> Import<I> {
         self.compiler.lock().unwrap().fork(forker, thread)
     }
 
+    /// Returns the modules directly under `prefix` (`std.json` but not `std.json.de` for the
+    /// prefix `std`) which are known to `db`, embedded in the standard library or stored as
+    /// files in one of the import paths. Modules which are only available through a
+    /// `ModuleSourceProvider` can't be listed and are not included.
+    pub fn submodules(&self, db: &CompilerDatabase, prefix: &str) -> Vec<String> {
+        let is_submodule = |module: &str| {
+            module.len() > prefix.len() + 1
+                && module.starts_with(prefix)
+                && module.as_bytes()[prefix.len()] == b'.'
+                && !module[prefix.len() + 1..].contains('.')
+        };
+
+        let mut modules: Vec<String> = self
+            .modules_in_paths(prefix)
+            .into_iter()
+            .chain(db.state().inline_modules.keys().cloned())
+            .chain(
+                crate::query::ExternLoaderQuery
+                    .in_db(db)
+                    .entries::<Vec<_>>()
+                    .into_iter()
+                    .map(|entry| entry.key),
+            )
+            .chain(if db.compiler_settings().use_standard_lib {
+                STD_LIBS.iter().map(|t| t.0.to_string()).collect()
+            } else {
+                Vec::new()
+            })
+            .filter(|module| is_submodule(module))
+            .collect();
+        modules.sort();
+        modules.dedup();
+        modules
+    }
+
+    fn modules_in_paths(&self, prefix: &str) -> Vec<String> {
+        let dir = prefix.replace('.', "/");
+        self.paths
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|path| std::fs::read_dir(path.join(&dir)).ok())
+            .flat_map(|entries| entries.filter_map(|entry| entry.ok()))
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension()? != "glu" {
+                    return None;
+                }
+                Some(format!("{}.{}", prefix, path.file_stem()?.to_str()?))
+            })
+            .collect()
+    }
+
+    /// Returns a future which fetches the source of `module` with
+    /// `ModuleSourceProvider::async_get`, or `None` if no provider needs to be queried because
+    /// the source of `module` is already known.
+    fn fetch_provided_source(
+        &self,
+        db: &CompilerDatabase,
+        module: &str,
+    ) -> Option<future::BoxFuture<'static, Option<Result<String, Error>>>> {
+        let providers = self.providers.read().unwrap().clone();
+        let known = {
+            let state = db.state();
+            state.inline_modules.contains_key(module) || state.provided_sources.contains_key(module)
+        };
+        if providers.is_empty()
+            || known
+            || ModuleTextQuery
+                .in_db(db)
+                .peek(&module.to_string())
+                .is_some()
+            || ExternLoaderQuery
+                .in_db(db)
+                .peek(&module.to_string())
+                .is_some()
+        {
+            return None;
+        }
+
+        let module = module.to_string();
+        let filename = format!("{}.glu", module.replace(".", "/"));
+        Some(
+            async move {
+                for provider in &providers {
+                    if let Some(source) = provider.async_get(&module, &filename).await {
+                        return Some(source);
+                    }
+                }
+                None
+            }
+            .boxed(),
+        )
+    }
+
     pub(crate) fn get_module_source(
         &self,
         use_standard_lib: bool,
         module: &str,
         filename: &str,
-    ) -> Result<Cow<'static, str>, Error> {
+    ) -> Result<Cow<'static, str>, MacroError> {
         let mut buffer = String::new();
 
+        let provided = self
+            .providers
+            .read()
+            .unwrap()
+            .iter()
+            .find_map(|provider| provider.get(module, filename));
+        if let Some(source) = provided {
+            return source.map(Cow::Owned).map_err(MacroError::new);
+        }
+
         // Retrieve the source, first looking in the standard library included in the
         // binary
 
@@ -330,23 +551,25 @@ impl<I> Import<I> {
                     .filter_map(|p| {
                         let base = p.join(filename);
                         match File::open(&base) {
-                            Ok(file) => Some(file),
+                            Ok(file) => Some(Ok(file)),
+                            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                                Some(Err(MacroError::new(ImportError::PermissionDenied(
+                                    module.to_string(),
+                                    base.display().to_string(),
+                                ))))
+                            }
                             Err(_) => None,
                         }
                     })
                     .next();
                 let mut file = file.ok_or_else(|| {
-                    Error::String(format!(
-                        "Could not find module '{}'. Searched {}.",
-                        module,
-                        paths
-                            .iter()
-                            .map(|p| format!("`{}`", p.display()))
-                            .format(", ")
+                    MacroError::new(ImportError::NotFound(
+                        module.to_string(),
+                        paths.iter().map(|p| p.display().to_string()).collect(),
                     ))
-                })?;
+                })??;
                 file.read_to_string(&mut buffer)
-                    .map_err(|err| Error::IO(err.into()))?;
+                    .map_err(|err| MacroError::new(Error::IO(err.into())))?;
                 Cow::Owned(buffer)
             }
         })
@@ -420,9 +643,9 @@ pub fn add_extern_module_with_deps<F>(
 }
 
 fn add_extern_module_(thread: &Thread, name: &str, loader: ExternLoader) {
-    thread
-        .get_database_mut()
-        .set_extern_loader(name.into(), PtrEq(Arc::new(loader)));
+    thread.get_database_mut().change_inputs(&[], |db| {
+        db.set_extern_loader(name.into(), PtrEq(Arc::new(loader)))
+    });
 }
 
 macro_rules! add_extern_module_if {
@@ -453,6 +676,199 @@ macro_rules! add_extern_module_if {
     }};
 }
 
+/// Resolves a relative import path such as `./sibling.glu` or `../parent` against the directory of
+/// `current_module`.
+fn resolve_relative_module(current_module: Option<&str>, path: &str) -> Result<String, Error> {
+    let invalid = || Error::InvalidRelativeImport(path.into());
+
+    let mut components = current_module
+        .ok_or_else(invalid)?
+        .split('.')
+        .collect::<Vec<_>>();
+    // Drop the importing module itself, leaving the "directory" it is in
+    components.pop();
+
+    let path = path.trim_end_matches(".glu");
+    for component in path.split(|c: char| c == '/' || c == '\\') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop().ok_or_else(invalid)?;
+            }
+            component => components.push(component),
+        }
+    }
+    if components.is_empty() {
+        return Err(invalid());
+    }
+    Ok(components.join("."))
+}
+
+fn get_module_name(
+    current_module: Option<&str>,
+    args: &[SpannedExpr<Symbol>],
+) -> Result<String, Error> {
+    if args.len() != 1 {
+        return Err(Error::String("Expected import to get 1 argument".into()).into());
+    }
+
+    let modulename = match args[0].value {
+        Expr::Ident(_) | Expr::Projection(..) => {
+            let mut modulename = String::new();
+            expr_to_path(&args[0], &mut modulename).map_err(|_| Error::NonLiteralArgument)?;
+            modulename
+        }
+        Expr::Literal(Literal::String(ref filename))
+            if filename.starts_with("./") || filename.starts_with("../") =>
+        {
+            resolve_relative_module(current_module, filename)?
+        }
+        Expr::Literal(Literal::String(ref filename)) => filename_to_module(filename),
+        _ => return Err(Error::NonLiteralArgument),
+    };
+    Ok(modulename)
+}
+
+struct ImportedModules<'m> {
+    module: &'m str,
+    imports: BTreeSet<String>,
+}
+
+impl<'a, 'ast> Visitor<'a, 'ast> for ImportedModules<'_> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+        if let Expr::App { func, args, .. } = &expr.value {
+            if let Expr::Ident(id) = &func.value {
+                if id.name.as_str() == "import!" {
+                    if let Ok(modulename) = get_module_name(Some(self.module), args) {
+                        self.imports.insert(modulename);
+                    }
+                }
+            }
+        }
+        ast::walk_expr(self, expr)
+    }
+}
+
+/// Returns the modules imported by the `import!` calls in `expr`, which belongs to `module`,
+/// without expanding any macros
+pub(crate) fn imported_modules(module: &str, expr: &SpannedExpr<Symbol>) -> BTreeSet<String> {
+    let mut visitor = ImportedModules {
+        module,
+        imports: BTreeSet::new(),
+    };
+    visitor.visit_expr(expr);
+    visitor.imports
+}
+
+struct LoadedImports<'a, 'ast> {
+    db: &'a dyn Compilation,
+    module: &'a str,
+    arena: ast::ArenaRef<'a, 'ast, Symbol>,
+    errors: Errors<Spanned<MacroError, BytePos>>,
+}
+
+impl LoadedImports<'_, '_> {
+    fn import(&self, args: &[SpannedExpr<Symbol>]) -> Result<TypedIdent<Symbol>, MacroError> {
+        let modulename = get_module_name(Some(self.module), args).map_err(MacroError::new)?;
+        let typ = self.db.peek_module_type(&modulename).ok_or_else(|| {
+            MacroError::message(format!(
+                "The module `{}` can't be imported without a thread as it has not been loaded",
+                modulename
+            ))
+        })?;
+        Ok(TypedIdent {
+            name: Symbol::from(format!("@{}", modulename)),
+            typ,
+        })
+    }
+}
+
+impl<'e, 'ast> MutVisitor<'e, 'ast> for LoadedImports<'_, 'ast> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'e mut SpannedExpr<'ast, Symbol>) {
+        let result = match &expr.value {
+            Expr::App { func, args, .. } => match &func.value {
+                Expr::Ident(id) if id.name.as_str() == "import!" => Some(self.import(args)),
+                Expr::Ident(id)
+                    if id.name.as_str().ends_with('!')
+                        || ast::literal_suffix(id.name.as_str()).is_some() =>
+                {
+                    Some(Err(MacroError::message(format!(
+                        "The macro `{}` can't be expanded without a thread",
+                        id.name
+                    ))))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        match result {
+            Some(Ok(id)) => {
+                let span = expr.span;
+                let original = mem::replace(expr, pos::spanned(span, Expr::Error(None)));
+                expr.value = Expr::MacroExpansion {
+                    original: self.arena.alloc(original),
+                    replacement: self.arena.alloc(pos::spanned(span, Expr::Ident(id))),
+                };
+            }
+            Some(Err(err)) => self.errors.push(pos::spanned(expr.span, err)),
+            None => ast::walk_mut_expr(self, expr),
+        }
+    }
+}
+
+/// Replaces the `import!` calls in `expr` with the modules they refer to, as long as those modules
+/// have already been loaded into `db`. Since no other macros can be expanded without a thread
+/// they are reported as errors.
+pub(crate) fn expand_loaded_imports<'ast>(
+    db: &dyn Compilation,
+    module: &str,
+    arena: ast::ArenaRef<'_, 'ast, Symbol>,
+    expr: &mut SpannedExpr<'ast, Symbol>,
+) -> Result<(), Errors<Spanned<MacroError, BytePos>>> {
+    let mut visitor = LoadedImports {
+        db,
+        module,
+        arena,
+        errors: Errors::new(),
+    };
+    visitor.visit_expr(expr);
+    if visitor.errors.has_errors() {
+        Err(visitor.errors)
+    } else {
+        Ok(())
+    }
+}
+
+impl<I> MacroDependencies for Import<I>
+where
+    I: Importer,
+{
+    fn referenced_symbols(
+        &self,
+        module: Option<&str>,
+        args: &[SpannedExpr<Symbol>],
+    ) -> Vec<Symbol> {
+        get_module_name(module, args)
+            .map(|modulename| vec![Symbol::from(format!("@{}", modulename))])
+            .unwrap_or_default()
+    }
+}
+
+impl<I> MacroDoc for Import<I>
+where
+    I: Importer,
+{
+    fn describe(&self, module: Option<&str>, args: &[SpannedExpr<Symbol>]) -> Option<String> {
+        get_module_name(module, args)
+            .ok()
+            .map(|modulename| format!("Imports the module `{}`", modulename))
+    }
+}
+
 impl<I> Macro for Import<I>
 where
     I: Importer,
@@ -471,6 +887,14 @@ where
             Some(Box::new(
                 arc_self.clone().downcast_arc::<Self>().ok().unwrap() as Arc<dyn ImportApi>,
             ))
+        } else if id == TypeId::of::<Arc<dyn MacroDependencies>>() {
+            Some(Box::new(
+                arc_self.clone().downcast_arc::<Self>().ok().unwrap() as Arc<dyn MacroDependencies>,
+            ))
+        } else if id == TypeId::of::<Arc<dyn MacroDoc>>() {
+            Some(Box::new(
+                arc_self.clone().downcast_arc::<Self>().ok().unwrap() as Arc<dyn MacroDoc>,
+            ))
         } else if id == TypeId::of::<salsa::Snapshot<CompilerDatabase>>() {
             Some(Box::new(self.snapshot(thread.root_thread())))
         } else if id == TypeId::of::<DatabaseMut>() {
@@ -494,30 +918,7 @@ where
         _arena: &'b mut ast::OwnedArena<'ast, Symbol>,
         args: &'b mut [SpannedExpr<'ast, Symbol>],
     ) -> MacroFuture<'r, 'ast> {
-        fn get_module_name(args: &[SpannedExpr<Symbol>]) -> Result<String, Error> {
-            if args.len() != 1 {
-                return Err(Error::String("Expected import to get 1 argument".into()).into());
-            }
-
-            let modulename = match args[0].value {
-                Expr::Ident(_) | Expr::Projection(..) => {
-                    let mut modulename = String::new();
-                    expr_to_path(&args[0], &mut modulename)
-                        .map_err(|err| Error::String(err.to_string()))?;
-                    modulename
-                }
-                Expr::Literal(Literal::String(ref filename)) => filename_to_module(filename),
-                _ => {
-                    return Err(Error::String(
-                        "Expected a string literal or path to import".into(),
-                    )
-                    .into());
-                }
-            };
-            Ok(modulename)
-        }
-
-        let modulename = match get_module_name(&args).map_err(MacroError::new) {
+        let modulename = match get_module_name(macros.module(), &args).map_err(MacroError::new) {
             Ok(modulename) => modulename,
             Err(err) => return Box::pin(future::err(err)),
         };
@@ -534,6 +935,8 @@ where
 
         let span = args[0].span;
 
+        let fetch_source = self.fetch_provided_source(&db, &modulename);
+
         #[cfg(feature = "tokio")]
         if let Some(spawn) = macros.spawn {
             use futures::task::SpawnExt;
@@ -541,6 +944,10 @@ where
             let (tx, rx) = tokio::sync::oneshot::channel();
             spawn
                 .spawn(Box::pin(async move {
+                    if let Some(fetch_source) = fetch_source {
+                        let source = fetch_source.await;
+                        add_provided_source(&db, &modulename, source);
+                    }
                     let result = std::panic::AssertUnwindSafe(db.import(modulename))
                         .catch_unwind()
                         .await
@@ -584,6 +991,10 @@ where
         Box::pin(async move {
             Ok(LazyMacroResult::from(move || {
                 async move {
+                    if let Some(fetch_source) = fetch_source {
+                        let source = fetch_source.await;
+                        add_provided_source(&db, &modulename, source);
+                    }
                     let result = db
                         .import(modulename)
                         .await
@@ -602,6 +1013,103 @@ where
     }
 }
 
+fn add_provided_source(db: &CompilerDatabase, module: &str, source: Option<Result<String, Error>>) {
+    if let Some(source) = source {
+        db.state()
+            .provided_sources
+            .entry(module.to_string())
+            .or_insert_with(|| source.map(|source| Arc::new(Cow::Owned(source))));
+    }
+}
+
+/// Macro which imports every module directly under a prefix and returns them as a record
+/// (`import_all! std.json` is equivalent to `{ de = import! std.json.de, ser = import! std.json.ser }`)
+pub struct ImportAll;
+
+impl Macro for ImportAll {
+    fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+        &self,
+        macros: &'b mut MacroExpander<'a>,
+        symbols: &'c mut Symbols,
+        arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+        args: &'b mut [SpannedExpr<'ast, Symbol>],
+    ) -> MacroFuture<'r, 'ast> {
+        let prefix = match get_module_name(macros.module(), &args).map_err(MacroError::new) {
+            Ok(prefix) => prefix,
+            Err(err) => return Box::pin(future::err(err)),
+        };
+
+        info!("import_all! {}", prefix);
+
+        let mut db = try_future!(macros
+            .userdata
+            .fork(macros.vm.root_thread())
+            .downcast::<salsa::Snapshot<CompilerDatabase>>()
+            .map_err(|_| MacroError::new(Error::String(
+                "`import_all` requires a `CompilerDatabase` as user data during macro expansion"
+                    .into(),
+            ))));
+        if macros
+            .vm
+            .get_macros()
+            .get_capability::<Arc<dyn ImportApi>>(macros.vm)
+            .is_none()
+        {
+            return Box::pin(future::err(MacroError::new(Error::String(
+                "`import_all` requires the `import` macro to be an `Import`".into(),
+            ))));
+        }
+
+        let modules = db.submodules(prefix.clone());
+        if modules.is_empty() {
+            return Box::pin(future::err(MacroError::new(Error::NoSubmodules(prefix))));
+        }
+        let mut fields = Vec::with_capacity(modules.len());
+        for module in modules.iter() {
+            let field = &module[prefix.len() + 1..];
+            if !crate::parser::is_identifier(field) {
+                return Box::pin(future::err(MacroError::new(Error::InvalidFieldName(
+                    module.clone(),
+                    field.into(),
+                ))));
+            }
+            fields.push(symbols.simple_symbol(field));
+        }
+
+        let span = args[0].span;
+        Box::pin(async move {
+            let mut exprs = Vec::with_capacity(modules.len());
+            for (module, field) in modules.iter().zip(fields) {
+                let id: TypedIdent<Symbol> = db
+                    .import(module.clone())
+                    .await
+                    .map_err(|salvage| MacroError::new(salvage.error))?;
+                exprs.push(ExprField {
+                    metadata: Default::default(),
+                    name: pos::spanned(span, field),
+                    value: Some(pos::spanned(span, Expr::Ident(id))),
+                });
+            }
+            drop(db);
+
+            Ok(pos::spanned(
+                span,
+                Expr::Record {
+                    typ: Type::hole(),
+                    types: &mut [],
+                    exprs: arena.alloc_extend(exprs),
+                    base: None,
+                },
+            )
+            .into())
+        })
+    }
+}
+
 unsafe impl<I> Trace for Import<I> {
     impl_trace! { self, _gc, () }
 }
+
+unsafe impl Trace for ImportAll {
+    impl_trace! { self, _gc, () }
+}
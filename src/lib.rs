@@ -45,6 +45,7 @@ macro_rules! try_future {
     };
 }
 
+#[macro_use]
 pub mod compiler_pipeline;
 #[macro_use]
 pub mod import;
@@ -62,7 +63,13 @@ use either::Either;
 
 use std as real_std;
 use std::{
-    env, error::Error as StdError, fmt, path::PathBuf, result::Result as StdResult, sync::Arc,
+    collections::{BTreeMap, BTreeSet},
+    env,
+    error::Error as StdError,
+    fmt,
+    path::PathBuf,
+    result::Result as StdResult,
+    sync::Arc,
 };
 
 use crate::base::{
@@ -71,7 +78,7 @@ use crate::base::{
     filename_to_module,
     metadata::Metadata,
     pos::{BytePos, Span, Spanned},
-    source::FileId,
+    source::{FileId, FileMap},
     symbol::{Symbol, Symbols},
     types::{ArcType, TypeCache},
 };
@@ -282,6 +289,22 @@ impl Error {
 /// Type alias for results returned by gluon
 pub type Result<T> = StdResult<T, Error>;
 
+/// The paths of the types which the compiler needs to know about, such as the type used for the
+/// result of comparisons and `if` conditions. Embeddings which do not load the standard library
+/// may point these at the types of their own core modules.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct PrimitiveTypes {
+    pub bool: String,
+}
+
+impl Default for PrimitiveTypes {
+    fn default() -> Self {
+        Self {
+            bool: "std.types.Bool".into(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct Settings {
     pub implicit_prelude: bool,
@@ -290,6 +313,12 @@ pub struct Settings {
     pub use_standard_lib: bool,
     pub optimize: bool,
     pub run_io: bool,
+    pub deprecation_warnings: bool,
+    pub allowed_extern_modules: Option<BTreeSet<String>>,
+    pub canonical_symbols: bool,
+    pub strict_conversions: bool,
+    pub record_timings: bool,
+    pub primitive_types: PrimitiveTypes,
 }
 
 impl Default for Settings {
@@ -301,6 +330,12 @@ impl Default for Settings {
             use_standard_lib: true,
             optimize: true,
             run_io: false,
+            deprecation_warnings: true,
+            allowed_extern_modules: None,
+            canonical_symbols: false,
+            strict_conversions: false,
+            record_timings: false,
+            primitive_types: PrimitiveTypes::default(),
         }
     }
 }
@@ -331,6 +366,10 @@ impl<'a, 'b> IntoDb<'a, 'b> for &'a mut salsa::Snapshot<CompilerDatabase> {
 pub struct ModuleCompiler<'a, 'b> {
     pub database: salsa::OwnedDb<'a, dyn Compilation + 'b>,
     symbols: Symbols,
+    expected_type: Option<ArcType>,
+    /// Set when the source is not the stored source of the module, so that its filemap is kept
+    /// apart from the module's own filemap
+    source_filemap: bool,
 }
 
 impl<'a, 'b> ModuleCompiler<'a, 'b> {
@@ -338,6 +377,36 @@ impl<'a, 'b> ModuleCompiler<'a, 'b> {
         Self {
             database: database.into_db(),
             symbols: Symbols::default(),
+            expected_type: None,
+            source_filemap: false,
+        }
+    }
+
+    fn add_source_filemap(&self, file: &str, source: &str) -> Arc<FileMap> {
+        if self.source_filemap {
+            self.state().add_source_filemap(file, source)
+        } else {
+            self.database.add_filemap(file, source)
+        }
+    }
+
+    fn get_or_insert_source_filemap(&self, file: &str, source: &str) -> Arc<FileMap> {
+        if self.source_filemap {
+            let mut state = self.state();
+            match state.get_source_filemap(file) {
+                Some(file_map) => file_map,
+                None => state.add_source_filemap(file, source),
+            }
+        } else {
+            self.database.get_or_insert_filemap(file, source)
+        }
+    }
+
+    fn get_source_filemap(&self, file: &str) -> Option<Arc<FileMap>> {
+        if self.source_filemap {
+            self.state().get_source_filemap(file)
+        } else {
+            self.database.get_filemap(file)
         }
     }
 }
@@ -380,7 +449,7 @@ macro_rules! runtime_option {
     pub fn $set_name(&mut self, $name: $typ) {
         let mut settings = self.compiler_settings();
         settings.$name = $name;
-        self.set_compiler_settings(settings);
+        self.update_compiler_settings(settings);
     }
 };
 }
@@ -422,6 +491,109 @@ impl import::DatabaseMut {
         /// (default: false)
         run_io set_run_io: bool
     }
+
+    runtime_option! {
+        /// Sets whether references to bindings marked with `#[deprecated]` emit warnings.
+        /// (default: true)
+        deprecation_warnings set_deprecation_warnings: bool
+    }
+
+    runtime_option! {
+        /// Restricts which extern modules may be imported. Any extern module which is not in the
+        /// set (including those imported transitively through the standard library) fails to
+        /// import with `import::Error::NotPermitted`.
+        /// (default: None, all extern modules may be imported)
+        allowed_extern_modules set_allowed_extern_modules: Option<BTreeSet<String>>
+    }
+
+    runtime_option! {
+        /// Sets whether the names of the compiled symbols should be independent of where they
+        /// are defined in the source. Enabling this makes the serialized output of two compilations
+        /// identical as long as the modules only differ in formatting.
+        /// (default: false)
+        canonical_symbols set_canonical_symbols: bool
+    }
+
+    runtime_option! {
+        /// Sets the paths of the types which the compiler treats as primitives, letting a custom
+        /// standard library provide its own `Bool` type.
+        /// (default: `PrimitiveTypes::default()`, the types of `std.types`)
+        primitive_types set_primitive_types: PrimitiveTypes
+    }
+
+    runtime_option! {
+        /// Sets whether the time spent typechecking and compiling each module is recorded. The
+        /// timings are retrieved with `CompilerDatabase::last_timings`.
+        /// (default: false)
+        record_timings set_record_timings: bool
+    }
+
+    /// Sets the number of columns a tab character advances to when reporting the location of
+    /// errors. Tabs advance to the next multiple of `tab_width`.
+    /// (default: 1)
+    pub fn tab_width(mut self, tab_width: usize) -> Self {
+        self.set_tab_width(tab_width);
+        self
+    }
+
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.state().code_map.set_tab_width(tab_width);
+    }
+
+    /// Sets whether literals passed to native functions must be representable by the Rust type of
+    /// the parameter without losing information. When enabled, passing `2147483648` to an `i32`
+    /// parameter or `0.1` to an `f32` parameter is a type error instead of silently truncating or
+    /// rounding the value.
+    /// (default: false)
+    pub fn strict_conversions(mut self, strict_conversions: bool) -> Self {
+        self.set_strict_conversions(strict_conversions);
+        self
+    }
+
+    pub fn set_strict_conversions(&mut self, strict_conversions: bool) {
+        let mut settings = self.compiler_settings();
+        settings.strict_conversions = strict_conversions;
+        self.update_compiler_settings(settings);
+    }
+}
+
+/// A set of module sources which are kept in memory.
+///
+/// Compiling a bundle with `ThreadExt::compile_bundle` resolves every module from the bundle
+/// (or the embedded standard library) instead of reading them from the filesystem.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ModuleBundle {
+    modules: BTreeMap<String, String>,
+}
+
+impl ModuleBundle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds (or replaces) the source of the module `name`
+    pub fn add_module(&mut self, name: impl Into<String>, source: impl Into<String>) -> &mut Self {
+        self.modules.insert(name.into(), source.into());
+        self
+    }
+
+    /// Iterates over the names and sources of the modules in the bundle
+    pub fn modules(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.modules
+            .iter()
+            .map(|(name, source)| (&name[..], &source[..]))
+    }
+}
+
+/// The result of `ThreadExt::analyze_expr`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExprAnalysis {
+    /// The modules imported by the expression
+    pub imports: BTreeSet<String>,
+    /// The bindings of imported modules which the expression refers to (`std.int.abs`)
+    pub globals: BTreeSet<String>,
+    /// The inferred type of the expression
+    pub typ: ArcType,
 }
 
 /// Extension trait which provides methods to load and execute gluon code
@@ -522,6 +694,52 @@ pub trait ThreadExt: Send + Sync {
         Ok((expr, typ))
     }
 
+    /// Returns the literals (ints, bytes, floats and strings) embedded in the compiled code of
+    /// `module`, compiling it if necessary
+    fn module_constants(&self, module: &str) -> Result<Vec<vm::types::Constant>> {
+        futures::executor::block_on(self.module_constants_async(module))
+    }
+
+    async fn module_constants_async(&self, module: &str) -> Result<Vec<vm::types::Constant>> {
+        let vm = self.thread();
+        let mut db = vm.get_database();
+        let closure = db.compiled_module(module.into(), None).await?;
+        Ok(closure.function.constants())
+    }
+
+    /// Parses and typechecks `expr_str` (without running it) and returns the modules it imports,
+    /// the imported bindings it refers to and its type
+    fn analyze_expr(&self, file: &str, expr_str: &str) -> Result<ExprAnalysis> {
+        futures::executor::block_on(self.analyze_expr_async(file, expr_str))
+    }
+
+    async fn analyze_expr_async(&self, file: &str, expr_str: &str) -> Result<ExprAnalysis> {
+        let vm = self.thread();
+        let mut db = vm.get_database();
+        // The expression must be typechecked with the symbols it was parsed with for its
+        // identifiers to refer to the bindings of the implicit prelude
+        let mut compiler = ModuleCompiler::new(&mut db);
+        let mut expr = parse_expr(&mut compiler, &vm.global_env().type_cache(), file, expr_str)
+            .map_err(|err| err.error)?;
+        let typ = (&mut expr)
+            .typecheck_expected(&mut compiler, vm, file, expr_str, None)
+            .await
+            .map(|result| result.typ)?;
+
+        let source_span = vm
+            .get_database()
+            .get_filemap(file)
+            .expect("Filemap was added while parsing")
+            .span();
+        let check::dependencies::Dependencies { imports, globals } =
+            check::dependencies::dependencies(source_span, expr.expr());
+        Ok(ExprAnalysis {
+            imports,
+            globals,
+            typ,
+        })
+    }
+
     /// Compiles `expr` into a function which can be added and run by the `vm`
     async fn compile_script(
         &self,
@@ -593,6 +811,148 @@ pub trait ThreadExt: Send + Sync {
             .await
     }
 
+    /// Compiles `module`, which must be known to the database (through `add_module` or the import
+    /// paths), into bytecode serialized using `serializer`.
+    ///
+    /// The bytecode can be loaded with `load_compiled`, letting a later process skip
+    /// typechecking and compiling the module.
+    #[cfg(feature = "serialization")]
+    async fn serialize_compiled<S>(
+        &self,
+        module: &str,
+        serializer: S,
+    ) -> StdResult<S::Ok, Either<Error, S::Error>>
+    where
+        S: serde::Serializer + Send,
+        S::Error: 'static,
+    {
+        let thread = self.thread();
+        let text = thread
+            .get_database()
+            .module_text(module.into())
+            .map_err(Either::Left)?;
+        compile_to(
+            &text[..],
+            &mut ModuleCompiler::new(&mut thread.get_database()),
+            &thread,
+            module,
+            &text[..],
+            None,
+            serializer,
+        )
+        .await
+    }
+
+    /// Loads bytecode which were serialized by `serialize_compiled`, runs it and stores the
+    /// result as the value of `module` without typechecking or compiling the module again.
+    ///
+    /// The modules imported by `module` are loaded as usual. Since the imported modules are not
+    /// typechecked against the bytecode they must not have changed since it was compiled.
+    #[cfg(feature = "serialization")]
+    async fn load_compiled<'de, D>(&self, module: &str, deserializer: D) -> Result<()>
+    where
+        D: serde::Deserializer<'de> + Send,
+    {
+        let thread = self.thread();
+        let ExecuteValue {
+            typ,
+            metadata,
+            value,
+            ..
+        } = Precompiled(deserializer)
+            .run_expr(
+                &mut ModuleCompiler::new(&mut thread.get_database()),
+                thread.root_thread(),
+                module,
+                "",
+                (),
+            )
+            .await?;
+        thread
+            .get_database_mut()
+            .set_global(module, typ, metadata, &value);
+        info!("Loaded compiled module `{}`", module);
+        Ok(())
+    }
+
+    /// Compiles and runs every module in `bundle` and serializes the resulting globals into a
+    /// single blob using `serializer`.
+    ///
+    /// The blob can be loaded with `load_bundle` which does not need to access the filesystem.
+    #[cfg(feature = "serialization")]
+    async fn compile_bundle<S>(
+        &self,
+        bundle: &ModuleBundle,
+        serializer: S,
+    ) -> StdResult<S::Ok, Either<Error, S::Error>>
+    where
+        S: serde::Serializer + Send,
+        S::Error: 'static,
+    {
+        use crate::serde::ser::SerializeState;
+        use crate::vm::{internal::Global, serialization::SeSeed};
+
+        let vm = self.thread();
+        {
+            let mut db = vm.get_database_mut();
+            for (name, source) in bundle.modules() {
+                db.add_module(name.into(), source);
+            }
+        }
+
+        let mut globals = Vec::with_capacity(bundle.modules.len());
+        {
+            let mut db = vm.get_database();
+            for name in bundle.modules.keys() {
+                db.import(name.clone())
+                    .await
+                    .map_err(|err| Either::Left(err.error))?;
+                globals.push(db.global(name.clone()).await.map_err(Either::Left)?);
+            }
+        }
+
+        let globals: Vec<_> = globals
+            .iter()
+            .zip(bundle.modules.keys())
+            .map(|(global, name)| Global {
+                id: Symbol::from(&name[..]),
+                typ: global.typ.clone(),
+                metadata: global.metadata.clone(),
+                value: global.value.get_variant(),
+            })
+            .collect();
+        globals
+            .serialize_state(serializer, &SeSeed::new())
+            .map_err(Either::Right)
+    }
+
+    /// Loads the modules which were serialized by `compile_bundle`
+    #[cfg(feature = "serialization")]
+    async fn load_bundle<'de, D>(&self, deserializer: D) -> Result<()>
+    where
+        D: serde::Deserializer<'de> + Send,
+    {
+        use crate::vm::{internal::Global, serialization::DeSeed, thread::RootedValue};
+
+        let vm = self.thread();
+        let globals: Vec<Global<RootedValue<RootedThread>>> =
+            DeSeed::new(vm, &mut vm.current_context())
+                .deserialize(deserializer)
+                .map_err(|err| err.to_string())?;
+
+        let mut db = vm.get_database_mut();
+        for global in globals {
+            db.set_global(
+                global.id.as_str(),
+                global.typ,
+                global.metadata,
+                &global.value,
+            );
+            info!("Loaded module `{}`", global.id);
+        }
+        Ok(())
+    }
+
     /// Parses and typechecks `expr_str` followed by extracting metadata from the created
     /// expression
     async fn extract_metadata(
@@ -657,6 +1017,42 @@ pub trait ThreadExt: Send + Sync {
             .map_err(|err| err.error)
     }
 
+    /// Compiles and runs each `(filename, input)` pair in `scripts` like `load_script`, loading the
+    /// modules concurrently.
+    ///
+    /// The order of `scripts` does not matter. All modules are added to the database before any
+    /// of them is compiled and a module which is imported by several others is only compiled
+    /// once, with the importing modules waiting for it to finish.
+    ///
+    /// If any module fails to load the database is restored to its state before the call and the
+    /// first error is returned.
+    async fn load_scripts_async(&self, scripts: &[(&str, &str)]) -> Result<()> {
+        let vm = self.thread();
+        let snapshot = vm.get_database().snapshot_globals();
+        {
+            let mut db = vm.get_database_mut();
+            for &(filename, input) in scripts {
+                db.add_module(filename_to_module(filename), input);
+            }
+        }
+
+        let imports = scripts.iter().map(|&(filename, _)| {
+            let mut db = vm.get_database();
+            let module_name = filename_to_module(filename);
+            async move {
+                db.import(module_name)
+                    .await
+                    .map(|_| ())
+                    .map_err(|err| err.error)
+            }
+        });
+        if let Err(err) = futures::future::try_join_all(imports).await {
+            vm.get_database_mut().restore_globals(&snapshot);
+            return Err(err);
+        }
+        Ok(())
+    }
+
     /// Loads `filename` and compiles and runs its input by calling `load_script`
     fn load_file<'vm>(&'vm self, filename: &str) -> Result<()> {
         futures::executor::block_on(self.load_file_async(filename))
@@ -828,6 +1224,25 @@ impl ThreadExt for Thread {
     }
 }
 
+/// Extension methods for macros which are expanded by the gluon compiler
+pub trait MacroExpanderExt {
+    /// Returns the settings of the compiler which is expanding the macro, letting a macro adapt
+    /// its output to them. Returns `None` if the macros are not expanded by a `CompilerDatabase`.
+    ///
+    /// The settings are read-only as changing them in the middle of an expansion would invalidate
+    /// the compilation which is in progress.
+    fn settings(&self) -> Option<Settings>;
+}
+
+/// The key of the macro state which holds the settings of the compiler expanding the macros
+const SETTINGS_STATE: &str = "gluon.settings";
+
+impl MacroExpanderExt for macros::MacroExpander<'_> {
+    fn settings(&self) -> Option<Settings> {
+        self.get_state::<Settings>(SETTINGS_STATE).cloned()
+    }
+}
+
 fn get_import(vm: &Thread) -> Arc<dyn import::ImportApi> {
     vm.get_macros()
         .get_capability::<Arc<dyn import::ImportApi>>(vm)
@@ -959,6 +1374,7 @@ impl VmBuilder {
                 }
                 macros.insert(String::from("import"), import);
             }
+            macros.insert(String::from("import_all"), import::ImportAll);
 
             macros.insert(String::from("lift_io"), lift_io::LiftIo);
         }
@@ -1,23 +1,26 @@
 use std::{
     borrow::Cow,
-    collections::hash_map,
+    collections::{hash_map, BTreeSet, HashMap, HashSet},
+    fmt,
     ops::Deref,
     result::Result as StdResult,
     sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
 };
 
-use salsa::{Database, OwnedDb};
+use salsa::{debug::DebugQueryTable, Database, OwnedDb};
 
 use {
     base::{
         ast::{self, OwnedExpr, TypedIdent},
+        error::{Errors, InFile},
         fnv::{FnvMap, FnvSet},
         kind::{ArcKind, KindEnv},
         metadata::{Metadata, MetadataEnv},
-        pos::BytePos,
-        source::{CodeMap, FileMap, Source},
-        symbol::{Name, Symbol, SymbolModule, SymbolRef},
-        types::{Alias, ArcType, NullInterner, PrimitiveEnv, TypeEnv, TypeExt},
+        pos::{self, ByteOffset, BytePos, Location, Span},
+        source::{CodeMap, FileMap, SharedSource, Source},
+        symbol::{Name, Symbol, SymbolModule, SymbolRef, Symbols},
+        types::{Alias, ArcType, NullInterner, PrimitiveEnv, Type, TypeEnv, TypeExt, TypeScheme},
     },
     vm::{
         self,
@@ -29,12 +32,17 @@ use {
         internal::Value,
         macros,
         thread::{RootedThread, RootedValue, Thread, ThreadInternal},
-        vm::VmEnv,
+        vm::{GlobalVmState, VmEnv},
         ExternLoader,
     },
 };
 
-use crate::{compiler_pipeline::*, import::PtrEq, Error, ModuleCompiler, Result, Settings};
+use crate::{
+    check::{dependencies::Dependencies, deprecation::Deprecated},
+    compiler_pipeline::*,
+    import::PtrEq,
+    Error, ModuleCompiler, Result, Settings,
+};
 
 pub use salsa;
 
@@ -75,15 +83,140 @@ unsafe fn root_global_with(global: UnrootedGlobal, vm: RootedThread) -> Database
 pub type UnrootedGlobal = vm::vm::Global<UnrootedValue>;
 pub type DatabaseGlobal = vm::vm::Global<RootedValue<RootedThread>>;
 
+/// The hasher used by the maps in the database which are keyed by module name.
+///
+/// Defaults to fnv which is fast for the small keys. With the `ahash` feature `ahash` is used
+/// instead which produces fewer collisions when a very large number of modules are loaded.
+#[cfg(not(feature = "ahash"))]
+pub type ModuleHasher = std::hash::BuildHasherDefault<crate::base::fnv::FnvHasher>;
+#[cfg(feature = "ahash")]
+pub type ModuleHasher = ahash::RandomState;
+
+pub type ModuleMap<K, V> = HashMap<K, V, ModuleHasher>;
+pub type ModuleSet<K> = HashSet<K, ModuleHasher>;
+
+/// The parse tree of a module, before any macros have been expanded
+#[derive(Debug)]
+pub struct ParsedModule {
+    pub expr: OwnedExpr<Symbol>,
+    /// The symbols created while parsing, needed to resolve the identifiers in `expr`
+    pub(crate) symbols: Symbols,
+}
+
+impl Eq for ParsedModule {}
+
+impl PartialEq for ParsedModule {
+    fn eq(&self, other: &Self) -> bool {
+        self.expr == other.expr
+    }
+}
+
 #[derive(Default)]
 pub struct State {
     pub(crate) code_map: CodeMap,
-    pub(crate) inline_modules: FnvMap<String, Arc<Cow<'static, str>>>,
-    pub(crate) index_map: FnvMap<String, BytePos>,
-    extern_globals: FnvSet<String>,
+    pub(crate) inline_modules: ModuleMap<String, Arc<Cow<'static, str>>>,
+    /// The sources fetched with `ModuleSourceProvider::async_get` before `import!` loaded the
+    /// module. Unlike `inline_modules` these are not captured by `snapshot_globals` and are
+    /// fetched again after the module is removed.
+    pub(crate) provided_sources:
+        ModuleMap<String, StdResult<Arc<Cow<'static, str>>, crate::import::Error>>,
+    pub(crate) index_map: ModuleMap<String, BytePos>,
+    /// The filemaps of the sources given to `typechecked_source`. These are not the stored
+    /// sources of their modules so they are kept out of `index_map`.
+    source_index_map: ModuleMap<String, BytePos>,
+    pub(crate) warnings: ModuleMap<String, InFile<Deprecated>>,
+    pub(crate) trace: Option<Vec<TraceEvent>>,
+    /// The modules whose values are never discarded, see `CompilerDatabase::pin_module`
+    pinned_modules: ModuleSet<String>,
+    /// The capacity given to `CompilerDatabase::set_cache_capacity`
+    cache_capacity: usize,
+    pub(crate) restored_globals: ModuleMap<String, UnrootedGlobal>,
+    /// Incremented each time an input of the database changes, see
+    /// `CompilerDatabase::current_revision`
+    revision: u64,
+    /// Incremented each time the source of a module changes, see
+    /// `CompilerDatabase::module_revision`
+    module_revisions: ModuleMap<String, usize>,
+    /// The types of the two most recently typechecked revisions of each module
+    module_type_history: ModuleMap<String, Vec<(usize, ArcType)>>,
+    extern_globals: ModuleSet<String>,
+    pub(crate) module_changed: Option<Arc<dyn Fn(&str) + Send + Sync>>,
+    error_sink: Option<Arc<dyn Fn(&str, &Error) + Send + Sync>>,
+    /// The time spent typechecking and compiling each module, if `record_timings` is enabled
+    timings: ModuleMap<String, Duration>,
+}
+
+/// The sources and values of the modules loaded into a `CompilerDatabase`, captured by
+/// `CompilerDatabase::snapshot_globals`
+#[derive(Clone, Default)]
+pub struct GlobalsSnapshot {
+    globals: ModuleMap<String, UnrootedGlobal>,
+    modules: ModuleMap<String, Arc<Cow<'static, str>>>,
+}
+
+impl GlobalsSnapshot {
+    /// Returns the names of the modules whose values were captured
+    pub fn modules(&self) -> impl Iterator<Item = &str> {
+        self.globals.keys().map(|module| &module[..])
+    }
+
+    /// Returns the type of the captured `module`
+    pub fn module_type(&self, module: &str) -> Option<&ArcType> {
+        self.globals.get(module).map(|global| &global.typ)
+    }
+}
+
+/// An input to the compiler which was recorded while tracing (see
+/// `CompilerDatabase::start_trace`)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TraceEvent {
+    /// The compiler settings were changed
+    Settings(crate::Settings),
+    /// The source of a module was set through `add_module` (or `load_script`)
+    AddModule { module: String, contents: String },
+    /// The source of a module was resolved by the import machinery (from the filesystem or the
+    /// embedded standard library)
+    ModuleText { module: String, contents: String },
+}
+
+/// The sequence of inputs given to a `CompilerDatabase` while tracing was enabled
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CompilationTrace {
+    pub events: Vec<TraceEvent>,
+}
+
+impl CompilationTrace {
+    /// Feeds the recorded inputs into `db` in the order they were recorded. Since module sources
+    /// which were resolved by the import machinery are added as inline modules the replayed
+    /// compilation does not depend on the filesystem.
+    pub fn replay(&self, db: &mut CompilerDatabase) {
+        for event in &self.events {
+            match event {
+                TraceEvent::Settings(settings) => db.update_compiler_settings(settings.clone()),
+                TraceEvent::AddModule { module, contents }
+                | TraceEvent::ModuleText { module, contents } => {
+                    db.add_module(module.clone(), contents)
+                }
+            }
+        }
+    }
 }
 
 impl State {
+    fn record_module_type(&mut self, module: &str, typ: ArcType) {
+        let revision = self
+            .module_revisions
+            .get(module)
+            .cloned()
+            .unwrap_or_default();
+        let history = self.module_type_history.entry(module.into()).or_default();
+        history.retain(|&(previous, _)| previous != revision);
+        history.push((revision, typ));
+        if history.len() > 2 {
+            history.remove(0);
+        }
+    }
+
     pub fn update_filemap<S>(&mut self, file: &str, source: S) -> Option<Arc<FileMap>>
     where
         S: Into<String>,
@@ -114,6 +247,16 @@ impl State {
         file_map
     }
 
+    pub fn add_shared_filemap(&mut self, file: &str, source: SharedSource) -> Arc<FileMap> {
+        match self.get_filemap(file) {
+            Some(ref file_map) if file_map.src() == (*source).as_ref() => return file_map.clone(),
+            _ => (),
+        }
+        let file_map = self.code_map.add_shared_filemap(file.to_string(), source);
+        self.index_map.insert(file.into(), file_map.span().start());
+        file_map
+    }
+
     pub(crate) fn get_or_insert_filemap<S>(&mut self, file: &str, source: S) -> Arc<FileMap>
     where
         S: AsRef<str> + Into<String>,
@@ -130,6 +273,24 @@ impl State {
             .and_then(move |i| self.code_map.get(*i))
             .cloned()
     }
+
+    pub(crate) fn add_source_filemap(&mut self, file: &str, source: &str) -> Arc<FileMap> {
+        match self.get_source_filemap(file) {
+            Some(ref file_map) if file_map.src() == source => return file_map.clone(),
+            _ => (),
+        }
+        let file_map = self.code_map.add_filemap(file.to_string(), source.into());
+        self.source_index_map
+            .insert(file.into(), file_map.span().start());
+        file_map
+    }
+
+    pub(crate) fn get_source_filemap(&self, file: &str) -> Option<Arc<FileMap>> {
+        self.source_index_map
+            .get(file)
+            .and_then(move |i| self.code_map.get(*i))
+            .cloned()
+    }
 }
 
 #[salsa::database(async CompileStorage)]
@@ -139,6 +300,10 @@ pub struct CompilerDatabase {
     // This is only set after calling snapshot on `Import`. `Import` itself can't contain a
     // `RootedThread` as that would create a cycle
     pub(crate) thread: Option<RootedThread>,
+    // The global state of the vm that `thread` belongs to. Typechecking only needs the type
+    // information stored here so it is kept separately from `thread`, letting snapshots without a
+    // thread still typecheck (see `snapshot_without_thread`)
+    pub(crate) global_env: Option<Arc<GlobalVmState>>,
 }
 
 impl CompilerDatabase {
@@ -146,17 +311,38 @@ impl CompilerDatabase {
         salsa::Snapshot::new(Self {
             storage: self.storage.snapshot(),
             state: self.state.clone(),
+            global_env: Some(thread.global_env().clone()),
             thread: Some(thread),
         })
     }
 
+    /// Creates a snapshot which does not keep a thread alive. The snapshot can typecheck
+    /// expressions against the modules which are already loaded (see `check_expr`) but any query
+    /// which needs to load or run a module panics.
+    pub fn snapshot_without_thread(&self) -> salsa::Snapshot<Self> {
+        salsa::Snapshot::new(Self {
+            storage: self.storage.snapshot(),
+            state: self.state.clone(),
+            global_env: self.global_env.clone(),
+            thread: None,
+        })
+    }
+
     pub fn fork(&self, state: salsa::ForkState, thread: RootedThread) -> salsa::Snapshot<Self> {
         salsa::Snapshot::new(Self {
             storage: self.storage.fork(state),
             state: self.state.clone(),
+            global_env: Some(thread.global_env().clone()),
             thread: Some(thread),
         })
     }
+
+    /// Returns the global state of the vm which the database compiles modules for
+    pub fn global_env(&self) -> &Arc<GlobalVmState> {
+        self.global_env
+            .as_ref()
+            .expect("Global state was not set in the compiler")
+    }
 }
 
 impl crate::query::CompilationBase for CompilerDatabase {
@@ -191,9 +377,24 @@ impl crate::query::CompilationBase for CompilerDatabase {
     }
 
     fn add_module(&mut self, module: String, contents: &str) {
+        let changed = self
+            .state()
+            .inline_modules
+            .get(&module)
+            .map_or(false, |text| &***text != contents);
+
         let state = self.state.clone();
         let mut state = state.lock().unwrap();
 
+        state.restored_globals.remove(&module);
+
+        if let Some(trace) = &mut state.trace {
+            trace.push(TraceEvent::AddModule {
+                module: module.clone(),
+                contents: contents.into(),
+            });
+        }
+
         match state.inline_modules.entry(module.clone()) {
             hash_map::Entry::Occupied(entry) => {
                 let entry = entry.into_mut();
@@ -201,9 +402,6 @@ impl crate::query::CompilationBase for CompilerDatabase {
                     let entry_contents = Arc::make_mut(entry).to_mut();
                     entry_contents.clear();
                     entry_contents.push_str(contents);
-                    ModuleTextQuery
-                        .in_db_mut(self as &mut dyn Compilation)
-                        .invalidate(&module);
                 } else {
                     return;
                 }
@@ -213,6 +411,18 @@ impl crate::query::CompilationBase for CompilerDatabase {
             }
         }
         state.add_filemap(&module, &contents[..]);
+        drop(state);
+
+        self.change_inputs(&[&module], |db| {
+            if changed {
+                ModuleTextQuery
+                    .in_db_mut(db as &mut dyn Compilation)
+                    .invalidate(&module);
+            }
+        });
+        if changed {
+            self.collect_garbage();
+        }
     }
 
     fn peek_typechecked_source_module(
@@ -226,6 +436,27 @@ impl crate::query::CompilationBase for CompilerDatabase {
                 Ok(t) => Some(t),
                 Err(salvage) => salvage.value,
             })
+            .or_else(|| {
+                if !self.state().pinned_modules.contains(key) {
+                    return None;
+                }
+                PinnedTypecheckedModuleQuery
+                    .in_db(self)
+                    .peek(&key.into())
+                    .and_then(|r| match r {
+                        Ok(t) => Some(t),
+                        Err(salvage) => salvage.value,
+                    })
+            })
+            .or_else(|| {
+                CachedTypecheckedModuleQuery
+                    .in_db(self)
+                    .peek(&key.into())
+                    .and_then(|r| match r {
+                        Ok(t) => Some(t),
+                        Err(salvage) => salvage.value,
+                    })
+            })
     }
 
     fn peek_module_type(&self, key: &str) -> Option<ArcType> {
@@ -253,6 +484,21 @@ impl crate::query::CompilationBase for CompilerDatabase {
             .in_db(self)
             .peek(&(key.into(), None))
             .and_then(|r| r.ok())
+            .or_else(|| {
+                if !self.state().pinned_modules.contains(key) {
+                    return None;
+                }
+                PinnedCoreExprQuery
+                    .in_db(self)
+                    .peek(&key.into())
+                    .and_then(|r| r.ok())
+            })
+            .or_else(|| {
+                CachedCoreExprQuery
+                    .in_db(self)
+                    .peek(&key.into())
+                    .and_then(|r| r.ok())
+            })
     }
 
     fn peek_global(&self, key: &str) -> Option<DatabaseGlobal> {
@@ -281,6 +527,7 @@ impl CompilerDatabase {
         let mut compiler = CompilerDatabase {
             state: Default::default(),
             storage: Default::default(),
+            global_env: thread.as_ref().map(|thread| thread.global_env().clone()),
             thread,
         };
         compiler.set_compiler_settings(Default::default());
@@ -306,6 +553,81 @@ impl CompilerDatabase {
         self.state().get_filemap(file)
     }
 
+    /// Returns the warnings emitted during the last typecheck of `module`, if any
+    pub fn warnings(&self, module: &str) -> Option<InFile<Deprecated>> {
+        self.state().warnings.get(module).cloned()
+    }
+
+    /// Returns the errors and warnings of every module in the database, grouped by severity.
+    ///
+    /// Errors are taken from the last typecheck of each module (without an expected type) and
+    /// warnings from `warnings`.
+    pub fn diagnostics(&self) -> Diagnostics {
+        let (mut modules, warnings) = {
+            let state = self.state();
+            let modules: Vec<_> = state.index_map.keys().cloned().collect();
+            let warnings: Vec<_> = state
+                .warnings
+                .iter()
+                .map(|(module, warnings)| (module.clone(), warnings.clone()))
+                .collect();
+            (modules, warnings)
+        };
+        modules.sort();
+
+        let mut diagnostics = Diagnostics::default();
+        for module in &modules {
+            if let Some(Err(salvage)) = TypecheckedSourceModuleQuery
+                .in_db(self)
+                .peek(&(module.clone(), None))
+            {
+                diagnostics.add_error(module, &salvage.error);
+            }
+        }
+        for (module, warnings) in &warnings {
+            diagnostics.add_in_file(Severity::Warning, module, warnings);
+        }
+        diagnostics.warnings.sort_by(|l, r| l.module.cmp(&r.module));
+        diagnostics
+    }
+
+    /// Typechecks every module in the database, continuing past modules which fail to
+    /// typecheck. Returns the errors of each failed module, keyed by the module name, if any
+    /// module failed.
+    pub fn typecheck_all(&self) -> StdResult<(), ModuleMap<String, Errors<Error>>> {
+        futures::executor::block_on(self.typecheck_all_async())
+    }
+
+    pub async fn typecheck_all_async(&self) -> StdResult<(), ModuleMap<String, Errors<Error>>> {
+        let mut modules = ModuleTextQuery
+            .in_db(self)
+            .entries::<Vec<_>>()
+            .into_iter()
+            .map(|entry| entry.key)
+            .collect::<Vec<String>>();
+        modules.extend(self.state().inline_modules.keys().cloned());
+        modules.sort();
+        modules.dedup();
+
+        let mut db = self.snapshot(self.thread().root_thread());
+        let mut failed = ModuleMap::default();
+        for module in modules {
+            if let Err(salvage) = db.typechecked_source_module(module.clone(), None).await {
+                let errors = match salvage.error {
+                    Error::Multiple(errors) => errors,
+                    error => Some(error).into_iter().collect(),
+                };
+                failed.insert(module, errors);
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(failed)
+        }
+    }
+
     pub(crate) fn get_or_insert_filemap<S>(&self, file: &str, source: S) -> Arc<FileMap>
     where
         S: AsRef<str> + Into<String>,
@@ -321,6 +643,12 @@ impl CompilerDatabase {
         self.state().add_filemap(file, source)
     }
 
+    /// Registers `source` as the source of `file` without copying it, which avoids keeping a
+    /// second copy of large (for instance memory mapped) files alive.
+    pub fn add_shared_filemap(&self, file: &str, source: SharedSource) -> Arc<FileMap> {
+        self.state().add_shared_filemap(file, source)
+    }
+
     pub fn set_global(&mut self, name: &str, typ: ArcType, metadata: Arc<Metadata>, value: &Value) {
         let thread = self.thread().root_thread();
         let mut gc = thread.global_env().gc.lock().unwrap();
@@ -331,17 +659,457 @@ impl CompilerDatabase {
         let id = Symbol::from(format!("@{}", name));
         unsafe { value.vm_mut().unroot() };
         self.state().extern_globals.insert(name.into());
-        self.set_extern_global(
-            name.into(),
-            UnrootedGlobal {
-                id,
-                typ,
-                metadata,
-                value: UnrootedValue(value),
-            },
-        )
+        self.change_inputs(&[], |db| {
+            db.set_extern_global(
+                name.into(),
+                UnrootedGlobal {
+                    id,
+                    typ,
+                    metadata,
+                    value: UnrootedValue(value),
+                },
+            )
+        });
+    }
+
+    /// Sets a function which is called with each error of a module as soon as typechecking the
+    /// module fails, letting a client publish the errors of one module without waiting for every
+    /// other module to be loaded. The errors are still returned as usual.
+    pub fn set_error_sink(&mut self, sink: Box<dyn Fn(&str, &Error) + Send + Sync>) {
+        self.state().error_sink = Some(sink.into());
+    }
+
+    fn report_errors(&self, module: &str, error: &Error) {
+        fn report(sink: &(dyn Fn(&str, &Error) + Send + Sync), module: &str, error: &Error) {
+            match error {
+                Error::Multiple(errors) => {
+                    for error in errors {
+                        report(sink, module, error);
+                    }
+                }
+                _ => sink(module, error),
+            }
+        }
+
+        let sink = self.state().error_sink.clone();
+        if let Some(sink) = sink {
+            report(&*sink, module, error);
+        }
+    }
+
+    /// Starts recording every input given to the database (module sources, settings and the
+    /// results of resolving imports) so that the compilation can be reproduced with
+    /// `CompilationTrace::replay`. The current settings are recorded as the first event.
+    pub fn start_trace(&self) {
+        let settings = self.compiler_settings();
+        self.state().trace = Some(vec![TraceEvent::Settings(settings)]);
+    }
+
+    /// Stops recording and returns the inputs which were recorded since `start_trace` was called
+    pub fn take_trace(&self) -> Option<CompilationTrace> {
+        self.state()
+            .trace
+            .take()
+            .map(|events| CompilationTrace { events })
+    }
+
+    pub(crate) fn trace(&self, event: impl FnOnce() -> TraceEvent) {
+        if let Some(trace) = &mut self.state().trace {
+            trace.push(event());
+        }
+    }
+
+    pub(crate) fn update_compiler_settings(&mut self, settings: Settings) {
+        self.trace(|| TraceEvent::Settings(settings.clone()));
+        self.change_inputs(&[], |db| db.set_compiler_settings(settings));
+        self.collect_garbage();
+    }
+
+    /// Changes the inputs of the database with `set_inputs` and starts a new revision in which the
+    /// sources of `modules` have changed. Every input of the database is changed through this so
+    /// that `current_revision` and `module_revision` never miss a change.
+    pub(crate) fn change_inputs(&mut self, modules: &[&str], set_inputs: impl FnOnce(&mut Self)) {
+        set_inputs(self);
+
+        let mut state = self.state();
+        state.revision += 1;
+        for &module in modules {
+            *state.module_revisions.entry(module.into()).or_default() += 1;
+            if let Some(module_changed) = &state.module_changed {
+                module_changed(module);
+            }
+        }
+    }
+
+    /// Returns the current revision of the database, which increases each time an input of the
+    /// database changes, for instance when the source of a module is changed or removed or the
+    /// compiler settings change. Values derived from the database can be cached for as long as
+    /// the revision stays the same.
+    pub fn current_revision(&self) -> u64 {
+        self.state().revision
+    }
+
+    /// Returns the revision of the source of `module`, which increases each time the source is
+    /// set, changed or removed. Modules which are not known to the database are at revision `0`.
+    pub fn module_revision(&self, module: &str) -> usize {
+        self.state()
+            .module_revisions
+            .get(module)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Sets the source of `module`, for instance to the contents of an edited editor buffer, and
+    /// invalidates everything derived from the previous source. Setting the source a module
+    /// already has does nothing, leaving its revision and any compiled values untouched.
+    pub fn set_module_source(&mut self, module: &str, source: impl Into<String>) {
+        let source = source.into();
+        self.add_module(module.into(), &source);
+    }
+
+    /// Forces every module to be recompiled the next time it is requested while keeping the
+    /// sources, filemaps and settings of the database intact.
+    pub fn rebuild_all(&mut self) {
+        let modules = ModuleTextQuery
+            .in_db(self)
+            .entries::<Vec<_>>()
+            .into_iter()
+            .map(|entry| entry.key)
+            .collect::<Vec<String>>();
+
+        // The sources are usually unchanged so salsa would backdate the invalidated texts and
+        // keep every value derived from them. Discard those values to force them to be recomputed
+        let strategy = salsa::SweepStrategy::default()
+            .discard_values()
+            .sweep_all_revisions();
+        ImportQuery.in_db(self).sweep(strategy);
+        GlobalInnerQuery.in_db(self).sweep(strategy);
+        ModuleTypeQuery.in_db(self).sweep(strategy);
+        ModuleMetadataQuery.in_db(self).sweep(strategy);
+        ModuleImportsQuery.in_db(self).sweep(strategy);
+        PinnedTypecheckedModuleQuery.in_db(self).sweep(strategy);
+        PinnedCoreExprQuery.in_db(self).sweep(strategy);
+        CachedTypecheckedModuleQuery.in_db(self).sweep(strategy);
+        CachedCoreExprQuery.in_db(self).sweep(strategy);
+
+        let changed: Vec<&str> = modules.iter().map(|module| &module[..]).collect();
+        self.change_inputs(&changed, |db| {
+            for module in &modules {
+                ModuleTextQuery
+                    .in_db_mut(db as &mut dyn Compilation)
+                    .invalidate(module);
+            }
+        });
+        self.collect_garbage();
     }
 
+    /// Removes `module` and its filemap from the database, forcing it and any modules which
+    /// import it to be recompiled the next time they are requested.
+    pub fn remove_module(&mut self, module: &str) {
+        {
+            let mut state = self.state();
+            state.inline_modules.remove(module);
+            state.provided_sources.remove(module);
+            if let Some(file_id) = state.index_map.remove(module) {
+                state.code_map.remove(file_id);
+            }
+            if let Some(file_id) = state.source_index_map.remove(module) {
+                state.code_map.remove(file_id);
+            }
+            state.warnings.remove(module);
+            state.pinned_modules.remove(module);
+            state.restored_globals.remove(module);
+            state.timings.remove(module);
+            state.module_type_history.remove(module);
+        }
+
+        self.change_inputs(&[module], |db| {
+            ModuleTextQuery
+                .in_db_mut(db as &mut dyn Compilation)
+                .invalidate(&module.to_string())
+        });
+        self.collect_garbage();
+    }
+
+    /// Removes every module from the database, as if `remove_module` were called for each of them,
+    /// and discards all of their values. The thread and the compiler settings are left intact so
+    /// new modules can be loaded afterwards without creating a new vm.
+    pub fn reset(&mut self) {
+        let mut modules = ModuleTextQuery
+            .in_db(self)
+            .entries::<Vec<_>>()
+            .into_iter()
+            .map(|entry| entry.key)
+            .collect::<Vec<String>>();
+        modules.extend(self.state().inline_modules.keys().cloned());
+        modules.sort();
+        modules.dedup();
+
+        for module in &modules {
+            self.remove_module(module);
+        }
+
+        let strategy = salsa::SweepStrategy::default()
+            .discard_values()
+            .sweep_all_revisions();
+        ImportQuery.in_db(self).sweep(strategy);
+        GlobalInnerQuery.in_db(self).sweep(strategy);
+        ModuleTypeQuery.in_db(self).sweep(strategy);
+        ModuleMetadataQuery.in_db(self).sweep(strategy);
+        ModuleImportsQuery.in_db(self).sweep(strategy);
+        PinnedTypecheckedModuleQuery.in_db(self).sweep(strategy);
+        PinnedCoreExprQuery.in_db(self).sweep(strategy);
+        CachedTypecheckedModuleQuery.in_db(self).sweep(strategy);
+        CachedCoreExprQuery.in_db(self).sweep(strategy);
+        self.collect_garbage();
+    }
+
+    /// Keeps the typechecked and compiled values of `module` when the database discards the
+    /// values of other modules to reclaim memory (which happens after each import).
+    ///
+    /// The values of pinned modules are computed by separate queries which `collect_garbage` never
+    /// sweeps, so they stay in the database until the module is unpinned. Salsa still recomputes
+    /// them when they are requested after an input of the database changed.
+    pub fn pin_module(&self, module: &str) {
+        self.state().pinned_modules.insert(module.into());
+    }
+
+    /// Undoes `pin_module`, letting the values of `module` be discarded again. The values of
+    /// other pinned modules which were not used since the last change to the database are
+    /// discarded as well and recomputed the next time they are needed.
+    pub fn unpin_module(&self, module: &str) {
+        if self.state().pinned_modules.remove(module) {
+            let strategy = salsa::SweepStrategy::discard_outdated();
+            PinnedTypecheckedModuleQuery.in_db(self).sweep(strategy);
+            PinnedCoreExprQuery.in_db(self).sweep(strategy);
+        }
+    }
+
+    /// Keeps the typechecked and compiled values of the `capacity` most recently used modules
+    /// when the database discards values to reclaim memory. The values of every other module
+    /// are discarded and recomputed the next time they are needed.
+    ///
+    /// While a capacity is set the values of the modules are computed by queries which
+    /// `collect_garbage` does not sweep, with the capacity as their salsa LRU capacity. The LRU of
+    /// salsa is approximate and keeps at least three values. A capacity of `0` (the default)
+    /// disables the cache.
+    pub fn set_cache_capacity(&mut self, capacity: usize) {
+        self.state().cache_capacity = capacity;
+        CachedTypecheckedModuleQuery
+            .in_db_mut(self as &mut dyn Compilation)
+            .set_lru_capacity(capacity);
+        CachedCoreExprQuery
+            .in_db_mut(self as &mut dyn Compilation)
+            .set_lru_capacity(capacity);
+        if capacity == 0 {
+            let strategy = salsa::SweepStrategy::default()
+                .discard_values()
+                .sweep_all_revisions();
+            CachedTypecheckedModuleQuery.in_db(self).sweep(strategy);
+            CachedCoreExprQuery.in_db(self).sweep(strategy);
+        }
+    }
+
+    /// Returns the time spent typechecking and compiling each module the last time it was
+    /// built. Modules whose values were reused from a previous build are not timed again.
+    ///
+    /// Nothing is recorded unless `record_timings` is enabled.
+    pub fn last_timings(&self) -> ModuleMap<String, Duration> {
+        self.state().timings.clone()
+    }
+
+    fn record_timing(&self, module: &str, elapsed: Duration, replace: bool) {
+        let mut state = self.state();
+        let timing = state.timings.entry(module.into()).or_default();
+        if replace {
+            *timing = elapsed;
+        } else {
+            *timing += elapsed;
+        }
+    }
+
+    /// Returns the value of every module in the database, loading the modules which have been
+    /// added but not loaded yet. Each value is produced by the `global` query of its own module so
+    /// modules which are already loaded are not compiled or run again. Modules which failed to
+    /// load are returned as errors instead of affecting the other modules.
+    ///
+    /// With the `parallel` feature the modules are loaded on a thread pool, each on its own
+    /// snapshot of the database. A module which imports a module that is being loaded on another
+    /// snapshot waits for that snapshot to finish it instead of loading it a second time, so
+    /// dependencies between the modules still resolve and every module is only run once. Since
+    /// each module is driven to completion by blocking a thread of the pool, modules which need to
+    /// wait on other futures (such as other tasks spawned on the database) should be loaded with
+    /// `globals_async` instead.
+    pub fn globals(&self) -> ModuleMap<String, Result<DatabaseGlobal>> {
+        #[cfg(feature = "parallel")]
+        {
+            self.globals_parallel()
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            futures::executor::block_on(self.globals_async())
+        }
+    }
+
+    /// Returns the value of every module in the database, loading the modules which have been
+    /// added but not loaded yet one after another.
+    pub async fn globals_async(&self) -> ModuleMap<String, Result<DatabaseGlobal>> {
+        let mut db = self.snapshot(self.thread().root_thread());
+        let mut globals = ModuleMap::default();
+        for module in self.global_modules() {
+            let global = db
+                .global_inner(module.clone())
+                .await
+                .map(|global| unsafe { root_global_with(global, self.thread().root_thread()) });
+            globals.insert(module, global);
+        }
+        globals
+    }
+
+    #[cfg(feature = "parallel")]
+    fn globals_parallel(&self) -> ModuleMap<String, Result<DatabaseGlobal>> {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        // The database itself can't be shared between threads so the snapshots are taken up front
+        // and moved to the thread pool
+        let snapshots = self
+            .global_modules()
+            .into_iter()
+            .map(|module| (module, self.snapshot(self.thread().root_thread())))
+            .collect::<Vec<_>>();
+        let globals = snapshots
+            .into_par_iter()
+            .map(|(module, mut db)| {
+                let global = futures::executor::block_on(db.global_inner(module.clone()));
+                (module, global)
+            })
+            .collect::<Vec<_>>();
+
+        globals
+            .into_iter()
+            .map(|(module, global)| {
+                let global = global
+                    .map(|global| unsafe { root_global_with(global, self.thread().root_thread()) });
+                (module, global)
+            })
+            .collect()
+    }
+
+    /// The modules which `globals` loads, the modules which have been loaded already and the
+    /// modules which have been added but not loaded yet
+    fn global_modules(&self) -> Vec<String> {
+        let mut modules = GlobalInnerQuery
+            .in_db(self)
+            .entries::<Vec<_>>()
+            .into_iter()
+            .filter(|entry| entry.value.is_some())
+            .map(|entry| entry.key)
+            .collect::<Vec<String>>();
+        modules.extend(self.state().inline_modules.keys().cloned());
+        modules.sort();
+        modules.dedup();
+        modules
+    }
+
+    /// Calls `f` with the name and type of every module which has been loaded so far, or with the
+    /// error of each module which failed to load. Unlike `globals` no values are rooted and the
+    /// globals are looked up one at a time, which makes this the cheaper choice when only the
+    /// names and types of the modules are needed (for instance to build an index of symbols).
+    pub fn for_each_global(&self, mut f: impl FnMut(&str, StdResult<&ArcType, &Error>)) {
+        // Every module which is loaded from source has a filemap
+        let mut modules: Vec<String> = self.state().index_map.keys().cloned().collect();
+        modules.extend(
+            ExternLoaderQuery
+                .in_db(self)
+                .entries::<Vec<_>>()
+                .into_iter()
+                .map(|entry| entry.key),
+        );
+        modules.sort();
+        modules.dedup();
+
+        for module in modules {
+            match GlobalInnerQuery.in_db(self).peek(&module) {
+                Some(Ok(global)) => f(&module, Ok(&global.typ)),
+                Some(Err(error)) => f(&module, Err(&error)),
+                None => (),
+            }
+        }
+    }
+
+    /// Captures the values of every module which has been loaded so far, along with the sources
+    /// of the modules added directly to the database. Passing the snapshot to `restore_globals`
+    /// resets the database to this point without recompiling the captured modules.
+    pub fn snapshot_globals(&self) -> GlobalsSnapshot {
+        let globals = GlobalInnerQuery
+            .in_db(self)
+            .entries::<Vec<_>>()
+            .into_iter()
+            .filter_map(|entry| Some((entry.key, entry.value?.ok()?)))
+            .collect();
+        let modules = self.state().inline_modules.clone();
+        GlobalsSnapshot { globals, modules }
+    }
+
+    /// Restores the modules captured by `snapshot_globals`. Modules which were added after the
+    /// snapshot was taken are removed and modules which were changed get back their captured
+    /// source and value.
+    pub fn restore_globals(&mut self, snapshot: &GlobalsSnapshot) {
+        let changed = {
+            let mut state = self.state();
+            let mut changed = Vec::new();
+
+            let current: Vec<String> = state.inline_modules.keys().cloned().collect();
+            for module in current {
+                if !snapshot.modules.contains_key(&module) {
+                    state.inline_modules.remove(&module);
+                    if let Some(file_id) = state.index_map.remove(&module) {
+                        state.code_map.remove(file_id);
+                    }
+                    changed.push(module);
+                }
+            }
+            for (module, text) in &snapshot.modules {
+                if state.inline_modules.get(module) != Some(text) {
+                    state.inline_modules.insert(module.clone(), text.clone());
+                    state.add_filemap(module, &text[..]);
+                    changed.push(module.clone());
+                }
+            }
+
+            for module in &changed {
+                match snapshot.globals.get(module) {
+                    Some(global) => {
+                        state
+                            .restored_globals
+                            .insert(module.clone(), global.clone());
+                    }
+                    None => {
+                        state.restored_globals.remove(module);
+                    }
+                }
+            }
+            changed
+        };
+
+        if !changed.is_empty() {
+            let modules: Vec<&str> = changed.iter().map(|module| &module[..]).collect();
+            self.change_inputs(&modules, |db| {
+                for module in &changed {
+                    ModuleTextQuery
+                        .in_db_mut(db as &mut dyn Compilation)
+                        .invalidate(module);
+                }
+            });
+            self.collect_garbage();
+        }
+    }
+
+    /// Discards the values computed so far, except those of pinned modules and the values kept
+    /// by the cache (see `set_cache_capacity`). Salsa keeps the typechecked and core values used
+    /// in the current revision (as they are recomputed in each revision) so only the compiled
+    /// values are discarded right away. The values computed before a change to an input of the
+    /// database are discarded by the call following the change.
     pub(crate) fn collect_garbage(&self) {
         let strategy = salsa::SweepStrategy::default()
             .discard_values()
@@ -396,6 +1164,12 @@ pub trait Compilation: CompilationBase {
     #[salsa::dependencies]
     fn module_text(&self, module: String) -> StdResult<Arc<Cow<'static, str>>, Error>;
 
+    /// Lists the modules directly under `prefix` (see `Import::submodules`).
+    fn submodules(&self, prefix: String) -> Arc<Vec<String>>;
+
+    /// Parses the source of `module` without expanding macros or typechecking it.
+    async fn parsed_module(&self, module: String) -> SalvageResult<Arc<ParsedModule>, Error>;
+
     #[salsa::cycle(recover_cycle_typecheck)]
     async fn typechecked_source_module(
         &self,
@@ -403,6 +1177,15 @@ pub trait Compilation: CompilationBase {
         expected_type: Option<ArcType>,
     ) -> SalvageResult<TypecheckValue<Arc<OwnedExpr<Symbol>>>, Error>;
 
+    /// Typechecks `source` as the contents of `module` without looking up the source of the
+    /// module, for instance to check an unsaved editor buffer.
+    async fn typechecked_source(
+        &self,
+        module: String,
+        source: Arc<str>,
+        expected_type: Option<ArcType>,
+    ) -> SalvageResult<TypecheckValue<Arc<OwnedExpr<Symbol>>>, Error>;
+
     async fn module_type(
         &self,
         module: String,
@@ -422,6 +1205,41 @@ pub trait Compilation: CompilationBase {
         expected_type: Option<ArcType>,
     ) -> StdResult<interpreter::Global<CoreExpr>, Error>;
 
+    /// Typechecks a pinned module. The values are stored separately from those of
+    /// `typechecked_source_module` so that `collect_garbage` can sweep the other modules without
+    /// discarding them.
+    #[doc(hidden)]
+    #[salsa::cycle(recover_cycle_salvage)]
+    async fn pinned_typechecked_module(
+        &self,
+        module: String,
+    ) -> SalvageResult<TypecheckValue<Arc<OwnedExpr<Symbol>>>, Error>;
+
+    /// Translates a pinned module, see `pinned_typechecked_module`
+    #[doc(hidden)]
+    #[salsa::cycle(recover_cycle)]
+    async fn pinned_core_expr(
+        &self,
+        module: String,
+    ) -> StdResult<interpreter::Global<CoreExpr>, Error>;
+
+    /// Typechecks a module while a cache capacity is set. The LRU capacity of this query bounds
+    /// the number of values which are kept, see `CompilerDatabase::set_cache_capacity`.
+    #[doc(hidden)]
+    #[salsa::cycle(recover_cycle_salvage)]
+    async fn cached_typechecked_module(
+        &self,
+        module: String,
+    ) -> SalvageResult<TypecheckValue<Arc<OwnedExpr<Symbol>>>, Error>;
+
+    /// Translates a module while a cache capacity is set, see `cached_typechecked_module`
+    #[doc(hidden)]
+    #[salsa::cycle(recover_cycle)]
+    async fn cached_core_expr(
+        &self,
+        module: String,
+    ) -> StdResult<interpreter::Global<CoreExpr>, Error>;
+
     #[salsa::cycle(recover_cycle_expected_type)]
     #[salsa::dependencies]
     async fn compiled_module(
@@ -433,6 +1251,14 @@ pub trait Compilation: CompilationBase {
     #[salsa::cycle(recover_cycle_salvage)]
     async fn import(&self, module: String) -> SalvageResult<TypedIdent<Symbol>, Error>;
 
+    /// Returns every module which `module` imports, directly or transitively, sorted by name.
+    /// `module` itself is only included if it is part of an import cycle.
+    async fn transitive_dependencies(&self, module: String) -> StdResult<Arc<Vec<String>>, Error>;
+
+    /// Returns the modules which `module` imports directly along with the fields of those
+    /// modules which it uses.
+    async fn module_imports(&self, module: String) -> StdResult<Arc<Dependencies>, Error>;
+
     #[doc(hidden)]
     #[salsa::cycle(recover_cycle)]
     async fn global_inner(&self, name: String) -> Result<UnrootedGlobal>;
@@ -440,6 +1266,11 @@ pub trait Compilation: CompilationBase {
     #[salsa::transparent]
     #[salsa::cycle(recover_cycle)]
     async fn global(&self, name: String) -> Result<DatabaseGlobal>;
+
+    /// Returns the module and the span of the top-level binding which defines `name`
+    /// (`std.int.abs`), letting an editor jump to the definition of a global. Returns `None` if
+    /// `name` does not refer to a value bound at the top-level of a module which typechecks.
+    async fn global_definition_span(&self, name: String) -> Option<(String, Span<BytePos>)>;
 }
 
 fn recover_cycle_typecheck<T>(
@@ -476,7 +1307,7 @@ fn recover_cycle<T>(
         })
         .collect();
     cycle.pop();
-    Err(macros::Error::new(crate::import::Error::CyclicDependency(
+    Err(macros::Error::new(crate::import::ImportError::Cyclic(
         module.to_string(),
         cycle,
     ))
@@ -500,7 +1331,7 @@ fn recover_cycle_salvage<T>(
         .collect();
     cycle.pop();
     Err(
-        Error::from(macros::Error::new(crate::import::Error::CyclicDependency(
+        Error::from(macros::Error::new(crate::import::ImportError::Cyclic(
             module.to_string(),
             cycle,
         )))
@@ -532,49 +1363,183 @@ fn module_text(db: &dyn Compilation, module: String) -> StdResult<Arc<Cow<'stati
         let mut filename = module.replace(".", "/");
         filename.push_str(".glu");
 
-        let use_standard_lib = db.compiler_settings().use_standard_lib;
-        Arc::new(
-            crate::get_import(db.thread())
-                .get_module_source(use_standard_lib, &module, &filename)
-                .map_err(macros::Error::new)?,
-        )
+        let provided = db.compiler().state().provided_sources.get(&module).cloned();
+        let contents: Arc<Cow<'static, str>> = match provided {
+            Some(provided) => provided.map_err(macros::Error::new)?,
+            None => {
+                let use_standard_lib = db.compiler_settings().use_standard_lib;
+                Arc::new(crate::get_import(db.thread()).get_module_source(
+                    use_standard_lib,
+                    &module,
+                    &filename,
+                )?)
+            }
+        };
+        db.compiler().trace(|| TraceEvent::ModuleText {
+            module: module.clone(),
+            contents: contents.to_string(),
+        });
+        contents
     };
 
     Ok(contents)
 }
 
-async fn typechecked_source_module(
+fn submodules(db: &dyn Compilation, prefix: String) -> Arc<Vec<String>> {
+    // The modules are listed from the filesystem so the listing is redone in each revision.
+    // Modules which depend on it are only recompiled if the listed modules change
+    db.salsa_runtime().report_untracked_read();
+
+    Arc::new(crate::get_import(db.thread()).submodules(db.compiler(), &prefix))
+}
+
+async fn parsed_module(
     db: &mut OwnedDb<'_, dyn Compilation + '_>,
     module: String,
-    expected_type: Option<ArcType>,
-) -> SalvageResult<TypecheckValue<Arc<OwnedExpr<Symbol>>>, Error> {
+) -> SalvageResult<Arc<ParsedModule>, Error> {
     db.salsa_runtime().report_untracked_read();
 
     let text = db.module_text(module.clone())?;
 
+    db.add_filemap(&module, &text);
+
     let thread = db.thread().root_thread();
     let mut compiler = ModuleCompiler::new(db);
-    let value = text
-        .typecheck_expected(
-            &mut compiler,
-            &thread,
-            &module,
-            &text,
-            expected_type.as_ref(),
-        )
-        .await
-        .map_err(|err| err.map(|value| value.map(Arc::new)))?;
-
-    Ok(value.map(Arc::new))
+    let result = parse_expr(
+        &mut compiler,
+        thread.global_env().type_cache(),
+        &module,
+        &text,
+    );
+    let symbols = compiler.symbols;
+    match result {
+        Ok(expr) => Ok(Arc::new(ParsedModule { expr, symbols })),
+        Err(Salvage { value, error }) => Err(Salvage {
+            value: value.map(|expr| Arc::new(ParsedModule { expr, symbols })),
+            error: error.into(),
+        }),
+    }
 }
 
-async fn module_type(
+async fn typechecked_source_module(
     db: &mut OwnedDb<'_, dyn Compilation + '_>,
-    name: String,
+    module: String,
     expected_type: Option<ArcType>,
-) -> SalvageResult<ArcType, Error> {
-    if ExternLoaderQuery.in_db(&**db).peek(&name).is_some() {
-        let global = db.extern_module(name).await?;
+) -> SalvageResult<TypecheckValue<Arc<OwnedExpr<Symbol>>>, Error> {
+    let result = typecheck_module(db, module.clone(), expected_type).await;
+    if let Err(salvage) = &result {
+        db.compiler().report_errors(&module, &salvage.error);
+    }
+    result
+}
+
+async fn typecheck_module(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: String,
+    expected_type: Option<ArcType>,
+) -> SalvageResult<TypecheckValue<Arc<OwnedExpr<Symbol>>>, Error> {
+    db.salsa_runtime().report_untracked_read();
+
+    if expected_type.is_none() {
+        if db.state().pinned_modules.contains(&module) {
+            return db.pinned_typechecked_module(module).await;
+        }
+        if db.state().cache_capacity != 0 {
+            return db.cached_typechecked_module(module).await;
+        }
+    }
+
+    typecheck_module_source(db, module, expected_type).await
+}
+
+async fn pinned_typechecked_module(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: String,
+) -> SalvageResult<TypecheckValue<Arc<OwnedExpr<Symbol>>>, Error> {
+    typecheck_module_source(db, module, None).await
+}
+
+async fn cached_typechecked_module(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: String,
+) -> SalvageResult<TypecheckValue<Arc<OwnedExpr<Symbol>>>, Error> {
+    typecheck_module_source(db, module, None).await
+}
+
+async fn typecheck_module_source(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: String,
+    expected_type: Option<ArcType>,
+) -> SalvageResult<TypecheckValue<Arc<OwnedExpr<Symbol>>>, Error> {
+    let start = if db.compiler_settings().record_timings {
+        Some(Instant::now())
+    } else {
+        None
+    };
+
+    let text = db.module_text(module.clone())?;
+
+    let thread = db.thread().root_thread();
+    let value = join_result!(db.parsed_module(module.clone()).await, |parsed| {
+        // Macro expansion and typechecking modify the expression so they work on a copy of the
+        // shared parse tree, using the same symbols as the parser
+        let mut compiler = ModuleCompiler::new(&mut *db);
+        compiler.symbols = parsed.symbols.clone();
+        parsed
+            .expr
+            .ast_clone()
+            .typecheck_expected(
+                &mut compiler,
+                &thread,
+                &module,
+                &text,
+                expected_type.as_ref(),
+            )
+            .await
+            .map(|value| value.map(Arc::new))
+            .map_err(|err| err.map(|value| value.map(Arc::new)))
+    })?;
+
+    db.state().record_module_type(&module, value.typ.clone());
+
+    if let Some(start) = start {
+        db.compiler().record_timing(&module, start.elapsed(), true);
+    }
+
+    Ok(value)
+}
+
+async fn typechecked_source(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: String,
+    source: Arc<str>,
+    expected_type: Option<ArcType>,
+) -> SalvageResult<TypecheckValue<Arc<OwnedExpr<Symbol>>>, Error> {
+    let thread = db.thread().root_thread();
+    let mut compiler = ModuleCompiler::new(db);
+    // Keep the filemap of the module itself intact as other queries still refer to it
+    compiler.source_filemap = true;
+    let value = source
+        .typecheck_expected(
+            &mut compiler,
+            &thread,
+            &module,
+            &source,
+            expected_type.as_ref(),
+        )
+        .await
+        .map_err(|err| err.map(|value| value.map(Arc::new)))?;
+
+    Ok(value.map(Arc::new))
+}
+
+async fn module_type(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    name: String,
+    expected_type: Option<ArcType>,
+) -> SalvageResult<ArcType, Error> {
+    if ExternLoaderQuery.in_db(&**db).peek(&name).is_some() {
+        let global = db.extern_module(name).await?;
         return Ok(global.typ.clone());
     }
     db.typechecked_source_module(name, expected_type)
@@ -583,6 +1548,122 @@ async fn module_type(
         .map_err(|err| err.map(|m| m.typ))
 }
 
+async fn module_imports(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: String,
+) -> StdResult<Arc<Dependencies>, Error> {
+    if let Some(loader) = ExternLoaderQuery.in_db(&**db).peek(&module) {
+        return Ok(Arc::new(Dependencies {
+            imports: loader.dependencies.iter().cloned().collect(),
+            globals: Default::default(),
+        }));
+    }
+
+    let value = match db.typechecked_source_module(module.clone(), None).await {
+        // Modules with errors are still typechecked as far as possible so their imports can be
+        // found
+        Ok(value)
+        | Err(Salvage {
+            value: Some(value), ..
+        }) => value,
+        Err(Salvage { value: None, error }) => return Err(error),
+    };
+    Ok(Arc::new(match db.get_filemap(&module) {
+        Some(file_map) => {
+            crate::check::dependencies::dependencies(file_map.span(), value.expr.expr())
+        }
+        None => Dependencies::default(),
+    }))
+}
+
+async fn transitive_dependencies(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: String,
+) -> StdResult<Arc<Vec<String>>, Error> {
+    // Typechecking a module which is part of an import cycle from inside a query trips up the
+    // cycle detection of salsa, so the modules in cycles are first found by following the
+    // `import!` calls of the unexpanded sources. Their imports can only be read from those calls
+    let mut source_imports = FnvMap::default();
+    let mut stack = vec![module.clone()];
+    while let Some(module) = stack.pop() {
+        if source_imports.contains_key(&module) {
+            continue;
+        }
+        let loader = ExternLoaderQuery.in_db(&**db).peek(&module);
+        let imports = match loader {
+            Some(loader) => loader.dependencies.iter().cloned().collect(),
+            None => parsed_imports(db, &module).await?,
+        };
+        stack.extend(imports.iter().cloned());
+        source_imports.insert(module, imports);
+    }
+    let in_cycle = |module: &String| {
+        let mut visited = BTreeSet::new();
+        let mut stack: Vec<_> = source_imports[module].iter().collect();
+        while let Some(import) = stack.pop() {
+            if import == module {
+                return true;
+            }
+            if visited.insert(import) {
+                stack.extend(&source_imports[import]);
+            }
+        }
+        false
+    };
+    let cyclic: BTreeSet<_> = source_imports.keys().filter(|m| in_cycle(m)).collect();
+
+    let mut dependencies = BTreeSet::new();
+    let mut stack = vec![module];
+    while let Some(module) = stack.pop() {
+        let loader = ExternLoaderQuery.in_db(&**db).peek(&module);
+        let imports = if let Some(loader) = loader {
+            loader.dependencies.iter().cloned().collect()
+        } else if cyclic.contains(&module) {
+            source_imports[&module].clone()
+        } else {
+            match db.typechecked_source_module(module.clone(), None).await {
+                // Modules with errors are still typechecked as far as possible so their imports
+                // can be found
+                Ok(value)
+                | Err(Salvage {
+                    value: Some(value), ..
+                }) => {
+                    let file_map = match db.get_filemap(&module) {
+                        Some(file_map) => file_map,
+                        None => continue,
+                    };
+                    crate::check::dependencies::dependencies(file_map.span(), value.expr.expr())
+                        .imports
+                }
+                Err(Salvage { value: None, error }) => return Err(error),
+            }
+        };
+        for import in imports {
+            if dependencies.insert(import.clone()) {
+                stack.push(import);
+            }
+        }
+    }
+    Ok(Arc::new(dependencies.into_iter().collect()))
+}
+
+/// Returns the modules which `module` imports through the `import!` calls of its unexpanded
+/// source
+async fn parsed_imports(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: &str,
+) -> StdResult<BTreeSet<String>, Error> {
+    let parsed = match db.parsed_module(module.into()).await {
+        Ok(parsed)
+        | Err(Salvage {
+            value: Some(parsed),
+            ..
+        }) => parsed,
+        Err(Salvage { value: None, error }) => return Err(error),
+    };
+    Ok(crate::import::imported_modules(module, parsed.expr.expr()))
+}
+
 async fn module_metadata(
     db: &mut OwnedDb<'_, dyn Compilation + '_>,
     name: String,
@@ -605,6 +1686,37 @@ async fn core_expr(
 ) -> StdResult<interpreter::Global<CoreExpr>, Error> {
     db.salsa_runtime().report_untracked_read();
 
+    if expected_type.is_none() {
+        if db.state().pinned_modules.contains(&module) {
+            return db.pinned_core_expr(module).await;
+        }
+        if db.state().cache_capacity != 0 {
+            return db.cached_core_expr(module).await;
+        }
+    }
+
+    translate_module(db, module, expected_type).await
+}
+
+async fn pinned_core_expr(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: String,
+) -> StdResult<interpreter::Global<CoreExpr>, Error> {
+    translate_module(db, module, None).await
+}
+
+async fn cached_core_expr(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: String,
+) -> StdResult<interpreter::Global<CoreExpr>, Error> {
+    translate_module(db, module, None).await
+}
+
+async fn translate_module(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: String,
+    expected_type: Option<ArcType>,
+) -> StdResult<interpreter::Global<CoreExpr>, Error> {
     let value = db
         .typechecked_source_module(module.clone(), expected_type.clone())
         .await?;
@@ -667,6 +1779,12 @@ async fn compiled_module(
         settings.emit_debug_info,
     );
 
+    let start = if settings.record_timings {
+        Some(Instant::now())
+    } else {
+        None
+    };
+
     let mut compiled_module = compiler.compile_expr(core_expr.value.expr())?;
     let module_id = Symbol::from(format!("@{}", name));
     compiled_module.function.id = module_id.clone();
@@ -674,6 +1792,10 @@ async fn compiled_module(
         .global_env()
         .new_global_thunk(&thread, compiled_module)?;
 
+    if let Some(start) = start {
+        db.compiler().record_timing(&module, start.elapsed(), false);
+    }
+
     Ok(closure)
 }
 
@@ -682,6 +1804,20 @@ async fn import(
     modulename: String,
 ) -> SalvageResult<TypedIdent<Symbol>, Error> {
     assert!(!modulename.starts_with('@'));
+
+    if let Some(allowed) = &db.compiler_settings().allowed_extern_modules {
+        if ExternLoaderQuery.in_db(&**db).peek(&modulename).is_some()
+            && !allowed.contains(&modulename)
+        {
+            return Err(
+                Error::from(macros::Error::new(crate::import::Error::NotPermitted(
+                    modulename,
+                )))
+                .into(),
+            );
+        }
+    }
+
     let thread = db.thread().root_thread();
 
     let name = Symbol::from(format!("@{}", modulename));
@@ -706,12 +1842,25 @@ async fn global_inner(
     db: &mut OwnedDb<'_, dyn Compilation + '_>,
     name: String,
 ) -> Result<UnrootedGlobal> {
+    let restored = db.state().restored_globals.get(&name).cloned();
+    if let Some(global) = restored {
+        // Depend on the source so that the restored value is discarded if the module changes
+        db.module_text(name)?;
+        return Ok(global);
+    }
+
+    // Modules loaded through `set_global` have no source to typecheck
+    if db.state().extern_globals.contains(&name) {
+        return Ok(db.extern_global(name));
+    }
+
     if ExternLoaderQuery.in_db(db.compiler()).peek(&name).is_some() {
         let global = db.extern_module(name.clone()).await?;
 
         // Ensure the type is stored in the database so we can collect typechecked_source_module later
         db.module_type(name.clone(), None).await?;
-        db.module_metadata(name, None).await?;
+        db.module_metadata(name.clone(), None).await?;
+        db.module_imports(name).await?;
 
         return Ok(global);
     }
@@ -722,6 +1871,7 @@ async fn global_inner(
     // Ensure the type is stored in the database so we can collect typechecked_source_module later
     db.module_type(name.clone(), None).await?;
     db.module_metadata(name.clone(), None).await?;
+    db.module_imports(name.clone()).await?;
 
     let closure = db.compiled_module(name.clone(), None).await?;
 
@@ -802,6 +1952,45 @@ async fn global(
         .map(|global| unsafe { root_global_with(global, db.thread().root_thread()) })
 }
 
+async fn global_definition_span(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    name: String,
+) -> Option<(String, Span<BytePos>)> {
+    let (module, field) = {
+        let mut iter = name.rsplitn(2, '.');
+        let field = iter.next()?;
+        (iter.next()?.to_string(), field)
+    };
+
+    let value = db
+        .typechecked_source_module(module.clone(), None)
+        .await
+        .ok()?;
+    // Bindings added by the implicit prelude lie outside of the module's source
+    let source_span = db.get_filemap(&module)?.span();
+
+    let mut expr = value.expr.expr();
+    loop {
+        match &expr.value {
+            ast::Expr::LetBindings(binds, body) => {
+                let definition = binds.iter().find(|bind| match &bind.name.value {
+                    ast::Pattern::Ident(id) => {
+                        source_span.contains(bind.name.span) && id.name.declared_name() == field
+                    }
+                    _ => false,
+                });
+                if let Some(bind) = definition {
+                    return Some((module, bind.span()));
+                }
+                expr = body;
+            }
+            ast::Expr::TypeBindings(_, body) => expr = body,
+            ast::Expr::MacroExpansion { replacement, .. } => expr = replacement,
+            _ => return None,
+        }
+    }
+}
+
 use std::cell::RefCell;
 pub struct Env<T>(RefCell<T>);
 
@@ -815,22 +2004,36 @@ where
     Env(RefCell::new(env))
 }
 
-impl<T> CompilerEnv for Env<T>
+impl<T> Env<T>
 where
     T: Deref<Target = CompilerDatabase>,
 {
-    fn find_var(&self, id: &Symbol) -> Option<(Variable<Symbol>, ArcType)> {
-        if id.is_global() {
+    /// Like `CompilerEnv::find_var` but also returns the metadata of the variable, for instance
+    /// to show its documentation when completing it
+    pub fn find_var_with_metadata(
+        &self,
+        id: &Symbol,
+    ) -> Option<(Variable<Symbol>, ArcType, Arc<Metadata>)> {
+        let global = if id.is_global() {
             self.get_global(id.definition_name())
-                .map(|g| (Variable::UpVar(g.id.clone()), g.typ.clone()))
         } else {
-            let name = id.definition_name();
+            self.0.borrow_mut().get_extern_global(id.definition_name())
+        }?;
+        Some((
+            Variable::UpVar(global.id.clone()),
+            global.typ.clone(),
+            global.metadata.clone(),
+        ))
+    }
+}
 
-            self.0
-                .borrow_mut()
-                .get_extern_global(name)
-                .map(|g| (Variable::UpVar(g.id.clone()), g.typ.clone()))
-        }
+impl<T> CompilerEnv for Env<T>
+where
+    T: Deref<Target = CompilerDatabase>,
+{
+    fn find_var(&self, id: &Symbol) -> Option<(Variable<Symbol>, ArcType)> {
+        self.find_var_with_metadata(id)
+            .map(|(variable, typ, _)| (variable, typ))
     }
 }
 
@@ -841,16 +2044,8 @@ where
     fn find_kind(&self, id: &SymbolRef) -> Option<ArcKind> {
         if id.is_global() {
             TypeEnv::find_type_info(self, id).map(|t| {
-                t.kind(
-                    &self
-                        .0
-                        .borrow_mut()
-                        .thread()
-                        .global_env()
-                        .type_cache()
-                        .kind_cache,
-                )
-                .into_owned()
+                t.kind(&self.0.borrow().global_env().type_cache().kind_cache)
+                    .into_owned()
             })
         } else {
             None
@@ -870,25 +2065,19 @@ where
                 .borrow_mut()
                 .get_binding_inner(id.definition_name(), |self_, module| {
                     self_
-                        .get_extern_global(module.as_str())
-                        .map(|global| global.typ)
+                        .extern_global_type(module.as_str())
                         .or_else(|| self_.peek_module_type(module.as_str().into()))
                 })
                 .ok()
         } else {
-            let name = id.definition_name();
-
-            self.0
-                .borrow_mut()
-                .get_extern_global(name)
-                .map(|global| global.typ.clone())
+            self.0.borrow_mut().extern_global_type(id.definition_name())
         }
     }
 
     fn find_type_info(&self, id: &SymbolRef) -> Option<Alias<Symbol, ArcType>> {
         if id.is_global() {
             let env = self.0.borrow();
-            let globals = env.thread().global_env().get_globals();
+            let globals = env.global_env().get_globals();
             globals.type_infos.find_type_info(id)
         } else {
             None
@@ -901,10 +2090,10 @@ where
     T: Deref<Target = CompilerDatabase>,
 {
     fn get_bool(&self) -> ArcType {
-        self.0
-            .borrow_mut()
-            .find_type_info("std.types.Bool")
-            .expect("std.types.Bool")
+        let db = self.0.borrow_mut();
+        let bool_path = db.compiler_settings().primitive_types.bool;
+        db.find_type_info(&bool_path)
+            .unwrap_or_else(|_| panic!("Missing primitive type `{}`", bool_path))
             .into_type()
     }
 }
@@ -957,6 +2146,10 @@ where
         env.get_extern_global(name)
             .or_else(|| env.peek_global(module.as_str().into()))
     }
+
+    fn binding_suggestions(&self, name: &str) -> Vec<String> {
+        self.0.borrow().binding_suggestions(name)
+    }
 }
 
 fn get_scoped_global<'n, T>(
@@ -984,53 +2177,220 @@ fn get_scoped_global<'n, T>(
     Some((remaining_fields, global))
 }
 
+/// Reports `err` at the bytes `start..end` of the binding path `name`
+fn binding_path_error(name: &str, (start, end): (usize, usize), err: vm::Error) -> Error {
+    let mut code_map = CodeMap::new();
+    let file_map = code_map.add_filemap(name.into(), name.into());
+    let span = file_map
+        .span()
+        .subspan(ByteOffset(start as i64), ByteOffset(end as i64));
+    Error::Macro(InFile::new(
+        code_map,
+        vec![pos::spanned(span, macros::Error::new(err))].into(),
+    ))
+}
+
 use crate::base::resolve;
 trait Extract: Sized {
     // type Output;
-    fn extract(&self, db: &CompilerDatabase, field_name: &str) -> Option<Self>;
+    fn extract(&self, db: &CompilerDatabase, field_name: &str) -> Result<Option<Self>>;
     fn typ(&self) -> &ArcType;
     // fn output(&self) -> Self::Output;
 }
 
+fn array_element_type(typ: &ArcType) -> Option<ArcType> {
+    match **typ {
+        Type::App(_, ref args) if typ.is_array() => args.first().cloned(),
+        _ => None,
+    }
+}
+
 impl Extract for ArcType {
-    fn extract(&self, db: &CompilerDatabase, field_name: &str) -> Option<Self> {
+    fn extract(&self, db: &CompilerDatabase, field_name: &str) -> Result<Option<Self>> {
         let typ = resolve::remove_aliases_cow(&env(db), &mut NullInterner, self);
-        typ.row_iter()
-            .find(|field| field.name.as_str() == field_name)
-            .map(|field| field.typ.clone())
+        if let Some(element_type) = array_element_type(&typ) {
+            return Ok(field_name.parse::<usize>().ok().map(|_| element_type));
+        }
+        Ok(typ
+            .row_iter()
+            .find(|field| field.name.definition_name() == field_name)
+            .map(|field| field.typ.clone()))
     }
     fn typ(&self) -> &ArcType {
         self
     }
 }
 impl Extract for (RootedValue<RootedThread>, ArcType) {
-    fn extract(&self, db: &CompilerDatabase, field_name: &str) -> Option<Self> {
+    fn extract(&self, db: &CompilerDatabase, field_name: &str) -> Result<Option<Self>> {
         let (value, typ) = self;
         let typ = resolve::remove_aliases_cow(&env(db), &mut NullInterner, typ);
-        typ.row_iter()
+        if let Some(element_type) = array_element_type(&typ) {
+            let index = match field_name.parse::<usize>() {
+                Ok(index) => index,
+                Err(_) => return Ok(None),
+            };
+            return match value.get_variants().as_ref() {
+                ValueRef::Array(array) => {
+                    let element = array
+                        .get(index)
+                        .ok_or_else(|| vm::Error::IndexOutOfBounds(index, array.len()))?;
+                    Ok(Some((db.thread().root_value(element), element_type)))
+                }
+                _ => ice!("Unexpected value {:?}", value),
+            };
+        }
+        Ok(typ
+            .row_iter()
             .enumerate()
-            .find(|&(_, field)| field.name.as_str() == field_name)
+            .find(|&(_, field)| field.name.definition_name() == field_name)
             .map(|(index, field)| match value.get_variants().as_ref() {
                 ValueRef::Data(data) => (
                     db.thread().root_value(data.get_variant(index).unwrap()),
                     field.typ.clone(),
                 ),
                 _ => ice!("Unexpected value {:?}", value),
-            })
+            }))
     }
     fn typ(&self) -> &ArcType {
         &self.1
     }
 }
 
+/// The differences between the exported bindings of two versions of a module
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ModuleDiff {
+    /// Bindings which are only exported by the new version
+    pub added: Vec<String>,
+    /// Bindings which are only exported by the previous version
+    pub removed: Vec<String>,
+    /// Bindings whose type changed, as `(name, previous type, new type)`
+    pub retyped: Vec<(String, ArcType, ArcType)>,
+}
+
+impl ModuleDiff {
+    /// Returns true if the both versions export the same bindings with the same types
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.retyped.is_empty()
+    }
+
+    /// Returns true if code which used the previous version may fail to compile against the new
+    /// version
+    pub fn is_breaking(&self) -> bool {
+        !self.removed.is_empty() || !self.retyped.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// An error or warning reported for a module
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DiagnosticMessage {
+    pub severity: Severity,
+    pub module: String,
+    pub message: String,
+    /// The location the diagnostic points to, if it points to a location in the source
+    pub location: Option<Location>,
+}
+
+/// Every error and warning in a `CompilerDatabase` (see `CompilerDatabase::diagnostics`)
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Diagnostics {
+    pub errors: Vec<DiagnosticMessage>,
+    pub warnings: Vec<DiagnosticMessage>,
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} errors, {} warnings",
+            self.error_count(),
+            self.warning_count()
+        )
+    }
+}
+
+impl Diagnostics {
+    pub fn error_count(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.warnings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+
+    /// Iterates over all errors followed by all warnings
+    pub fn iter(&self) -> impl Iterator<Item = &DiagnosticMessage> {
+        self.errors.iter().chain(&self.warnings)
+    }
+
+    fn add_error(&mut self, module: &str, error: &Error) {
+        match error {
+            Error::Parse(err) => self.add_in_file(Severity::Error, module, err),
+            Error::Typecheck(err) => self.add_in_file(Severity::Error, module, err),
+            Error::Macro(err) => self.add_in_file(Severity::Error, module, err),
+            Error::Multiple(errors) => {
+                for err in errors {
+                    self.add_error(module, err);
+                }
+            }
+            Error::IO(_) | Error::VM(_) | Error::Other(_) => self.errors.push(DiagnosticMessage {
+                severity: Severity::Error,
+                module: module.into(),
+                message: error.to_string(),
+                location: None,
+            }),
+        }
+    }
+
+    fn add_in_file<E>(&mut self, severity: Severity, module: &str, in_file: &InFile<E>)
+    where
+        E: fmt::Display,
+    {
+        let messages = match severity {
+            Severity::Error => &mut self.errors,
+            Severity::Warning => &mut self.warnings,
+        };
+        messages.extend(in_file.errors().iter().map(|err| {
+            let start = err.span.start();
+            DiagnosticMessage {
+                severity,
+                module: module.into(),
+                message: err.value.to_string(),
+                location: in_file
+                    .source()
+                    .get(start)
+                    .and_then(|file_map| Source::location(&**file_map, start)),
+            }
+        }));
+    }
+}
+
 impl CompilerDatabase {
+    /// Typechecks `expr_str` as the contents of `file` and returns its type, without needing a
+    /// thread. Since loading modules and expanding macros requires a thread, `import!` may only
+    /// refer to modules which are already loaded and no other macros may be used.
+    pub fn check_expr(&self, file: &str, expr_str: &str) -> Result<ArcType> {
+        let global_env = self.global_env().clone();
+        let mut db = self.snapshot_without_thread();
+        let mut compiler = ModuleCompiler::new(&mut db);
+        typecheck_without_thread(&mut compiler, global_env.type_cache(), file, expr_str)
+    }
+
     pub fn find_type_info(&self, name: &str) -> Result<Alias<Symbol, ArcType>> {
         let name = Name::new(name);
 
         let typ = self.get_binding_inner(name.module().as_str(), |self_, module| {
             self_
-                .get_extern_global(module.as_str())
-                .map(|global| global.typ)
+                .extern_global_type(module.as_str())
                 .or_else(|| self_.peek_module_type(module.as_str().into()))
         })?;
 
@@ -1041,19 +2401,156 @@ impl CompilerDatabase {
                 .map(|field| &field.typ)
                 .cloned()
         };
-        maybe_type_info
-            .ok_or_else(move || vm::Error::UndefinedField(typ, name.name().as_str().into()).into())
+        let mut alias = maybe_type_info
+            .ok_or_else(move || vm::Error::UndefinedField(typ, name.name().as_str().into()))?;
+
+        // Follow aliases which only re-export another alias (`type MyType = b.OtherType`)
+        let mut visited = FnvSet::default();
+        loop {
+            let next = match **alias.unresolved_type() {
+                Type::Alias(ref next) if alias.params().is_empty() && next.params().is_empty() => {
+                    Alias::from(next.clone())
+                }
+                _ => return Ok(alias),
+            };
+            visited.insert(alias.name.clone());
+            if visited.contains(&next.name) {
+                return Err(vm::Error::Message(format!(
+                    "The type alias `{}` is an alias of itself",
+                    next.name
+                ))
+                .into());
+            }
+            alias = next;
+        }
     }
 
+    /// Returns the fields of the record type `base_expr_type` (after resolving aliases) whose
+    /// names start with `prefix`, along with their types.
+    pub fn complete_field(&self, base_expr_type: &ArcType, prefix: &str) -> Vec<(String, ArcType)> {
+        let typ = resolve::remove_aliases_cow(&env(self), &mut NullInterner, base_expr_type);
+        typ.remove_forall()
+            .row_iter()
+            .filter(|field| field.name.declared_name().starts_with(prefix))
+            .map(|field| (field.name.declared_name().to_string(), field.typ.clone()))
+            .collect()
+    }
+
+    /// Returns the value and type bound to `name` (`std.int.abs`). The value is rooted on the
+    /// thread so it stays valid even if the module is reloaded, removed or garbage collected
+    /// afterwards.
+    ///
+    /// If `name` is not defined the returned `vm::Error::UndefinedBinding` contains similarly
+    /// named paths (see `binding_suggestions`).
     pub fn get_binding(&self, name: &str) -> Result<(RootedValue<RootedThread>, ArcType)> {
         self.get_binding_inner(name, |self_, module| {
             self_
                 .get_extern_global(module.as_str())
-                .or_else(|| self_.peek_global(module.as_str().into()))
+                .or_else(|| self_.peek_loaded_global(module.as_str()))
                 .map(|global| (global.value, global.typ))
         })
     }
 
+    /// Like `peek_global` but ignores the value of a module which has been removed, which is
+    /// only discarded by the next garbage collection
+    fn peek_loaded_global(&self, module: &str) -> Option<DatabaseGlobal> {
+        self.get_filemap(module)?;
+        self.peek_global(module)
+    }
+
+    /// Like `get_binding` but resolves every path in `names` at once, looking up each module
+    /// which the paths may refer to only once. The results are returned in the same order as
+    /// `names`.
+    pub fn get_bindings(
+        &self,
+        names: &[&str],
+    ) -> Vec<Result<(RootedValue<RootedThread>, ArcType)>> {
+        let mut globals = FnvMap::<String, Option<DatabaseGlobal>>::default();
+        names
+            .iter()
+            .map(|name| {
+                self.get_binding_inner(name, |self_, module| {
+                    globals
+                        .entry(module.as_str().to_string())
+                        .or_insert_with(|| {
+                            self_
+                                .get_extern_global(module.as_str())
+                                .or_else(|| self_.peek_loaded_global(module.as_str()))
+                        })
+                        .clone()
+                        .map(|global| (global.value, global.typ))
+                })
+            })
+            .collect()
+    }
+
+    /// Like `get_binding` but takes a symbol, for instance one taken from a typechecked AST.
+    /// Global symbols name a module directly so they are looked up without splitting the name
+    /// into a module path. Other symbols are resolved like a path given to `get_binding`.
+    pub fn get_binding_by_symbol(
+        &self,
+        symbol: &Symbol,
+    ) -> Result<(RootedValue<RootedThread>, ArcType)> {
+        let name = symbol.definition_name();
+        if symbol.is_global() {
+            if let Some(global) = self
+                .get_extern_global(name)
+                .or_else(|| self.peek_loaded_global(name))
+            {
+                return Ok((global.value, global.typ));
+            }
+        }
+        self.get_binding(name)
+    }
+
+    /// Returns up to three paths similar to `name` which start with the name of a loaded module,
+    /// ordered from the most to the least similar. These are attached to the
+    /// `vm::Error::UndefinedBinding` returned by `get_binding` for `name`.
+    pub fn binding_suggestions(&self, name: &str) -> Vec<String> {
+        let mut modules: Vec<String> = GlobalInnerQuery
+            .in_db(self)
+            .entries::<Vec<_>>()
+            .into_iter()
+            .map(|entry| entry.key)
+            .collect();
+        modules.extend(self.state().extern_globals.iter().cloned());
+
+        let components: Vec<&str> = name.split('.').collect();
+        let mut suggestions: Vec<(usize, String)> = modules
+            .into_iter()
+            .filter_map(|module| {
+                let len = module.split('.').count();
+                if len > components.len() {
+                    return None;
+                }
+                // Only suggest modules which differ from `name` in a single, misspelled component,
+                // otherwise `std.strng.len` would also suggest `std.string.prim`
+                let mut differing = module.split('.').zip(&components).filter(|(l, r)| l != *r);
+                let (expected, actual) = differing.next()?;
+                if differing.next().is_some() {
+                    return None;
+                }
+                let distance = strsim::levenshtein(actual, expected);
+                if distance > std::cmp::max(1, expected.len() / 3) {
+                    return None;
+                }
+                let suggestion = Some(&module[..])
+                    .into_iter()
+                    .chain(components[len..].iter().cloned())
+                    .collect::<Vec<_>>()
+                    .join(".");
+                Some((distance, suggestion))
+            })
+            .collect();
+        suggestions.sort();
+        suggestions.dedup();
+        suggestions
+            .into_iter()
+            .take(3)
+            .map(|(_, suggestion)| suggestion)
+            .collect()
+    }
+
     fn get_binding_inner<T>(
         &self,
         name: &str,
@@ -1063,35 +2560,61 @@ impl CompilerDatabase {
         T: Extract,
     {
         let (remaining_fields, mut value) = get_scoped_global(name, |n| lookup(self, n))
-            .ok_or_else(|| vm::Error::UndefinedBinding(name.into()))?;
+            .ok_or_else(|| {
+                vm::Error::UndefinedBinding(name.into(), self.binding_suggestions(name))
+            })?;
 
         if remaining_fields.as_str().is_empty() {
             // No fields left
             return Ok(value);
         }
 
-        for mut field_name in remaining_fields.components() {
+        let mut offset = name.len() - remaining_fields.as_str().len();
+        for component in remaining_fields.components() {
+            let span = (offset, offset + component.len());
+            offset += component.len() + 1;
+
+            let mut field_name = component;
             if field_name.starts_with('(') && field_name.ends_with(')') {
                 field_name = &field_name[1..field_name.len() - 1];
             } else if field_name.contains(ast::is_operator_char) {
-                return Err(vm::Error::Message(format!(
-                    "Operators cannot be used as fields \
-                     directly. To access an operator field, \
-                     enclose the operator with parentheses \
-                     before passing it in. (test.(+) instead of \
-                     test.+)"
-                ))
-                .into());
+                return Err(binding_path_error(
+                    name,
+                    span,
+                    vm::Error::Message(format!(
+                        "Operators cannot be used as fields \
+                         directly. To access an operator field, \
+                         enclose the operator with parentheses \
+                         before passing it in. (test.(+) instead of \
+                         test.+)"
+                    )),
+                ));
             }
 
-            value = value.extract(self, field_name).ok_or_else(move || {
-                vm::Error::UndefinedField(value.typ().clone(), field_name.into())
-            })?;
+            value = match value.extract(self, field_name) {
+                Ok(Some(value)) => value,
+                Ok(None) => {
+                    let err = vm::Error::UndefinedField(value.typ().clone(), field_name.into());
+                    return Err(binding_path_error(name, span, err));
+                }
+                Err(Error::VM(err)) => return Err(binding_path_error(name, span, err)),
+                Err(err) => return Err(err),
+            };
         }
 
         Ok(value)
     }
 
+    /// Returns the type of the extern global `name` without rooting its value, which would
+    /// require a thread
+    fn extern_global_type(&self, name: &str) -> Option<ArcType> {
+        if self.state().extern_globals.contains(name) {
+            Some(self.extern_global(name.into()).typ)
+        } else {
+            None
+        }
+    }
+
     pub fn get_metadata(&self, name_str: &str) -> Result<Arc<Metadata>> {
         self.get_metadata_(name_str)
             .ok_or_else(|| vm::Error::MetadataDoesNotExist(name_str.into()).into())
@@ -1112,7 +2635,380 @@ impl CompilerDatabase {
         Some(metadata.clone())
     }
 
+    /// Returns the quantified type variables and the implicit arguments (constraints) of the
+    /// binding at `name`.
+    pub fn type_scheme(&self, name: &str) -> Result<TypeScheme> {
+        let (_, typ) = self.get_binding(name)?;
+        Ok(TypeScheme::new(&typ))
+    }
+
+    /// Returns the types of the explicit parameters and the return type of the function bound at
+    /// `name`, resolving aliases to find each argument. Returns `None` if the binding is not a
+    /// function.
+    pub fn function_signature(&self, name: &str) -> Result<Option<(Vec<ArcType>, ArcType)>> {
+        let (_, mut typ) = self.get_binding(name)?;
+        let env = env(self);
+        let mut args = Vec::new();
+        loop {
+            let resolved = resolve::remove_aliases_cow(
+                &env,
+                &mut NullInterner,
+                typ.remove_forall_and_implicit_args(),
+            );
+            let ret = match resolved
+                .remove_forall_and_implicit_args()
+                .as_explicit_function()
+            {
+                Some((arg, ret)) => {
+                    args.push(arg.clone());
+                    ret.clone()
+                }
+                None => break,
+            };
+            typ = ret;
+        }
+        Ok(if args.is_empty() {
+            None
+        } else {
+            Some((args, typ))
+        })
+    }
+
+    /// Compares the bindings exported by the currently loaded version of `module` against those
+    /// of `previous`. Two bindings are considered to have the same type if each type is an
+    /// instance of the other.
+    pub fn diff_module(
+        &self,
+        module: &str,
+        previous: &TypecheckValue<Arc<OwnedExpr<Symbol>>>,
+    ) -> Result<ModuleDiff> {
+        self.diff_module_type(module, &previous.typ)
+    }
+
+    /// Compares the bindings exported by the currently loaded version of `module` against those
+    /// of the version which was typechecked at `old_revision` (see `module_revision`).
+    ///
+    /// Only the two most recently typechecked revisions of each module are retained so an error
+    /// is returned if `old_revision` is older than that or was never typechecked.
+    pub fn typecheck_diff(&self, module: &str, old_revision: usize) -> Result<ModuleDiff> {
+        let previous = self
+            .state()
+            .module_type_history
+            .get(module)
+            .and_then(|history| {
+                history
+                    .iter()
+                    .find(|&&(revision, _)| revision == old_revision)
+                    .map(|(_, typ)| typ.clone())
+            })
+            .ok_or_else(|| {
+                vm::Error::Message(format!(
+                    "Revision {} of `{}` is no longer available to compare against",
+                    old_revision, module
+                ))
+            })?;
+        self.diff_module_type(module, &previous)
+    }
+
+    fn diff_module_type(&self, module: &str, previous: &ArcType) -> Result<ModuleDiff> {
+        use crate::check::check_signature;
+
+        let current = self
+            .peek_module_type(module)
+            .ok_or_else(|| vm::Error::UndefinedBinding(module.into(), Vec::new()))?;
+
+        let env = self.as_env();
+        let previous_type = resolve::remove_aliases_cow(&env, &mut NullInterner, previous);
+        let current_type = resolve::remove_aliases_cow(&env, &mut NullInterner, &current);
+
+        let previous_fields: FnvMap<_, _> = previous_type
+            .remove_forall()
+            .row_iter()
+            .map(|field| (field.name.declared_name(), &field.typ))
+            .collect();
+
+        let mut diff = ModuleDiff::default();
+        for field in current_type.remove_forall().row_iter() {
+            let name = field.name.declared_name();
+            match previous_fields.get(name) {
+                Some(previous_type) => {
+                    if !check_signature(&env, previous_type, &field.typ)
+                        || !check_signature(&env, &field.typ, previous_type)
+                    {
+                        diff.retyped.push((
+                            name.to_string(),
+                            (*previous_type).clone(),
+                            field.typ.clone(),
+                        ));
+                    }
+                }
+                None => diff.added.push(name.to_string()),
+            }
+        }
+        let current_fields: FnvSet<_> = current_type
+            .remove_forall()
+            .row_iter()
+            .map(|field| field.name.declared_name())
+            .collect();
+        diff.removed.extend(
+            previous_fields
+                .keys()
+                .filter(|name| !current_fields.contains(*name))
+                .map(|name| name.to_string()),
+        );
+        diff.removed.sort();
+        Ok(diff)
+    }
+
+    /// Returns the top-level bindings of `module` which are neither exported nor used by any of
+    /// the exported bindings. `module` must have been typechecked.
+    pub fn unused_bindings(&self, module: &str) -> Result<Vec<(String, Span<BytePos>)>> {
+        let value = self
+            .peek_typechecked_source_module(module)
+            .ok_or_else(|| vm::Error::UndefinedBinding(module.into(), Vec::new()))?;
+        let source_span = self
+            .get_filemap(module)
+            .ok_or_else(|| vm::Error::UndefinedBinding(module.into(), Vec::new()))?
+            .span();
+
+        Ok(
+            crate::check::unused::unused_bindings(source_span, value.expr.expr())
+                .into_iter()
+                .map(|name| (name.value.declared_name().to_string(), name.span))
+                .collect(),
+        )
+    }
+
+    /// Returns the modules imported by `module` the last time its imports were computed (which
+    /// happens when it is loaded)
+    pub fn module_dependencies(&self, module: &str) -> Option<BTreeSet<String>> {
+        ModuleImportsQuery
+            .in_db(self)
+            .peek(&module.into())
+            .and_then(|r| r.ok())
+            .map(|dependencies| dependencies.imports.clone())
+    }
+
+    /// Returns `module` followed by every module which imports it, directly or transitively.
+    /// These are the modules which may need to be recompiled after `module` has been edited.
+    ///
+    /// Only modules which have been loaded by this database are considered.
+    pub fn affected_by_edit(&self, module: &str) -> Result<Vec<String>> {
+        self.affected_modules(module, None)
+    }
+
+    /// Like `affected_by_edit` but only for an edit which changed the exports of `module` named
+    /// in `changed_exports` (see `typecheck_diff`).
+    ///
+    /// A module which imports `module` directly is only included if it uses one of
+    /// `changed_exports`, or if it uses `module` without naming the fields it uses. Every module
+    /// which imports an included module is then included as well.
+    pub fn affected_by_export_edit(
+        &self,
+        module: &str,
+        changed_exports: &[&str],
+    ) -> Result<Vec<String>> {
+        self.affected_modules(module, Some(changed_exports))
+    }
+
+    fn affected_modules(
+        &self,
+        module: &str,
+        changed_exports: Option<&[&str]>,
+    ) -> Result<Vec<String>> {
+        let loaded: Vec<(String, Arc<Dependencies>)> = ModuleImportsQuery
+            .in_db(self)
+            .entries::<Vec<_>>()
+            .into_iter()
+            .filter_map(|entry| Some((entry.key, entry.value?.ok()?)))
+            // Skip modules which have been removed since they were loaded
+            .filter(|(module, _)| {
+                self.get_filemap(module).is_some()
+                    || ExternLoaderQuery.in_db(self).peek(module).is_some()
+            })
+            .collect();
+
+        let prefix = format!("{}.", module);
+        let mut importers: ModuleMap<&str, Vec<&str>> = ModuleMap::default();
+        for (importer, dependencies) in &loaded {
+            for import in &dependencies.imports {
+                match changed_exports {
+                    Some(changed_exports) if import == module => {
+                        let mut used_fields = dependencies
+                            .globals
+                            .iter()
+                            .filter_map(|global| global.strip_prefix(&prefix[..]))
+                            .peekable();
+                        // If no fields are named the module may be used as a whole
+                        let affected = used_fields.peek().is_none()
+                            || used_fields.any(|field| changed_exports.contains(&field));
+                        if !affected {
+                            continue;
+                        }
+                    }
+                    _ => (),
+                }
+                importers
+                    .entry(&import[..])
+                    .or_default()
+                    .push(&importer[..]);
+            }
+        }
+        if loaded.iter().all(|(loaded, _)| loaded != module) && !importers.contains_key(module) {
+            return Err(vm::Error::UndefinedBinding(module.into(), Vec::new()).into());
+        }
+
+        let mut visited = ModuleSet::default();
+        visited.insert(module);
+        let mut affected = vec![module];
+        let mut i = 0;
+        while i < affected.len() {
+            if let Some(importers) = importers.get(affected[i]) {
+                let mut new_importers: Vec<_> = importers
+                    .iter()
+                    .cloned()
+                    .filter(|importer| visited.insert(*importer))
+                    .collect();
+                new_importers.sort();
+                affected.extend(new_importers);
+            }
+            i += 1;
+        }
+        Ok(affected.into_iter().map(String::from).collect())
+    }
+
     pub fn as_env(&self) -> Env<&Self> {
         env(self)
     }
 }
+
+#[cfg(feature = "serialization")]
+impl CompilerDatabase {
+    /// Serializes the current value of the binding at `name` into bytes.
+    ///
+    /// The value can be restored with `deserialize_value`.
+    pub fn serialize_value(&self, name: &str) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        self.serialize_value_with(name, &mut serde_json::Serializer::new(&mut buffer))
+            .map_err(|err| err.either(|err| err, |err| Error::from(err.to_string())))?;
+        Ok(buffer)
+    }
+
+    /// Reconstructs a value from the bytes returned by `serialize_value`
+    pub fn deserialize_value(&self, bytes: &[u8]) -> Result<RootedValue<RootedThread>> {
+        self.deserialize_value_with(&mut serde_json::Deserializer::from_slice(bytes))
+            .map_err(|err| Error::from(err.to_string()))
+    }
+
+    /// Serializes the current value of the binding at `name` using `serializer`.
+    ///
+    /// The value can be restored with `deserialize_value_with`.
+    pub fn serialize_value_with<S>(
+        &self,
+        name: &str,
+        serializer: S,
+    ) -> StdResult<S::Ok, either::Either<Error, S::Error>>
+    where
+        S: crate::serde::Serializer,
+    {
+        use crate::serde::ser::SerializeState;
+        use vm::serialization::SeSeed;
+
+        let (value, _) = self.get_binding(name).map_err(either::Either::Left)?;
+        value
+            .get_variants()
+            .serialize_state(serializer, &SeSeed::new())
+            .map_err(either::Either::Right)
+    }
+
+    /// Converts the value bound at `name` (`std.int.abs`) into JSON, using its type to label the
+    /// fields of records.
+    ///
+    /// Records become objects, arrays become arrays and numbers, strings and `Bool` become JSON
+    /// scalars. Other variants are tagged like serde tags enums: a variant without arguments
+    /// becomes its name (`"None"`) and other variants an object from the name to the argument, or
+    /// an array of the arguments (`{ "Some": 1 }`). Functions, userdata and threads can't be
+    /// represented so they become placeholder strings (`"<function>"`).
+    pub fn value_to_json(&self, name: &str) -> Result<serde_json::Value> {
+        let (value, typ) = self.get_binding(name)?;
+        Ok(self.variants_to_json(value.get_variants(), &typ))
+    }
+
+    fn variants_to_json(&self, value: vm::Variants, typ: &ArcType) -> serde_json::Value {
+        use serde_json::Value as Json;
+
+        let typ = resolve::remove_aliases_cow(&env(self), &mut NullInterner, typ.remove_forall());
+        if typ.as_function().is_some() {
+            return Json::from("<function>");
+        }
+        match value.as_ref() {
+            ValueRef::Byte(b) => Json::from(b),
+            ValueRef::Int(i) => Json::from(i),
+            ValueRef::Float(f) => Json::from(f),
+            ValueRef::String(s) => Json::from(s),
+            ValueRef::Array(array) => {
+                let element_type = array_element_type(&typ).unwrap_or_else(Type::hole);
+                Json::Array(
+                    array
+                        .iter()
+                        .map(|element| self.variants_to_json(element, &element_type))
+                        .collect(),
+                )
+            }
+            ValueRef::Data(data) => match **typ {
+                Type::Record(_) => Json::Object(
+                    typ.row_iter()
+                        .zip(data.iter())
+                        .map(|(field, value)| {
+                            (
+                                field.name.declared_name().to_string(),
+                                self.variants_to_json(value, &field.typ),
+                            )
+                        })
+                        .collect(),
+                ),
+                Type::Variant(_) => {
+                    let names: Vec<_> = typ
+                        .row_iter()
+                        .map(|variant| variant.name.declared_name())
+                        .collect();
+                    if names == ["False", "True"] {
+                        return Json::Bool(data.tag() == 1);
+                    }
+                    let variant = match typ.row_iter().nth(data.tag() as usize) {
+                        Some(variant) => variant,
+                        None => return Json::from("<data>"),
+                    };
+                    let mut args: Vec<_> = crate::base::types::ctor_args(&variant.typ)
+                        .zip(data.iter())
+                        .map(|(typ, value)| self.variants_to_json(value, typ))
+                        .collect();
+                    let name = variant.name.declared_name().to_string();
+                    match args.len() {
+                        0 => Json::String(name),
+                        1 => Json::Object(Some((name, args.pop().unwrap())).into_iter().collect()),
+                        _ => Json::Object(Some((name, Json::Array(args))).into_iter().collect()),
+                    }
+                }
+                _ => Json::from("<data>"),
+            },
+            ValueRef::Userdata(_) => Json::from("<userdata>"),
+            ValueRef::Thread(_) => Json::from("<thread>"),
+            ValueRef::Closure(_) | ValueRef::Internal => Json::from("<function>"),
+        }
+    }
+
+    /// Reconstructs a value which were serialized with `serialize_value_with`
+    pub fn deserialize_value_with<'de, D>(
+        &self,
+        deserializer: D,
+    ) -> StdResult<RootedValue<RootedThread>, D::Error>
+    where
+        D: crate::serde::Deserializer<'de>,
+    {
+        use vm::serialization::DeSeed;
+
+        let thread = self.thread();
+        DeSeed::new(thread, &mut thread.current_context()).deserialize(deserializer)
+    }
+}
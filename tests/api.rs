@@ -6,12 +6,16 @@ extern crate gluon_vm;
 #[macro_use]
 extern crate gluon_codegen;
 
-use std::{collections::BTreeMap, sync::Arc};
+use std::{any::Any, collections::BTreeMap, sync::Arc};
 
 use futures::prelude::*;
 
 use gluon::{
-    base::types::{Alias, ArcType, Type},
+    base::{
+        ast::{self, SpannedExpr},
+        symbol::{Symbol, Symbols},
+        types::{Alias, ArcType, Type},
+    },
     import::{add_extern_module, add_extern_module_with_deps, Import},
     query::Compilation,
     vm::{
@@ -20,7 +24,8 @@ use gluon::{
             scoped::{Ref, RefMut},
             FunctionRef, FutureResult, Hole, OpaqueValue, OwnedFunction, RuntimeResult, VmType, IO,
         },
-        gc,
+        gc::{self, Trace},
+        macros::{self, Macro, MacroExpander, MacroFuture, MacroUserdata},
         thread::{RootedThread, Thread},
         types::VmInt,
         Error, ExternModule,
@@ -43,6 +48,62 @@ fn make_vm() -> RootedThread {
     vm
 }
 
+/// Macro which expands by calling a closure, see `fn_macro`
+struct FnMacro<F>(F);
+
+unsafe impl<F> Trace for FnMacro<F> {
+    impl_trace! { self, _gc, () }
+}
+
+impl<F> Macro for FnMacro<F>
+where
+    F: for<'a, 'ast> Fn(
+            &mut MacroExpander<'a>,
+            &mut Symbols,
+            &mut ast::OwnedArena<'ast, Symbol>,
+            &mut [SpannedExpr<'ast, Symbol>],
+        ) -> Result<SpannedExpr<'ast, Symbol>, macros::Error>
+        + Send
+        + Sync
+        + 'static,
+{
+    fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+        &self,
+        env: &'b mut MacroExpander<'a>,
+        symbols: &'c mut Symbols,
+        arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+        args: &'b mut [SpannedExpr<'ast, Symbol>],
+    ) -> MacroFuture<'r, 'ast> {
+        let result = (self.0)(env, symbols, arena, args).map(Into::into);
+        Box::pin(future::ready(result))
+    }
+}
+
+/// Creates a macro which expands to the expression returned by `expand`
+fn fn_macro<F>(expand: F) -> FnMacro<F>
+where
+    F: for<'a, 'ast> Fn(
+            &mut MacroExpander<'a>,
+            &mut Symbols,
+            &mut ast::OwnedArena<'ast, Symbol>,
+            &mut [SpannedExpr<'ast, Symbol>],
+        ) -> Result<SpannedExpr<'ast, Symbol>, macros::Error>
+        + Send
+        + Sync
+        + 'static,
+{
+    FnMacro(expand)
+}
+
+/// Userdata for tests which run a `MacroExpander` without a database
+struct NoUserdata;
+
+impl MacroUserdata for NoUserdata {
+    fn fork(&self, _thread: RootedThread) -> Box<dyn Any> {
+        Box::new(())
+    }
+}
+
 #[derive(Debug, Userdata, Trace)]
 struct Test(VmInt);
 impl VmType for Test {
@@ -777,3 +838,2541 @@ fn clone_userdata() {
 
     assert_eq!(*result, Test(123));
 }
+
+#[test]
+fn extern_module_not_in_allowlist_is_rejected() {
+    let _ = ::env_logger::try_init();
+
+    fn my_module(thread: &Thread) -> gluon::vm::Result<ExternModule> {
+        ExternModule::new(thread, record! { value => 1 })
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "my_module", my_module);
+    add_extern_module(&vm, "other_module", my_module);
+
+    {
+        let mut db = vm.get_database_mut();
+        db.set_implicit_prelude(false);
+        db.set_allowed_extern_modules(Some(vec!["my_module".to_string()].into_iter().collect()));
+    }
+
+    let (result, _) = vm
+        .run_expr::<VmInt>("allowed", "let m = import! my_module in m.value")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 1);
+
+    let err = vm
+        .run_expr::<VmInt>("not_allowed", "let m = import! other_module in m.value")
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("not permitted"),
+        "Unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn unused_top_level_bindings() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let text = r#"
+let used = 1
+let only_used_by_unused = 2
+let unused = only_used_by_unused
+let helper x = x + used
+{ helper }
+"#;
+    vm.typecheck_str("unused_bindings", text, None)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let unused = vm
+        .get_database()
+        .unused_bindings("unused_bindings")
+        .unwrap_or_else(|err| panic!("{}", err));
+    let names: Vec<_> = unused.into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, ["only_used_by_unused", "unused"]);
+}
+
+#[test]
+fn unused_bindings_allow_attribute() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let text = r#"
+let used = 1
+let unused = 2
+#[allow(unused)]
+let kept = used
+{ used }
+"#;
+    vm.typecheck_str("unused_bindings_allow", text, None)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let unused = vm
+        .get_database()
+        .unused_bindings("unused_bindings_allow")
+        .unwrap_or_else(|err| panic!("{}", err));
+    let names: Vec<_> = unused.into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, ["unused"]);
+}
+
+#[test]
+fn extern_module_with_specialized_type() {
+    use gluon::base::{
+        symbol::Symbol,
+        types::{Field, Type},
+    };
+    use gluon::vm::api::generic::A;
+    use gluon::vm::api::Generic;
+
+    let _ = ::env_logger::try_init();
+
+    fn test(_: Generic<A>) {}
+
+    let vm = make_vm();
+
+    let specialized: ArcType = Type::record(
+        vec![],
+        vec![Field::new(
+            Symbol::from("test"),
+            Type::function(vec![Type::int()], Type::unit()),
+        )],
+    );
+    let module = ExternModule::with_type(
+        &vm,
+        record! { test => primitive!(1, test) },
+        specialized.clone(),
+        Default::default(),
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(module.typ, specialized);
+
+    let not_specialized = Type::record(
+        vec![],
+        vec![Field::new(
+            Symbol::from("test"),
+            Type::function(vec![Type::int()], Type::int()),
+        )],
+    );
+    assert!(ExternModule::with_type(
+        &vm,
+        record! { test => primitive!(1, test) },
+        not_specialized,
+        Default::default(),
+    )
+    .is_err());
+}
+
+#[test]
+fn type_scheme_of_implicit_binding() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.run_expr::<()>("load", "let _ = import! std.show in ()")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let scheme = vm
+        .get_database()
+        .type_scheme("std.show.show")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        scheme
+            .params
+            .iter()
+            .map(|param| param.id.declared_name())
+            .collect::<Vec<_>>(),
+        ["a"]
+    );
+    assert_eq!(scheme.constraints.len(), 1);
+    assert!(
+        scheme.constraints[0].to_string().ends_with("Show a"),
+        "{}",
+        scheme.constraints[0]
+    );
+    assert_eq!(scheme.typ.to_string(), "a -> String");
+}
+
+#[test]
+fn push_with_closure() {
+    use gluon::vm::api::{convert, with_push, Pushable};
+    use std::time::Duration;
+
+    let vm = make_vm();
+    let value = with_push::<(VmInt, VmInt), _, _>(Duration::new(3, 500), |duration, context| {
+        (
+            duration.as_secs() as VmInt,
+            duration.subsec_nanos() as VmInt,
+        )
+            .vm_push(context)
+    });
+    // Tuple types get fresh field symbols each time so only their printed form is comparable
+    assert_eq!(
+        <gluon::vm::api::PushWith<Duration, fn(), (VmInt, VmInt)>>::make_type(&vm).to_string(),
+        <(VmInt, VmInt)>::make_type(&vm).to_string()
+    );
+    assert_eq!(convert::<_, (VmInt, VmInt)>(&vm, value).unwrap(), (3, 500));
+}
+
+#[test]
+fn diff_module_versions() {
+    use gluon::query::{AsyncCompilation, CompilationBase};
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_database_mut()
+        .add_module("hot".into(), r#" { keep = 1, retype = 2, remove = "a" } "#);
+    let previous = futures::executor::block_on(
+        vm.get_database()
+            .typechecked_source_module("hot".into(), None),
+    )
+    .unwrap_or_else(|err| panic!("{}", err.error));
+
+    load_script(&vm, "hot", r#" { keep = 3, retype = "b", add = 1.0 } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let diff = vm
+        .get_database()
+        .diff_module("hot", &previous)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(diff.added, ["add"]);
+    assert_eq!(diff.removed, ["remove"]);
+    assert_eq!(
+        diff.retyped
+            .iter()
+            .map(|(name, _, _)| &name[..])
+            .collect::<Vec<_>>(),
+        ["retype"]
+    );
+    assert!(diff.is_breaking());
+}
+
+#[test]
+fn typecheck_diff_between_revisions() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    load_script(&vm, "outline", r#" { before = 1, keep = "a" } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let old_revision = vm.get_database().module_revision("outline");
+
+    load_script(&vm, "outline", r#" { after = 1, keep = "a" } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let diff = vm
+        .get_database()
+        .typecheck_diff("outline", old_revision)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(diff.added, ["after"]);
+    assert_eq!(diff.removed, ["before"]);
+    assert!(diff.retyped.is_empty());
+
+    // Only the two most recent revisions are kept
+    load_script(&vm, "outline", r#" { last = 1, keep = "a" } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(vm
+        .get_database()
+        .typecheck_diff("outline", old_revision)
+        .is_err());
+
+    // Removing the module forgets its history
+    let last_revision = vm.get_database().module_revision("outline");
+    vm.get_database_mut().remove_module("outline");
+    load_script(&vm, "outline", r#" { last = 1 } "#).unwrap_or_else(|err| panic!("{}", err));
+    assert!(vm
+        .get_database()
+        .typecheck_diff("outline", last_revision)
+        .is_err());
+}
+
+#[test]
+fn complete_record_field() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    load_script(
+        &vm,
+        "config",
+        r#"
+        type Config = { server : String, serialization : Bool, port : Int }
+        let config : Config = { server = "localhost", serialization = True, port = 80 }
+        { config }
+        "#,
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    let db = vm.get_database();
+    let (_, typ) = db
+        .get_binding("config.config")
+        .unwrap_or_else(|err| panic!("{}", err));
+    let completions = db.complete_field(&typ, "ser");
+    assert_eq!(
+        completions
+            .iter()
+            .map(|(name, _)| &name[..])
+            .collect::<Vec<_>>(),
+        ["server", "serialization"]
+    );
+    assert_eq!(completions[0].1, Type::string());
+}
+
+#[test]
+fn replay_compilation_trace() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_database().start_trace();
+    vm.get_database_mut().set_optimize(false);
+    load_script(
+        &vm,
+        "traced",
+        r#" let { id } = import! std.function in { x = id 1 } "#,
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    let trace = vm.get_database().take_trace().expect("trace");
+
+    let vm2 = make_vm();
+    trace.replay(&mut vm2.get_database_mut());
+    assert!(!vm2.get_database().compiler_settings().optimize);
+
+    let (x, _) = vm2
+        .run_expr::<VmInt>("test", "let { x } = import! traced in x")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(x, 1);
+}
+
+#[test]
+fn analyze_expr_dependencies() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    let analysis = vm
+        .analyze_expr(
+            "analyze",
+            r#"
+            let { abs } = import! std.int
+            let string = import! std.string
+            string.len "abc" + abs 1
+            "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        analysis.imports.iter().collect::<Vec<_>>(),
+        ["std.int", "std.string"]
+    );
+    assert_eq!(
+        analysis.globals.iter().collect::<Vec<_>>(),
+        ["std.int.abs", "std.string.len"]
+    );
+    assert_eq!(analysis.typ, Type::int());
+}
+
+#[test]
+fn extern_module_metadata() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    fn new_struct(x: VmInt) -> VmInt {
+        x
+    }
+
+    add_extern_module(&vm, "functions", |vm| {
+        Ok(ExternModule::new(
+            vm,
+            record! {
+                new_struct => primitive!(1, new_struct)
+            },
+        )?
+        .doc("new_struct", "Creates a new struct")
+        .attribute("new_struct", "deprecated", Some("\"Use something else\"")))
+    });
+
+    vm.run_expr::<OpaqueValue<&Thread, Hole>>("test", "import! functions")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let metadata = vm
+        .get_env()
+        .get_metadata("functions.new_struct")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        metadata
+            .comment
+            .as_ref()
+            .map(|comment| &comment.content[..]),
+        Some("Creates a new struct")
+    );
+    assert_eq!(
+        metadata.get_attribute("deprecated"),
+        Some("\"Use something else\"")
+    );
+}
+
+#[test]
+fn module_metadata_tree() {
+    use gluon::query::AsyncCompilation;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let text = r#"
+/// Adds one
+let add_one x = x #Int+ 1
+
+let inner =
+    /// Subtracts one
+    let sub_one x = x #Int- 1
+    { sub_one }
+
+{ add_one, inner }
+"#;
+    load_script(&vm, "documented", text).unwrap_or_else(|err| panic!("{}", err));
+
+    let metadata =
+        futures::executor::block_on(vm.get_database().module_metadata("documented".into(), None))
+            .unwrap_or_else(|err| panic!("{}", err.error));
+
+    let comment = |metadata: &gluon::base::metadata::Metadata| {
+        metadata
+            .comment
+            .as_ref()
+            .map(|comment| comment.content.clone())
+    };
+    assert_eq!(
+        comment(&metadata.module["add_one"]),
+        Some("Adds one".to_string())
+    );
+    assert_eq!(
+        comment(&metadata.module["inner"].module["sub_one"]),
+        Some("Subtracts one".to_string())
+    );
+}
+
+#[test]
+fn find_var_with_metadata() {
+    use gluon::base::symbol::Symbol;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    load_script(
+        &vm,
+        "documented_var",
+        "/// Doubles its argument\nlet double x = x #Int* 2\n{ double }",
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    let db = vm.get_database();
+    let (_, typ, metadata) = db
+        .as_env()
+        .find_var_with_metadata(&Symbol::from("@documented_var"))
+        .expect("documented_var");
+    assert_eq!(typ.to_string(), "{ double : Int -> Int }");
+    assert_eq!(
+        metadata.module["double"]
+            .comment
+            .as_ref()
+            .map(|comment| &comment.content[..]),
+        Some("Doubles its argument")
+    );
+}
+
+#[test]
+fn pinned_module_survives_garbage_collection() {
+    use gluon::query::CompilationBase;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.get_database().pin_module("pinned");
+    load_script(&vm, "pinned", "{ x = 1 }").unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "transient", "{ y = 2 }").unwrap_or_else(|err| panic!("{}", err));
+
+    // Values used in the current revision are never discarded so edit a module to start a new one
+    vm.get_database_mut()
+        .set_module_source("transient", "{ y = 3 }");
+    let db = vm.get_database();
+    assert!(db.peek_typechecked_source_module("pinned").is_some());
+    assert!(db.peek_typechecked_source_module("transient").is_none());
+
+    db.unpin_module("pinned");
+    drop(db);
+    vm.get_database_mut()
+        .set_module_source("transient", "{ y = 4 }");
+    assert!(vm
+        .get_database()
+        .peek_typechecked_source_module("pinned")
+        .is_none());
+}
+
+#[test]
+fn pinned_module_is_not_recomputed_after_garbage_collection() {
+    use gluon::query::{AsyncCompilation, CompilationBase};
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.get_database().pin_module("pinned_hot");
+    load_script(&vm, "pinned_hot", "{ x = 1 }").unwrap_or_else(|err| panic!("{}", err));
+    let before = vm
+        .get_database()
+        .peek_typechecked_source_module("pinned_hot")
+        .expect("pinned_hot is typechecked");
+
+    // Importing another module runs `collect_garbage`
+    load_script(&vm, "transient_cold", "{ y = 2 }").unwrap_or_else(|err| panic!("{}", err));
+
+    let after = futures::executor::block_on(
+        vm.get_database()
+            .typechecked_source_module("pinned_hot".into(), None),
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    assert!(Arc::ptr_eq(&before.expr, &after.expr));
+}
+
+#[test]
+fn pinned_module_is_recomputed_after_change() {
+    use gluon::query::{AsyncCompilation, CompilationBase};
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.get_database().pin_module("pinned_edit");
+    load_script(&vm, "pinned_edit", "{ x = 1 }").unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "transient_edit", "{ y = 2 }").unwrap_or_else(|err| panic!("{}", err));
+
+    vm.get_database_mut()
+        .set_module_source("pinned_edit", r#"{ x = "a" }"#);
+
+    let value = futures::executor::block_on(
+        vm.get_database()
+            .typechecked_source_module("pinned_edit".into(), None),
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    assert!(value.typ.to_string().contains("String"));
+}
+
+#[test]
+fn pinned_module_is_recomputed_after_import_change() {
+    use gluon::query::{AsyncCompilation, CompilationBase};
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.get_database().pin_module("pinned_importer");
+    load_script(&vm, "pinned_dep", "{ x = 1 }").unwrap_or_else(|err| panic!("{}", err));
+    load_script(
+        &vm,
+        "pinned_importer",
+        "let { x } = import! pinned_dep\n{ y = x }",
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    vm.get_database_mut()
+        .set_module_source("pinned_dep", r#"{ x = "a" }"#);
+
+    let value = futures::executor::block_on(
+        vm.get_database()
+            .typechecked_source_module("pinned_importer".into(), None),
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    assert!(value.typ.to_string().contains("String"));
+}
+
+#[test]
+fn cache_capacity_keeps_recently_used_modules() {
+    use gluon::query::{AsyncCompilation, CompilationBase};
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    {
+        let mut db = vm.get_database_mut();
+        // The prelude modules would otherwise count as used as well
+        db.set_implicit_prelude(false);
+        // The LRU of salsa keeps at least three values
+        db.set_cache_capacity(3);
+    }
+    load_script(&vm, "lru_a", "{ a = 1 }").unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "lru_b", "{ b = 2 }").unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "lru_c", "{ c = 3 }").unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "lru_d", "{ d = 4 }").unwrap_or_else(|err| panic!("{}", err));
+
+    // Values used in the current revision are never discarded so edit a module to start a new one
+    vm.get_database_mut()
+        .set_module_source("lru_d", "{ d = 5 }");
+    {
+        let db = vm.get_database();
+        assert!(db.peek_typechecked_source_module("lru_a").is_none());
+        assert!(db.peek_typechecked_source_module("lru_b").is_some());
+        assert!(db.peek_typechecked_source_module("lru_c").is_some());
+    }
+
+    // Requesting the evicted module recompiles it and makes it the most recently used module
+    futures::executor::block_on(
+        vm.get_database()
+            .typechecked_source_module("lru_a".into(), None),
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "lru_e", "{ e = 6 }").unwrap_or_else(|err| panic!("{}", err));
+
+    vm.get_database_mut()
+        .set_module_source("lru_d", "{ d = 7 }");
+    let db = vm.get_database();
+    assert!(db.peek_typechecked_source_module("lru_a").is_some());
+    assert!(db.peek_typechecked_source_module("lru_b").is_none());
+    assert!(db.peek_typechecked_source_module("lru_e").is_some());
+}
+
+#[test]
+fn literal_suffix_macro() {
+    use gluon::base::{
+        ast::{Expr, Literal},
+        pos,
+    };
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros().insert_literal_suffix(
+        "px".into(),
+        fn_macro(|_env, _symbols, _arena, args| match args[0].value {
+            Expr::Literal(Literal::Int(i)) => Ok(pos::spanned(
+                args[0].span,
+                Expr::Literal(Literal::Int(i * 10)),
+            )),
+            _ => Err(macros::Error::message("Expected an Int literal")),
+        }),
+    );
+
+    let (result, _) = vm
+        .run_expr::<VmInt>("test", "5px + 1")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 51);
+}
+
+#[test]
+fn literal_suffix_macro_argument() {
+    use gluon::base::{
+        ast::{Expr, Literal},
+        pos,
+    };
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros().insert_literal_suffix(
+        "px".into(),
+        fn_macro(|_env, _symbols, _arena, args| match args[0].value {
+            Expr::Literal(Literal::Int(i)) => Ok(pos::spanned(
+                args[0].span,
+                Expr::Literal(Literal::Int(i * 10)),
+            )),
+            _ => Err(macros::Error::message("Expected an Int literal")),
+        }),
+    );
+
+    let text = r#"
+        let add x y = x + y
+        add 5px 2px
+        "#;
+    let (result, _) = vm
+        .run_expr::<VmInt>("test", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 70);
+}
+
+#[test]
+fn literal_suffix_without_macro() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let err = vm.run_expr::<VmInt>("test", "5px").unwrap_err().to_string();
+    assert!(
+        err.contains("No macro is registered for the literal suffix `px`"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn literal_suffix_macro_record() {
+    use gluon::base::{
+        ast::{Expr, ExprField},
+        pos,
+        types::Type,
+    };
+
+    /// Rewrites `10px` into `{ px = 10 }`
+    fn tagged(tag: &'static str) -> impl Macro {
+        fn_macro(move |_env, symbols, arena, args| {
+            let span = args[0].span;
+            let literal = match &args[0].value {
+                Expr::Literal(literal) => literal.clone(),
+                _ => return Err(macros::Error::message("Expected a literal")),
+            };
+            let field = ExprField {
+                metadata: Default::default(),
+                name: pos::spanned(span, symbols.simple_symbol(tag)),
+                value: Some(pos::spanned(span, Expr::Literal(literal))),
+            };
+            Ok(pos::spanned(
+                span,
+                Expr::Record {
+                    typ: Type::hole(),
+                    types: &mut [],
+                    exprs: arena.alloc_extend(Some(field)),
+                    base: None,
+                },
+            ))
+        })
+    }
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros()
+        .insert_literal_suffix("px".into(), tagged("px"));
+    vm.get_macros()
+        .insert_literal_suffix("sql".into(), tagged("sql"));
+
+    let text = r#"
+        let { px } = 10px
+        let { sql } = "select 1"sql
+        if sql == "select 1" then px else 0
+        "#;
+    let (result, _) = vm
+        .run_expr::<VmInt>("test", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 10);
+}
+
+#[test]
+fn remove_macro() {
+    let vm = make_vm();
+    let macros = vm.get_macros();
+    assert!(!macros.contains("noop"));
+
+    macros.insert(
+        "noop".into(),
+        fn_macro(|_env, _symbols, _arena, _args| Err(macros::Error::message("noop"))),
+    );
+    assert!(macros.contains("noop"));
+
+    assert!(macros.remove("noop").is_some());
+    assert!(!macros.contains("noop"));
+    assert!(macros.get("noop").is_none());
+    assert!(macros.remove("noop").is_none());
+}
+
+#[test]
+fn macro_expected_type() {
+    use gluon::base::{
+        ast::{Expr, Literal},
+        pos,
+    };
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros().insert(
+        "expected_type".into(),
+        fn_macro(|env, _symbols, _arena, args| {
+            let typ = env
+                .expected_type()
+                .map_or_else(|| "unknown".to_string(), |typ| typ.to_string());
+            Ok(pos::spanned(
+                args[0].span,
+                Expr::Literal(Literal::String(typ)),
+            ))
+        }),
+    );
+
+    let (result, _) = vm
+        .run_expr::<String>("test", "expected_type! ()")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, "String");
+}
+
+#[test]
+fn macro_expect_args() {
+    use gluon::base::{
+        ast::{Expr, Literal},
+        pos,
+    };
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros().insert(
+        "pair".into(),
+        fn_macro(|env, _symbols, _arena, args| {
+            let span = args[0].span;
+            let expr = match env.expect_args(span, args, 2..=2) {
+                Ok(()) => Expr::Literal(Literal::Int(2)),
+                Err(()) => Expr::Error(None),
+            };
+            Ok(pos::spanned(span, expr))
+        }),
+    );
+
+    let err = vm
+        .run_expr::<()>("test", "pair! 1")
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("called with 2 arguments but it was called with 1"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn macro_expansion_depth_limit() {
+    use gluon::base::{
+        ast::{Expr, Literal, TypedIdent},
+        pos,
+    };
+
+    /// Expands `recurse! x` to `recurse! x` and expands the result again
+    #[derive(Trace)]
+    #[gluon_trace(skip)]
+    struct Recurse;
+
+    impl Macro for Recurse {
+        fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+            &self,
+            env: &'b mut MacroExpander<'a>,
+            symbols: &'c mut Symbols,
+            arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+            args: &'b mut [SpannedExpr<'ast, Symbol>],
+        ) -> MacroFuture<'r, 'ast> {
+            let span = args[0].span;
+            Box::pin(async move {
+                let func =
+                    pos::spanned(span, Expr::Ident(TypedIdent::new(Symbol::from("recurse!"))));
+                let arg = pos::spanned(span, Expr::Literal(Literal::Int(0)));
+                let mut expr = pos::spanned(span, Expr::app(arena.borrow(), func, Some(arg)));
+                env.run_once(symbols, arena, &mut expr).await;
+                Ok(expr.into())
+            })
+        }
+    }
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros().insert("recurse".into(), Recurse);
+
+    let err = vm
+        .run_expr::<VmInt>("test", "recurse! 0")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("macro expansion depth exceeded"), "{}", err);
+}
+
+#[test]
+fn macro_shared_state() {
+    use gluon::base::{
+        ast::{Expr, Literal},
+        pos,
+    };
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros().insert(
+        "counter".into(),
+        fn_macro(|env, _symbols, _arena, args| {
+            let previous = env.get_state::<i64>("counter").cloned();
+            let counter = env.state_entry::<i64>("counter");
+            assert_eq!(previous.unwrap_or_default(), *counter);
+            *counter += 1;
+            Ok(pos::spanned(
+                args[0].span,
+                Expr::Literal(Literal::Int(*counter)),
+            ))
+        }),
+    );
+
+    let (result, _) = vm
+        .run_expr::<(VmInt, VmInt)>("test", "(counter! (), counter! ())")
+        .unwrap_or_else(|err| panic!("{}", err));
+    let (first, second) = result;
+    assert_eq!(first.min(second), 1);
+    assert_eq!(first.max(second), 2);
+}
+
+#[test]
+fn macro_reads_settings() {
+    use gluon::{
+        base::{
+            ast::{Expr, Literal},
+            pos,
+        },
+        MacroExpanderExt,
+    };
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros().insert(
+        "optimized".into(),
+        fn_macro(|env, _symbols, _arena, args| {
+            let optimize = env.settings().expect("Settings").optimize;
+            let expr = Expr::Literal(Literal::Int(if optimize { 1 } else { 0 }));
+            Ok(pos::spanned(args[0].span, expr))
+        }),
+    );
+
+    let (result, _) = vm
+        .run_expr::<VmInt>("test", "optimized! ()")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 1);
+
+    vm.get_database_mut().set_optimize(false);
+    let (result, _) = vm
+        .run_expr::<VmInt>("test2", "optimized! ()")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 0);
+}
+
+#[test]
+fn macro_error_notes_in_other_module() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    load_script(
+        &vm,
+        "note_definition",
+        "let replacement = 1
+{ replacement }",
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    let span = vm
+        .get_database()
+        .get_filemap("note_definition")
+        .expect("filemap")
+        .span();
+    vm.get_macros().insert(
+        "deprecated".into(),
+        fn_macro(move |_env, _symbols, _arena, _args| {
+            Err(macros::Error::message("`deprecated!` may not be used")
+                .with_note(span, "replacement defined here"))
+        }),
+    );
+
+    let err = vm
+        .run_expr::<()>("note_user", "deprecated! 1")
+        .unwrap_err()
+        .emit_string()
+        .unwrap();
+    assert!(err.contains("note_user"), "{}", err);
+    assert!(err.contains("note_definition"), "{}", err);
+    assert!(err.contains("replacement defined here"), "{}", err);
+}
+
+#[test]
+fn macro_error_notes() {
+    use gluon::base::pos::{self, BytePos};
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros().insert(
+        "no_arguments".into(),
+        fn_macro(|_env, _symbols, _arena, args| {
+            Err(
+                macros::Error::message("`no_arguments!` does not take any arguments")
+                    .with_note(args[0].span, "unexpected argument"),
+            )
+        }),
+    );
+
+    let err = vm
+        .run_expr::<()>("test", "no_arguments! 1")
+        .unwrap_err()
+        .emit_string()
+        .unwrap();
+    assert!(err.contains("does not take any arguments"), "{}", err);
+    assert!(err.contains("unexpected argument"), "{}", err);
+
+    // Notes do not change the identity of an error
+    let span = pos::span(BytePos::from(0), BytePos::from(1));
+    assert_eq!(
+        macros::Error::message("error").with_note(span, "note"),
+        macros::Error::message("error")
+    );
+}
+
+#[test]
+fn pre_expansion_transform() {
+    use gluon::{
+        base::{
+            ast::{self, Expr, Literal, SpannedExpr},
+            symbol::{Symbol, Symbols},
+        },
+        vm::macros::AstTransform,
+    };
+
+    struct DoubleLiteral;
+
+    impl AstTransform for DoubleLiteral {
+        fn transform<'ast>(
+            &self,
+            _symbols: &mut Symbols,
+            _arena: ast::ArenaRef<'_, 'ast, Symbol>,
+            expr: &mut SpannedExpr<'ast, Symbol>,
+        ) {
+            if let Expr::Literal(Literal::Int(i)) = &mut expr.value {
+                *i *= 2;
+            }
+        }
+    }
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_database_mut().set_implicit_prelude(false);
+    vm.get_macros().add_pre_expansion_transform(DoubleLiteral);
+
+    let (result, _) = vm
+        .run_expr::<VmInt>("test", "20")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 40);
+}
+
+#[test]
+fn post_expansion_transform() {
+    use gluon::{
+        base::{
+            ast::{self, Expr, Literal, SpannedExpr},
+            symbol::{Symbol, Symbols},
+        },
+        vm::macros::AstTransform,
+    };
+
+    struct MapLiteral(fn(VmInt) -> VmInt);
+
+    impl AstTransform for MapLiteral {
+        fn transform<'ast>(
+            &self,
+            _symbols: &mut Symbols,
+            _arena: ast::ArenaRef<'_, 'ast, Symbol>,
+            expr: &mut SpannedExpr<'ast, Symbol>,
+        ) {
+            if let Expr::Literal(Literal::Int(i)) = &mut expr.value {
+                *i = (self.0)(*i);
+            }
+        }
+    }
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_database_mut().set_implicit_prelude(false);
+    // Added first but runs after the pre-expansion transform
+    vm.get_macros()
+        .add_post_expansion_transform(MapLiteral(|i| i * 2));
+    vm.get_macros()
+        .add_pre_expansion_transform(MapLiteral(|i| i + 1));
+
+    let (result, _) = vm
+        .run_expr::<VmInt>("test", "20")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 42);
+}
+
+#[test]
+fn custom_primitive_bool() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    {
+        let mut db = vm.get_database_mut();
+        db.set_implicit_prelude(false);
+        db.set_primitive_types(gluon::PrimitiveTypes {
+            bool: "my_core.Bool".into(),
+        });
+    }
+    load_script(&vm, "my_core", "type Bool = | False | True\n{ Bool }")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let (result, _) = vm
+        .run_expr::<VmInt>(
+            "test",
+            "let { Bool } = import! my_core\nif True then 1 else 2",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 1);
+}
+
+#[test]
+fn function_signature() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    let text = r#"
+        type IntToString = Int -> String
+        let add x y : Int -> Int -> Int = x #Int+ y
+        let show_int : IntToString = \_ -> ""
+        { IntToString, add, show_int, value = 1 }
+    "#;
+    load_script(&vm, "signature", text).unwrap_or_else(|err| panic!("{}", err));
+
+    let signature = |name: &str| {
+        vm.get_database()
+            .function_signature(name)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .map(|(args, ret)| {
+                (
+                    args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>(),
+                    ret.to_string(),
+                )
+            })
+    };
+    assert_eq!(
+        signature("signature.add"),
+        Some((
+            vec!["Int".to_string(), "Int".to_string()],
+            "Int".to_string()
+        ))
+    );
+    assert_eq!(
+        signature("signature.show_int"),
+        Some((vec!["Int".to_string()], "String".to_string()))
+    );
+    assert_eq!(signature("signature.value"), None);
+}
+
+#[test]
+fn snapshot_and_restore_globals() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    load_script(&vm, "snapshot_a", "{ x = 1 }").unwrap_or_else(|err| panic!("{}", err));
+    let snapshot = vm.get_database().snapshot_globals();
+    assert!(snapshot.modules().any(|module| module == "snapshot_a"));
+
+    load_script(&vm, "snapshot_a", "{ x = 2 }").unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "snapshot_b", "{ y = 3 }").unwrap_or_else(|err| panic!("{}", err));
+
+    vm.get_database_mut().restore_globals(&snapshot);
+
+    let (x, _) = vm
+        .run_expr::<VmInt>("test", "let { x } = import! snapshot_a in x")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(x, 1);
+    assert!(vm
+        .run_expr::<VmInt>("test2", "let { y } = import! snapshot_b in y")
+        .is_err());
+}
+
+#[test]
+fn undefined_binding_suggestions() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.run_expr::<OpaqueValue<&Thread, Hole>>("test", "import! std.string")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    {
+        let db = vm.get_database();
+        match db.get_binding("std.strng.len") {
+            Err(gluon::Error::VM(Error::UndefinedBinding(name, suggestions))) => {
+                assert_eq!(name, "std.strng.len");
+                assert_eq!(suggestions, ["std.string.len"]);
+            }
+            Err(err) => panic!("Unexpected error {}", err),
+            Ok(_) => panic!("Expected an error"),
+        }
+        assert_eq!(db.binding_suggestions("std.strng.len"), ["std.string.len"]);
+        assert_eq!(db.binding_suggestions("std.strng"), ["std.string"]);
+        assert!(db.binding_suggestions("std.string.len").is_empty());
+    }
+
+    match vm.get_global::<i32>("std.strng.len") {
+        Err(err @ Error::UndefinedBinding(..)) => assert_eq!(
+            err.to_string(),
+            "Binding `std.strng.len` is not defined\nDid you mean `std.string.len`?"
+        ),
+        Err(err) => panic!("Unexpected error {}", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
+
+#[test]
+fn import_all() {
+    use gluon::query::CompilationBase;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    {
+        let mut db = vm.get_database_mut();
+        db.add_module("glob.a".into(), "{ x = 1 }");
+        db.add_module("glob.b".into(), "{ y = 2 }");
+        db.add_module("glob.b.nested".into(), "{ z = 3 }");
+    }
+
+    let (result, _) = vm
+        .run_expr::<VmInt>("test", "let { a, b } = import_all! glob in a.x + b.y")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 3);
+
+    let err = vm
+        .run_expr::<()>("test2", "import_all! no_such_prefix")
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("Could not find any modules under 'no_such_prefix'"),
+        "{}",
+        err
+    );
+
+    // Modules which are added later are included once the modules are listed again, which
+    // happens in the next revision of the database
+    load_script(&vm, "glob_all", "import_all! glob").unwrap_or_else(|err| panic!("{}", err));
+    {
+        let mut db = vm.get_database_mut();
+        db.add_module("glob.c".into(), "{ w = 4 }");
+        db.set_module_source("glob.a", "{ x = 5 }");
+    }
+    let (result, _) = vm
+        .run_expr::<VmInt>("test3", "let { c } = import! glob_all in c.w")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 4);
+}
+
+#[test]
+fn import_all_errors() {
+    use gluon::query::CompilationBase;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.get_database_mut()
+        .add_module("keyword.type".into(), "{ x = 1 }");
+    let err = vm
+        .run_expr::<()>("test", "import_all! keyword")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("`type` is not a valid identifier"), "{}", err);
+
+    vm.get_database_mut()
+        .add_module("broken.a".into(), r#"{ x = 1 + "" }"#);
+    let err = vm
+        .run_expr::<()>("test2", "import_all! broken")
+        .unwrap_err();
+    // The error of the imported module is kept as is instead of being turned into a message
+    match err {
+        gluon::Error::Macro(err) => assert!(
+            err.errors()
+                .iter()
+                .any(|err| err.value.downcast_ref::<gluon::Error>().is_some()),
+            "{}",
+            err
+        ),
+        err => panic!("Expected a macro error, got: {}", err),
+    }
+}
+
+#[test]
+fn remove_module() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    load_script(&vm, "removed", "{ x = 1 }").unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "kept", "{ y = 2 }").unwrap_or_else(|err| panic!("{}", err));
+
+    vm.get_database_mut().remove_module("removed");
+
+    let db = vm.get_database();
+    assert!(db.get_filemap("removed").is_none());
+    assert!(db.get_filemap("kept").is_some());
+    drop(db);
+
+    let (y, _) = vm
+        .run_expr::<VmInt>("test", "let { y } = import! kept in y")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(y, 2);
+    assert!(vm
+        .run_expr::<VmInt>("test2", "let { x } = import! removed in x")
+        .is_err());
+}
+
+#[test]
+fn reset_database() {
+    use gluon::query::CompilationBase;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    load_script(&vm, "reset_module", "{ x = 1 }").unwrap_or_else(|err| panic!("{}", err));
+    assert!(vm.get_database().get_binding("reset_module.x").is_ok());
+
+    vm.get_database_mut().reset();
+
+    let db = vm.get_database();
+    assert!(db.peek_global("reset_module").is_none());
+    assert!(db.get_filemap("reset_module").is_none());
+    assert!(db.get_binding("reset_module.x").is_err());
+    drop(db);
+
+    load_script(&vm, "reset_module", "{ x = 2 }").unwrap_or_else(|err| panic!("{}", err));
+    let (x, _) = vm
+        .run_expr::<VmInt>("test", "let { x } = import! reset_module in x")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(x, 2);
+}
+
+#[test]
+fn typecheck_unsaved_source() {
+    use gluon::query::{AsyncCompilation, CompilationBase};
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_database_mut()
+        .add_module("buffer".into(), r#" { x = 1 } "#);
+
+    let unsaved = futures::executor::block_on(vm.get_database().typechecked_source(
+        "buffer".into(),
+        r#" { x = "a" } "#.into(),
+        None,
+    ))
+    .unwrap_or_else(|err| panic!("{}", err.error));
+    assert!(unsaved.typ.to_string().contains("String"));
+    assert_eq!(
+        vm.get_database().get_filemap("buffer").unwrap().source(),
+        r#" { x = 1 } "#
+    );
+
+    let saved = futures::executor::block_on(
+        vm.get_database()
+            .typechecked_source_module("buffer".into(), None),
+    )
+    .unwrap_or_else(|err| panic!("{}", err.error));
+    assert!(saved.typ.to_string().contains("Int"));
+    assert_eq!(
+        vm.get_database().get_filemap("buffer").unwrap().source(),
+        r#" { x = 1 } "#
+    );
+}
+
+#[test]
+fn get_binding_array_index() {
+    use gluon::vm::api::ValueRef;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    load_script(&vm, "indexed", r#" { items = [1, 2, 3] } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let db = vm.get_database();
+    let (value, typ) = db
+        .get_binding("indexed.items.1")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(typ, Type::int());
+    match value.get_variant().as_ref() {
+        ValueRef::Int(2) => (),
+        value => panic!("Unexpected value {:?}", value),
+    }
+
+    let err = db.get_binding("indexed.items.3").unwrap_err();
+    assert!(
+        err.to_string().contains("out of bounds"),
+        "Unexpected error {}",
+        err
+    );
+}
+
+#[test]
+fn get_binding_error_spans() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    load_script(&vm, "path_errors", r#" { x = { y = 1 } } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let db = vm.get_database();
+    for (path, expected) in &[("path_errors.x.z", "z"), ("path_errors.x.+", "+")] {
+        match db.get_binding(path) {
+            Err(gluon::Error::Macro(err)) => {
+                let range = err.errors()[0].span.to_range(err.source()).unwrap();
+                assert_eq!(&path[range], *expected);
+            }
+            Err(err) => panic!("Expected a spanned error: {}", err),
+            Ok(_) => panic!("Expected an error for `{}`", path),
+        }
+    }
+}
+
+#[test]
+fn macro_dependencies() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    let text = r#"
+        let int = import! std.int
+        let float = import! "std/float.glu"
+        let string = import! "./string.glu"
+        { int, float, string }
+    "#;
+    let expr = vm
+        .parse_expr(&vm.global_env().type_cache(), "test", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let mut userdata = NoUserdata;
+    let expander = MacroExpander::new(&vm, &mut userdata, None).with_module("std.test");
+    let dependencies = expander.collect_dependencies(expr.expr());
+    assert_eq!(
+        dependencies
+            .iter()
+            .map(|symbol| symbol.as_str())
+            .collect::<Vec<_>>(),
+        ["@std.int", "@std.float", "@std.string"]
+    );
+}
+
+#[test]
+fn describe_import_macro() {
+    use gluon::base::ast::Expr;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    let expr = vm
+        .parse_expr(&vm.global_env().type_cache(), "test", "import! std.int")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let mut userdata = NoUserdata;
+    let expander = MacroExpander::new(&vm, &mut userdata, None);
+    let description = match &expr.expr().value {
+        Expr::App { args, .. } => expander.describe_macro("import!", args),
+        _ => panic!("Expected a macro invocation"),
+    };
+    let description = description.expect("import! has a description");
+    assert!(description.contains("std.int"), "{}", description);
+
+    assert_eq!(expander.describe_macro("not_a_macro", &[]), None);
+
+    // Relative imports are resolved against the module containing the invocation
+    let expr = vm
+        .parse_expr(
+            &vm.global_env().type_cache(),
+            "test",
+            r#"import! "./int.glu""#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    let expander = MacroExpander::new(&vm, &mut userdata, None).with_module("std.float");
+    let description = match &expr.expr().value {
+        Expr::App { args, .. } => expander.describe_macro("import!", args),
+        _ => panic!("Expected a macro invocation"),
+    };
+    let description = description.expect("import! has a description");
+    assert!(description.contains("`std.int`"), "{}", description);
+}
+
+#[test]
+fn compile_without_executing() {
+    use gluon::{compiler_pipeline::*, vm::api::ValueRef};
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_database_mut().implicit_prelude(false);
+
+    let text = "123";
+    let compiled = futures::executor::block_on(text.compile(
+        &mut vm.module_compiler(&mut vm.get_database()),
+        &vm,
+        "compile_only",
+        text,
+        None,
+    ))
+    .unwrap_or_else(|err| panic!("{}", err));
+    assert!(!compiled.instructions().is_empty());
+
+    let value = futures::executor::block_on(compiled.execute(&*vm, "compile_only"))
+        .unwrap_or_else(|err| panic!("{}", err));
+    match value.value.get_variant().as_ref() {
+        ValueRef::Int(123) => (),
+        value => panic!("Unexpected value: {:?}", value),
+    }
+}
+
+#[test]
+fn cancel_macro_expansion() {
+    use gluon::vm::macros::LazyMacroResult;
+
+    #[derive(Trace)]
+    #[gluon_trace(skip)]
+    struct Hang;
+
+    impl Macro for Hang {
+        fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+            &self,
+            _env: &'b mut MacroExpander<'a>,
+            _symbols: &'c mut Symbols,
+            _arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+            _args: &'b mut [SpannedExpr<'ast, Symbol>],
+        ) -> MacroFuture<'r, 'ast> {
+            Box::pin(future::ok(LazyMacroResult::Lazy(Box::new(|| {
+                Box::pin(future::pending())
+            }))))
+        }
+    }
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros().insert("hang".into(), Hang);
+
+    let mut expr = vm
+        .parse_expr(&vm.global_env().type_cache(), "test", "hang! 1")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let (cancel, cancelled) = futures::channel::oneshot::channel();
+    let mut userdata = NoUserdata;
+    let mut symbols = Symbols::new();
+    let mut expander = MacroExpander::new(&vm, &mut userdata, None).cancel_on(cancelled);
+
+    cancel.send(()).unwrap();
+    let (arena, expr) = expr.arena_expr();
+    futures::executor::block_on(expander.run(&mut symbols, arena, expr));
+
+    let errors = expander.finish().unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].value.to_string(), "macro expansion cancelled");
+}
+
+#[test]
+fn loaded_globals() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    load_script(&vm, "globals_ok", "{ x = 1 }").unwrap_or_else(|err| panic!("{}", err));
+    assert!(load_script(&vm, "globals_err", "{ x = 1 + \"\" }").is_err());
+
+    let globals = vm.get_database().globals();
+    assert!(globals["globals_ok"].is_ok());
+    assert!(globals
+        .get("globals_err")
+        .map_or(true, |global| global.is_err()));
+}
+
+#[test]
+fn globals_loads_added_modules() {
+    use gluon::query::CompilationBase;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    {
+        let mut db = vm.get_database_mut();
+        for i in 0..10 {
+            db.add_module(format!("parallel_{}", i), &format!("{{ x = {} }}", i));
+        }
+        db.add_module(
+            "parallel_user".into(),
+            "let { x } = import! parallel_3 in { y = x + 1 }",
+        );
+    }
+
+    let globals = vm.get_database().globals();
+    for i in 0..10 {
+        assert!(globals[&format!("parallel_{}", i)].is_ok());
+    }
+    assert!(globals["parallel_user"].is_ok());
+
+    let (y, _) = vm
+        .run_expr::<VmInt>("test", "let { y } = import! parallel_user in y")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(y, 4);
+}
+
+#[test]
+fn module_source_providers() {
+    use gluon::import::{self, ModuleSourceProvider};
+
+    struct Provider(&'static str);
+
+    impl ModuleSourceProvider for Provider {
+        fn get(&self, module: &str, _filename: &str) -> Option<Result<String, import::Error>> {
+            if module == "virtual.answer" {
+                Some(Ok(self.0.to_string()))
+            } else {
+                None
+            }
+        }
+    }
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    {
+        let import = vm.get_macros().get("import");
+        let import = import
+            .as_ref()
+            .and_then(|import| import.downcast_ref::<Import>())
+            .expect("Import macro");
+        import.add_source_provider(Arc::new(Provider("42")));
+        import.add_source_provider(Arc::new(Provider("0")));
+    }
+
+    let (answer, _) = vm
+        .run_expr::<i32>("test", "import! virtual.answer")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(answer, 42);
+
+    vm.run_expr::<i32>("test", "let { abs } = import! std.int in abs (-1)")
+        .unwrap_or_else(|err| panic!("{}", err));
+}
+
+#[test]
+fn async_module_source_provider() {
+    use std::{thread, time::Duration};
+
+    use gluon::import::{self, ModuleSourceProvider};
+
+    struct DelayedProvider;
+
+    #[async_trait::async_trait]
+    impl ModuleSourceProvider for DelayedProvider {
+        fn get(&self, _module: &str, _filename: &str) -> Option<Result<String, import::Error>> {
+            None
+        }
+
+        async fn async_get(
+            &self,
+            module: &str,
+            _filename: &str,
+        ) -> Option<Result<String, import::Error>> {
+            match module {
+                "delayed.answer" => (),
+                "delayed.broken" => {
+                    return Some(Err(import::Error::String("Connection lost".into())))
+                }
+                _ => return None,
+            }
+            let (tx, rx) = futures::channel::oneshot::channel();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                let _ = tx.send("42".to_string());
+            });
+            Some(Ok(rx.await.expect("source")))
+        }
+    }
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    {
+        let import = vm.get_macros().get("import");
+        let import = import
+            .as_ref()
+            .and_then(|import| import.downcast_ref::<Import>())
+            .expect("Import macro");
+        import.add_source_provider(Arc::new(DelayedProvider));
+    }
+
+    let snapshot = vm.get_database().snapshot_globals();
+    let (answer, _) = vm
+        .run_expr::<i32>("test", "import! delayed.answer")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(answer, 42);
+
+    // Provided sources are not inline modules so restoring a snapshot leaves them alone
+    vm.get_database_mut().restore_globals(&snapshot);
+    let (answer, _) = vm
+        .run_expr::<i32>("test", "import! delayed.answer")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(answer, 42);
+
+    let err = match vm.run_expr::<i32>("test", "import! delayed.broken") {
+        Ok(_) => panic!("Expected an error"),
+        Err(err) => err.to_string(),
+    };
+    assert!(err.contains("Connection lost"), "{}", err);
+}
+
+#[test]
+fn macro_emit_binding_around_nested_expansion() {
+    use gluon::{
+        base::{
+            ast::{Expr, Literal, Pattern, TypedIdent, ValueBinding},
+            pos,
+        },
+        vm::macros::MacroExpansion,
+    };
+
+    /// Emits a binding and then expands the macros in its argument before returning both
+    struct Outer;
+
+    unsafe impl Trace for Outer {
+        impl_trace! { self, _gc, () }
+    }
+
+    impl Macro for Outer {
+        fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+            &self,
+            env: &'b mut MacroExpander<'a>,
+            symbols: &'c mut Symbols,
+            arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+            args: &'b mut [SpannedExpr<'ast, Symbol>],
+        ) -> MacroFuture<'r, 'ast> {
+            Box::pin(async move {
+                let span = args[0].span;
+                let helper = symbols.simple_symbol("outer_helper");
+                env.run_once(symbols, arena, &mut args[0]).await;
+                let arg = std::mem::replace(&mut args[0], pos::spanned(span, Expr::Error(None)));
+                let mut expansion = MacroExpansion::new(pos::spanned(
+                    span,
+                    Expr::Tuple {
+                        typ: Type::hole(),
+                        elems: arena.alloc_extend(vec![
+                            pos::spanned(span, Expr::Ident(TypedIdent::new(helper.clone()))),
+                            arg,
+                        ]),
+                    },
+                ));
+                expansion.emit_binding(ValueBinding {
+                    name: pos::spanned(span, Pattern::Ident(TypedIdent::new(helper))),
+                    expr: pos::spanned(span, Expr::Literal(Literal::Int(10))),
+                    ..ValueBinding::default()
+                });
+                Ok(expansion.into())
+            })
+        }
+    }
+
+    /// Expands to a reference to a binding which it emits
+    struct Inner;
+
+    unsafe impl Trace for Inner {
+        impl_trace! { self, _gc, () }
+    }
+
+    impl Macro for Inner {
+        fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+            &self,
+            _env: &'b mut MacroExpander<'a>,
+            symbols: &'c mut Symbols,
+            _arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+            args: &'b mut [SpannedExpr<'ast, Symbol>],
+        ) -> MacroFuture<'r, 'ast> {
+            let span = args[0].span;
+            let helper = symbols.simple_symbol("inner_helper");
+            let mut expansion = MacroExpansion::new(pos::spanned(
+                span,
+                Expr::Ident(TypedIdent::new(helper.clone())),
+            ));
+            expansion.emit_binding(ValueBinding {
+                name: pos::spanned(span, Pattern::Ident(TypedIdent::new(helper))),
+                expr: pos::spanned(span, Expr::Literal(Literal::Int(1))),
+                ..ValueBinding::default()
+            });
+            Box::pin(future::ok(expansion.into()))
+        }
+    }
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros().insert("outer".into(), Outer);
+    vm.get_macros().insert("inner".into(), Inner);
+
+    // `outer_helper` must be bound around the whole expansion of `outer!`, not just around the
+    // expansion of `inner!` which is expanded while `outer!` is running
+    let (result, _) = vm
+        .run_expr::<(VmInt, VmInt)>("test", "outer! (inner! ())")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, (10, 1));
+}
+
+#[test]
+fn macro_sibling_bindings() {
+    use gluon::{
+        base::{
+            ast::{Expr, Literal, Pattern, TypedIdent, ValueBinding},
+            pos,
+        },
+        vm::macros::MacroExpansion,
+    };
+
+    /// Expands to `1` and binds `gen_helper` next to the `let` it is the right-hand side of
+    struct Gen;
+
+    unsafe impl Trace for Gen {
+        impl_trace! { self, _gc, () }
+    }
+
+    impl Macro for Gen {
+        fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+            &self,
+            _env: &'b mut MacroExpander<'a>,
+            symbols: &'c mut Symbols,
+            _arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+            args: &'b mut [SpannedExpr<'ast, Symbol>],
+        ) -> MacroFuture<'r, 'ast> {
+            let span = args[0].span;
+            let mut expansion =
+                MacroExpansion::new(pos::spanned(span, Expr::Literal(Literal::Int(1))));
+            expansion.emit_sibling_binding(ValueBinding {
+                name: pos::spanned(
+                    span,
+                    Pattern::Ident(TypedIdent::new(symbols.simple_symbol("gen_helper"))),
+                ),
+                expr: pos::spanned(span, Expr::Literal(Literal::Int(10))),
+                ..ValueBinding::default()
+            });
+            Box::pin(future::ok(expansion.into()))
+        }
+    }
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros().insert("gen".into(), Gen);
+
+    let (result, _) = vm
+        .run_expr::<VmInt>("test", "let x = gen! ()\nx + gen_helper")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 11);
+
+    let err = vm
+        .run_expr::<VmInt>("test2", "1 + gen! ()")
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("right-hand side of a `let`"),
+        "Unexpected error: {}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn run_expr_async_concurrently() {
+    let _ = ::env_logger::try_init();
+    let vm = gluon::new_vm_async().await;
+
+    let names: Vec<_> = (0..4).map(|i| format!("test{}", i)).collect();
+    let sources: Vec<_> = (0..4)
+        .map(|i| format!("let {{ of }} = import! std.list in {} + 1", i))
+        .collect();
+    let results = future::join_all(
+        names
+            .iter()
+            .zip(&sources)
+            .map(|(name, source)| vm.run_expr_async::<VmInt>(name, source)),
+    )
+    .await;
+    for (i, result) in results.into_iter().enumerate() {
+        let (value, _) = result.unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(value, i as VmInt + 1);
+    }
+
+    match vm.run_expr_async::<VmInt>("error", r#"1 + """#).await {
+        Err(gluon::Error::Typecheck(_)) => (),
+        result => panic!(
+            "Expected a type error: {:?}",
+            result.map(|(value, _)| value)
+        ),
+    }
+}
+
+#[test]
+fn database_revisions() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    assert_eq!(vm.get_database().module_revision("test"), 0);
+
+    load_script(&vm, "test", "1").unwrap_or_else(|err| panic!("{}", err));
+    let (revision, module_revision) = {
+        let db = vm.get_database();
+        (db.current_revision(), db.module_revision("test"))
+    };
+    assert!(module_revision > 0);
+
+    load_script(&vm, "test", "2").unwrap_or_else(|err| panic!("{}", err));
+    let (revision2, module_revision2) = {
+        let db = vm.get_database();
+        (db.current_revision(), db.module_revision("test"))
+    };
+    assert!(revision2 > revision);
+    assert!(module_revision2 > module_revision);
+
+    vm.get_database_mut().set_implicit_prelude(false);
+    let db = vm.get_database();
+    assert!(db.current_revision() > revision2);
+    assert_eq!(db.module_revision("test"), module_revision2);
+}
+
+#[test]
+fn parsed_module_skips_typechecking() {
+    use gluon::query::{AsyncCompilation, CompilationBase};
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.get_database_mut()
+        .add_module("parse_type_error".into(), r#"let x : Int = "abc" in x"#);
+    vm.get_database_mut()
+        .add_module("parse_syntax_error".into(), "let x = in x");
+
+    futures::executor::block_on(vm.get_database().parsed_module("parse_type_error".into()))
+        .unwrap_or_else(|err| panic!("{}", err.error));
+
+    match futures::executor::block_on(
+        vm.get_database()
+            .typechecked_source_module("parse_type_error".into(), None),
+    ) {
+        Err(err) => match err.error {
+            gluon::Error::Typecheck(_) => (),
+            err => panic!("Expected a type error, found: {}", err),
+        },
+        Ok(_) => panic!("Expected a type error"),
+    }
+
+    match futures::executor::block_on(vm.get_database().parsed_module("parse_syntax_error".into()))
+    {
+        Err(err) => match err.error {
+            gluon::Error::Parse(_) => (),
+            err => panic!("Expected a parse error, found: {}", err),
+        },
+        Ok(_) => panic!("Expected a parse error"),
+    }
+}
+
+#[test]
+fn get_binding_by_symbol() {
+    use gluon::{base::symbol::Symbol, vm::api::ValueRef};
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    load_script(&vm, "by_symbol", "{ x = 1, inner = { y = 2 } }")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let db = vm.get_database();
+
+    let (value, _) = db
+        .get_binding_by_symbol(&Symbol::from("@by_symbol"))
+        .unwrap_or_else(|err| panic!("{}", err));
+    match value.get_variants().as_ref() {
+        ValueRef::Data(data) => assert_eq!(data.len(), 2),
+        value => panic!("Expected a record, found {:?}", value),
+    }
+
+    // Symbols which are not globals are resolved as paths
+    let (value, typ) = db
+        .get_binding_by_symbol(&Symbol::from("by_symbol.inner.y"))
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(typ, Type::int());
+    assert_eq!(value.get_variants().as_ref(), ValueRef::Int(2));
+
+    assert!(db
+        .get_binding_by_symbol(&Symbol::from("@missing_module"))
+        .is_err());
+}
+
+#[test]
+fn module_changed_callback() {
+    use std::sync::Mutex;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let changed = Arc::new(Mutex::new(Vec::new()));
+    {
+        let import = vm.get_macros().get("import");
+        let import = import
+            .as_ref()
+            .and_then(|import| import.downcast_ref::<Import>())
+            .expect("Import macro");
+        let changed = changed.clone();
+        import.set_module_changed_callback(Box::new(move |module| {
+            changed.lock().unwrap().push(module.to_string())
+        }));
+    }
+
+    load_script(&vm, "changed_module", "{ x = 1 }").unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(*changed.lock().unwrap(), ["changed_module"]);
+}
+
+#[test]
+fn load_scripts_concurrently() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static LOADED: AtomicUsize = AtomicUsize::new(0);
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    add_extern_module(&vm, "loaded", |vm| {
+        ExternModule::new(
+            vm,
+            primitive!(1, "loaded", |()| {
+                LOADED.fetch_add(1, Ordering::SeqCst);
+            }),
+        )
+    });
+
+    futures::executor::block_on(vm.load_scripts_async(&[
+        ("batch_b", "let { x } = import! batch_a in { y = x + 1 }"),
+        ("batch_c", "let { x } = import! batch_a in { z = x + 2 }"),
+        (
+            "batch_a",
+            "let loaded = import! loaded\nlet _ = loaded ()\n{ x = 1 }",
+        ),
+    ]))
+    .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(LOADED.load(Ordering::SeqCst), 1);
+
+    let (result, _) = vm
+        .run_expr::<VmInt>(
+            "test",
+            "let { y } = import! batch_b\nlet { z } = import! batch_c\ny + z",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 5);
+
+    // A failing module leaves the database as it was before the call
+    let result = futures::executor::block_on(
+        vm.load_scripts_async(&[("batch_ok", "{ w = 1 }"), ("batch_err", "{ w = 1 + \"\" }")]),
+    );
+    assert!(result.is_err());
+    assert!(vm.get_database().get_binding("batch_ok").is_err());
+}
+
+#[test]
+fn error_sink() {
+    use gluon::query::{AsyncCompilation, CompilationBase};
+    use std::sync::Mutex;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    {
+        let reported = reported.clone();
+        let mut db = vm.get_database_mut();
+        db.set_error_sink(Box::new(move |module, err| {
+            reported
+                .lock()
+                .unwrap()
+                .push((module.to_string(), err.to_string()))
+        }));
+        db.add_module("sink_a".into(), r#" 1 + "" "#);
+        db.add_module("sink_b".into(), r#" "" + 1 "#);
+        db.add_module("sink_ok".into(), "1");
+    }
+
+    for module in &["sink_a", "sink_b", "sink_ok"] {
+        let _ = futures::executor::block_on(
+            vm.get_database()
+                .typechecked_source_module(module.to_string(), None),
+        );
+    }
+
+    let reported = reported.lock().unwrap();
+    assert_eq!(
+        reported
+            .iter()
+            .map(|(module, _)| &module[..])
+            .collect::<Vec<_>>(),
+        ["sink_a", "sink_b"]
+    );
+}
+
+#[test]
+fn relative_import() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.load_script("relative.value", "{ x = 1 }")
+        .unwrap_or_else(|err| panic!("{}", err));
+    vm.load_script(
+        "relative.nested.sibling",
+        r#" let { x } = import! "../value.glu" in { y = x + 1 } "#,
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    vm.load_script(
+        "relative.nested.main",
+        r#" let { y } = import! "./sibling.glu" in y "#,
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    let (result, _) = vm
+        .run_expr::<i32>("test", "import! relative.nested.main")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 2);
+}
+
+#[test]
+fn global_definition_span() {
+    use gluon::query::AsyncCompilation;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let source = r#"
+let x = 1
+let add_x y = y + x
+{ x, add_x }
+"#;
+    vm.load_script("definition", source)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let mut db = vm.get_database();
+    let (module, span) =
+        futures::executor::block_on(db.global_definition_span("definition.add_x".into()))
+            .expect("Definition span");
+    assert_eq!(module, "definition");
+
+    let file_span = db.get_filemap("definition").unwrap().span();
+    let start = span.start().to_usize() - file_span.start().to_usize();
+    let end = span.end().to_usize() - file_span.start().to_usize();
+    assert_eq!(&source[start..end], "add_x y = y + x");
+
+    assert_eq!(
+        futures::executor::block_on(db.global_definition_span("definition.missing".into())),
+        None
+    );
+}
+
+#[test]
+fn get_bindings() {
+    use gluon::vm::api::ValueRef;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.load_script("config", r#" { port = 8080, host = "localhost" } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let db = vm.get_database();
+    let results = db.get_bindings(&["config.port", "config.missing", "config.host", "nothing"]);
+    assert_eq!(results.len(), 4);
+
+    let port = results[0].as_ref().unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(port.0.get_variants().as_ref(), ValueRef::Int(8080));
+    assert!(results[1].is_err());
+    let host = results[2].as_ref().unwrap_or_else(|err| panic!("{}", err));
+    match host.0.get_variants().as_ref() {
+        ValueRef::String(s) => assert_eq!(s, "localhost"),
+        value => panic!("Expected a string, got {:?}", value),
+    }
+    assert!(results[3].is_err());
+}
+
+#[test]
+fn get_binding_outlives_reload() {
+    use gluon::vm::api::ValueRef;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.load_script("rooted", r#" { greeting = "hello" ++ " world" } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let (greeting, _) = vm
+        .get_database()
+        .get_binding("rooted.greeting")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    vm.get_database_mut().remove_module("rooted");
+    vm.load_script("rooted", r#" { greeting = "bye" } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+    vm.collect();
+
+    match greeting.get_variants().as_ref() {
+        ValueRef::String(s) => assert_eq!(s, "hello world"),
+        value => panic!("Expected a string, got {:?}", value),
+    }
+}
+
+#[test]
+fn record_timings() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    load_script(&vm, "untimed", "{ x = 1 }").unwrap_or_else(|err| panic!("{}", err));
+    assert!(vm.get_database().last_timings().is_empty());
+
+    vm.get_database_mut().set_record_timings(true);
+    load_script(&vm, "timed", "let f x = x #Int+ 1\n{ y = f 2 }")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let timings = vm.get_database().last_timings();
+    let timing = timings
+        .get("timed")
+        .unwrap_or_else(|| panic!("No timing recorded: {:?}", timings));
+    assert!(*timing > std::time::Duration::default());
+}
+
+#[test]
+fn typecheck_all() {
+    use gluon::query::CompilationBase;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    {
+        let mut db = vm.get_database_mut();
+        db.add_module("check_good".into(), "{ x = 1 }");
+        db.add_module("check_bad1".into(), r#"{ x = 1 + "" }"#);
+        db.add_module("check_bad2".into(), "let y : String = 2\n{ y }");
+    }
+
+    let failed = vm.get_database().typecheck_all().unwrap_err();
+    let mut modules: Vec<_> = failed.keys().map(|module| &module[..]).collect();
+    modules.sort();
+    assert_eq!(modules, ["check_bad1", "check_bad2"]);
+    assert!(failed.values().all(|errors| errors.has_errors()));
+}
+
+#[test]
+fn for_each_global() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.load_script("each_a", "{ x = 1 }")
+        .unwrap_or_else(|err| panic!("{}", err));
+    vm.load_script("each_b", r#"{ y = "" }"#)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(vm.load_script("each_c", "{ z = 1 + \"\" }").is_err());
+
+    let db = vm.get_database();
+    let mut count = 0;
+    let mut names = Vec::new();
+    let mut failed = Vec::new();
+    db.for_each_global(|name, global| {
+        count += 1;
+        match global {
+            Ok(typ) if name.starts_with("each_") => names.push((name.to_string(), typ.to_string())),
+            Ok(_) => (),
+            Err(_) => failed.push(name.to_string()),
+        }
+    });
+    assert_eq!(count, db.globals().len());
+    assert_eq!(failed, ["each_c"]);
+
+    names.sort();
+    assert_eq!(names.len(), 2, "{:?}", names);
+    assert_eq!(names[0].0, "each_a");
+    assert!(names[0].1.contains("Int"), "{:?}", names);
+    assert_eq!(names[1].0, "each_b");
+    assert!(names[1].1.contains("String"), "{:?}", names);
+}
+
+#[test]
+fn set_module_source_only_bumps_on_change() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let mut db = vm.get_database_mut();
+    assert_eq!(db.module_revision("buffer"), 0);
+
+    db.set_module_source("buffer", "1 + 1");
+    let revision = db.current_revision();
+    let module_revision = db.module_revision("buffer");
+    assert!(module_revision > 0);
+
+    db.set_module_source("buffer", "1 + 1");
+    assert_eq!(db.module_revision("buffer"), module_revision);
+    assert_eq!(db.current_revision(), revision);
+
+    db.set_module_source("buffer", "1 + 2");
+    assert!(db.module_revision("buffer") > module_revision);
+    assert!(db.current_revision() > revision);
+}
+
+#[test]
+fn modules_affected_by_edit() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    load_script(&vm, "edit_a", "{ x = 1, v = 2 }").unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "edit_b", "let { x } = import! edit_a in { y = x }")
+        .unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "edit_c", "let { y } = import! edit_b in { z = y }")
+        .unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "edit_d", "{ w = 1 }").unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "edit_e", "let a = import! edit_a in { u = a.v }")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let db = vm.get_database();
+    assert_eq!(
+        db.affected_by_edit("edit_a")
+            .unwrap_or_else(|err| panic!("{}", err)),
+        ["edit_a", "edit_b", "edit_e", "edit_c"]
+    );
+    assert_eq!(
+        db.affected_by_edit("edit_b")
+            .unwrap_or_else(|err| panic!("{}", err)),
+        ["edit_b", "edit_c"]
+    );
+    assert_eq!(
+        db.affected_by_edit("edit_d")
+            .unwrap_or_else(|err| panic!("{}", err)),
+        ["edit_d"]
+    );
+    assert!(db.affected_by_edit("edit_unknown").is_err());
+
+    assert_eq!(
+        db.affected_by_export_edit("edit_a", &["x"])
+            .unwrap_or_else(|err| panic!("{}", err)),
+        ["edit_a", "edit_b", "edit_c"]
+    );
+    assert_eq!(
+        db.affected_by_export_edit("edit_a", &["v"])
+            .unwrap_or_else(|err| panic!("{}", err)),
+        ["edit_a", "edit_e"]
+    );
+    assert!(db.affected_by_export_edit("edit_unknown", &[]).is_err());
+}
+
+#[test]
+fn transitive_module_dependencies() {
+    use gluon::query::{AsyncCompilation, CompilationBase};
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.get_database_mut()
+        .add_module("deps_a".into(), "{ x = 1 }");
+    vm.get_database_mut()
+        .add_module("deps_b".into(), "let { x } = import! deps_a in { y = x }");
+    vm.get_database_mut().add_module(
+        "deps_c".into(),
+        "let { y } = import! deps_b\nlet { x } = import! deps_a\n{ z = x + y }",
+    );
+    vm.get_database_mut()
+        .add_module("deps_self".into(), "let _ = import! deps_self in { w = 1 }");
+
+    let dependencies =
+        futures::executor::block_on(vm.get_database().transitive_dependencies("deps_c".into()))
+            .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(*dependencies, ["deps_a", "deps_b"]);
+
+    let dependencies = futures::executor::block_on(
+        vm.get_database()
+            .transitive_dependencies("deps_self".into()),
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(*dependencies, ["deps_self"]);
+
+    add_extern_module_with_deps(
+        &vm,
+        "deps_extern",
+        |vm| ExternModule::new(vm, primitive!(1, "deps_extern", |x: VmInt| x)),
+        vec!["deps_a".into()],
+    );
+    let dependencies = futures::executor::block_on(
+        vm.get_database()
+            .transitive_dependencies("deps_extern".into()),
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(*dependencies, ["deps_a"]);
+}
+
+#[test]
+fn compiled_module_with_expected_type() {
+    use gluon::{
+        base::types::Type,
+        query::{AsyncCompilation, CompilationBase},
+    };
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_database_mut()
+        .add_module("plugin_entry".into(), r#" "not an int" "#);
+
+    let result = futures::executor::block_on(
+        vm.get_database()
+            .compiled_module("plugin_entry".into(), Some(Type::int())),
+    );
+    match result {
+        Err(gluon::Error::Typecheck(_)) => (),
+        Err(err) => panic!("Expected a type error: {}", err),
+        Ok(_) => panic!("Expected a type error"),
+    }
+
+    futures::executor::block_on(
+        vm.get_database()
+            .compiled_module("plugin_entry".into(), Some(Type::string())),
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+}
+
+#[test]
+fn range_marshalling() {
+    use std::ops::{Range, RangeInclusive};
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let (range, _) = vm
+        .run_expr::<RangeInclusive<VmInt>>("test", "{ start = 1, end = 3 }")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(range, 1..=3);
+
+    let text = r#"
+        let range_len r : { start : Int, end : Int } -> Int = r.end - r.start
+        range_len
+    "#;
+    load_script(&vm, "range_len", text).unwrap_or_else(|err| panic!("{}", err));
+    let mut range_len: FunctionRef<fn(Range<VmInt>) -> VmInt> = vm.get_global("range_len").unwrap();
+    assert_eq!(range_len.call(2..10).unwrap(), 8);
+}
+
+#[test]
+fn compiled_module_constants() {
+    use gluon::vm::types::Constant;
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_database_mut()
+        .implicit_prelude(false)
+        .set_optimize(false);
+
+    load_script(
+        &vm,
+        "constants",
+        r#"
+        let secret = "hunter2"
+        let f x = x #Float+ 1.5
+        { secret, f, size = 1024 }
+        "#,
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    let constants = vm
+        .module_constants("constants")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(constants.contains(&Constant::String("hunter2".into())));
+    assert!(constants.contains(&Constant::Int(1024)));
+    assert!(constants.contains(&Constant::Float(1.5)));
+    assert_eq!(Constant::Int(1024).typ(), Type::int());
+}
+
+#[test]
+fn strict_conversions_reject_truncation() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    fn identity(i: i32) -> i32 {
+        i
+    }
+    add_extern_module(&vm, "strict_identity", |thread| {
+        ExternModule::new(thread, primitive!(1, identity))
+    });
+
+    let expr = r#"
+        let identity = import! strict_identity
+        identity 2147483648
+    "#;
+    let (result, _) = vm
+        .run_expr::<i32>("lenient", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, i32::MIN);
+
+    vm.get_database_mut().set_strict_conversions(true);
+    let err = vm.run_expr::<i32>("strict", expr).unwrap_err();
+    assert!(
+        err.to_string().contains("does not fit in a `i32`"),
+        "Unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn rebuild_all_recompiles_modules() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    add_extern_module(&vm, "rebuild_runs", |vm| {
+        ExternModule::new(
+            vm,
+            primitive!(1, "rebuild_runs", |()| {
+                RUNS.fetch_add(1, Ordering::SeqCst);
+            }),
+        )
+    });
+
+    load_script(
+        &vm,
+        "rebuilt",
+        "let runs = import! rebuild_runs\nlet _ = runs ()\n{ x = 1 }",
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+
+    // The source is unchanged but the module must still be compiled and run again
+    vm.get_database_mut().rebuild_all();
+    let (result, _) = vm
+        .run_expr::<VmInt>("test", "let { x } = import! rebuilt in x")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 1);
+    assert_eq!(RUNS.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn macro_emit_binding() {
+    use gluon::{
+        base::{
+            ast::{Expr, Literal, Pattern, TypedIdent, ValueBinding},
+            pos,
+        },
+        vm::macros::MacroExpansion,
+    };
+
+    /// Expands to a reference to `emitted_helper` which it binds around the expansion
+    struct Helper;
+
+    unsafe impl Trace for Helper {
+        impl_trace! { self, _gc, () }
+    }
+
+    impl Macro for Helper {
+        fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+            &self,
+            _env: &'b mut MacroExpander<'a>,
+            symbols: &'c mut Symbols,
+            _arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+            args: &'b mut [SpannedExpr<'ast, Symbol>],
+        ) -> MacroFuture<'r, 'ast> {
+            let span = args[0].span;
+            let helper = symbols.simple_symbol("emitted_helper");
+            let mut expansion = MacroExpansion::new(pos::spanned(
+                span,
+                Expr::Ident(TypedIdent::new(helper.clone())),
+            ));
+            expansion.emit_binding(ValueBinding {
+                name: pos::spanned(span, Pattern::Ident(TypedIdent::new(helper))),
+                expr: pos::spanned(span, Expr::Literal(Literal::Int(10))),
+                ..ValueBinding::default()
+            });
+            Box::pin(future::ok(expansion.into()))
+        }
+    }
+
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    vm.get_macros().insert("helper".into(), Helper);
+
+    let (result, _) = vm
+        .run_expr::<VmInt>("test", "helper! ()")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 10);
+}
+
+#[test]
+fn check_expr_without_thread() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    vm.run_expr::<OpaqueValue<&Thread, Hole>>("test", "import! std.string")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let db = vm.get_database().snapshot_without_thread();
+    assert_eq!(
+        db.check_expr("check", "1 + 2")
+            .unwrap_or_else(|err| panic!("{}", err)),
+        Type::int()
+    );
+    assert_eq!(
+        db.check_expr(
+            "check",
+            "let string = import! std.string in string.len \"abc\""
+        )
+        .unwrap_or_else(|err| panic!("{}", err)),
+        Type::int()
+    );
+
+    assert!(db.check_expr("check", "1 + \"abc\"").is_err());
+
+    let err = db
+        .check_expr("check", "import! std.json")
+        .expect_err("std.json is not loaded");
+    assert!(
+        err.to_string()
+            .contains("`std.json` can't be imported without a thread"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "chrono")]
+#[macro_use]
+extern crate gluon_vm;
+#[macro_use]
+extern crate gluon_codegen;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use gluon::{
+    import, new_vm,
+    vm::{self, types::VmInt, ExternModule},
+    Thread, ThreadExt,
+};
+
+fn add_second(at: DateTime<Utc>) -> DateTime<Utc> {
+    at + Duration::seconds(1)
+}
+
+#[test]
+fn push_and_get_timestamp() {
+    let _ = env_logger::try_init();
+
+    let vm = new_vm();
+    import::add_extern_module(&vm, "timestamp", |vm| {
+        ExternModule::new(
+            vm,
+            record! {
+                add_second => primitive!(1, add_second),
+            },
+        )
+    });
+
+    let (at, _) = vm
+        .run_expr::<DateTime<Utc>>(
+            "test",
+            r#"
+                let { add_second } = import! timestamp
+                add_second { secs = 1600000000, nanos = 123000000 }
+            "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(at, timestamp() + Duration::seconds(1));
+}
+
+#[derive(Debug, PartialEq, Getable, Pushable, VmType)]
+struct Event {
+    name: String,
+    at: DateTime<Utc>,
+}
+
+fn timestamp() -> DateTime<Utc> {
+    Utc.timestamp_millis(1_600_000_000_123)
+}
+
+fn new_event(_: ()) -> Event {
+    Event {
+        name: "release".into(),
+        at: timestamp(),
+    }
+}
+
+fn event_millis(event: Event) -> VmInt {
+    event.at.timestamp_millis()
+}
+
+fn load_event_mod(vm: &Thread) -> vm::Result<ExternModule> {
+    let module = record! {
+        new_event => primitive!(1, new_event),
+        event_millis => primitive!(1, event_millis),
+    };
+
+    ExternModule::new(vm, module)
+}
+
+#[test]
+fn roundtrip_struct_with_timestamp() {
+    let _ = env_logger::try_init();
+
+    let vm = new_vm();
+    import::add_extern_module(&vm, "event", load_event_mod);
+
+    let (event, _) = vm
+        .run_expr::<Event>("test", "let { new_event } = import! event in new_event ()")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(event, new_event(()));
+
+    let (millis, _) = vm
+        .run_expr::<VmInt>(
+            "test",
+            "let { new_event, event_millis } = import! event in event_millis (new_event ())",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(millis, timestamp().timestamp_millis());
+}
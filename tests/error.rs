@@ -136,3 +136,44 @@ fn module_imports_provide_a_type_despite_internal_errors() {
 
     "#]].assert_eq(&result.unwrap_err().to_string());
 }
+
+#[test]
+fn import_with_non_literal_argument() {
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    let result = vm.run_expr::<i32>(
+        "test",
+        r#"
+        let name = "std.int"
+        import! (name ++ "")
+        "#,
+    );
+    let err = result.unwrap_err().to_string();
+    assert!(
+        err.contains("Module paths must be literals"),
+        "Unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn missing_module_is_not_found() {
+    use gluon::{import, query::Compilation};
+
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    let err = vm
+        .get_database()
+        .module_text("tests.missing_module".into())
+        .unwrap_err();
+    match err {
+        Error::Other(err) => match *err
+            .downcast::<import::ImportError>()
+            .unwrap_or_else(|err| panic!("Unexpected error: {}", err))
+        {
+            import::ImportError::NotFound(module, _) => assert_eq!(module, "tests.missing_module"),
+            err => panic!("Unexpected error: {}", err),
+        },
+        err => panic!("Unexpected error: {}", err),
+    }
+}
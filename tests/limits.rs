@@ -47,3 +47,25 @@ fn stack_overflow() {
         Ok(_) => panic!("Expected an error"),
     }
 }
+
+#[test]
+fn fuel_exhausted() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    vm.set_fuel(Some(1000));
+    vm.get_database_mut().implicit_prelude(false);
+
+    let expr = r#"
+        let loop x = loop (x #Int+ 1)
+        loop 0
+    "#;
+    let result = vm.run_expr::<OpaqueValue<&Thread, Hole>>("example", expr);
+
+    match result {
+        Err(Error::VM(VMError::FuelExhausted)) => (),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+    assert_eq!(vm.remaining_fuel(), Some(0));
+}
@@ -26,3 +26,59 @@ let { List, of }  = import! std.list
     assert!(env.get_metadata("test.of").is_ok());
     assert!(env.get_metadata("test.List").is_ok());
 }
+
+#[test]
+fn deprecated_binding_emits_warning() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    let text = r#"
+#[deprecated("use y instead")]
+let x = 1
+let y = 2
+x + y
+"#;
+    vm.load_script("test_deprecated", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let warnings = vm
+        .get_database()
+        .warnings("test_deprecated")
+        .expect("Deprecation warning");
+    assert_eq!(warnings.errors().len(), 1);
+    assert_eq!(
+        warnings.errors()[0].value.to_string(),
+        "Use of deprecated binding `x`: use y instead"
+    );
+}
+
+#[test]
+fn diagnostics_grouped_by_severity() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    let text = r#"
+#[deprecated("use y instead")]
+let x = 1
+let y = 2
+x + y
+"#;
+    vm.load_script("test_warning", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let text = r#"
+let x : Int = ""
+x
+"#;
+    assert!(vm.load_script("test_error", text).is_err());
+
+    let diagnostics = vm.get_database().diagnostics();
+    assert_eq!(diagnostics.to_string(), "1 errors, 1 warnings");
+
+    let error = &diagnostics.errors[0];
+    assert_eq!(error.module, "test_error");
+    let location = error.location.expect("Error location");
+    assert_eq!((location.line.0, location.column.0), (1, 14));
+
+    let warning = &diagnostics.warnings[0];
+    assert_eq!(warning.module, "test_warning");
+    let location = warning.location.expect("Warning location");
+    assert_eq!((location.line.0, location.column.0), (4, 0));
+}
@@ -282,3 +282,150 @@ fn issue_805_no_deadlock_in_deserialize() {
         .to_string()
         .contains("is not defined"));
 }
+
+#[test]
+fn roundtrip_database_value() {
+    let thread = new_vm();
+    thread
+        .load_script("test_value", r#" { x = 1, y = "test" } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let db = thread.get_database();
+    let bytes = db
+        .serialize_value("test_value")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let value = db
+        .deserialize_value(&bytes)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let (expected, _) = db.get_binding("test_value").unwrap();
+    assert_eq!(value, expected);
+}
+
+#[test]
+fn value_to_json() {
+    let thread = new_vm();
+    thread
+        .load_script(
+            "json_value",
+            r#"
+            let { Option } = import! std.option
+            {
+                name = "test",
+                version = { major = 1, minor = 2.5 },
+                tags = ["a", "b"],
+                enabled = True,
+                parent = None,
+                child = Some 3,
+                id = \x -> x,
+            }
+            "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let json = thread
+        .get_database()
+        .value_to_json("json_value")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "name": "test",
+            "version": { "major": 1, "minor": 2.5 },
+            "tags": ["a", "b"],
+            "enabled": true,
+            "parent": "None",
+            "child": { "Some": 3 },
+            "id": "<function>",
+        })
+    );
+}
+
+#[test]
+fn compile_and_load_bundle() {
+    use gluon::ModuleBundle;
+
+    let mut bundle = ModuleBundle::new();
+    bundle.add_module("bundle.a", r#" { x = 1 } "#).add_module(
+        "bundle.b",
+        r#" let { x } = import! bundle.a in { y = x + 1 } "#,
+    );
+
+    let thread = new_vm();
+    let mut buffer = Vec::new();
+    futures::executor::block_on(
+        thread.compile_bundle(&bundle, &mut serde_json::Serializer::new(&mut buffer)),
+    )
+    .unwrap_or_else(|err| panic!("{:?}", err.map_left(|err| err.to_string())));
+
+    let thread2 = new_vm();
+    futures::executor::block_on(
+        thread2.load_bundle(&mut serde_json::Deserializer::from_slice(&buffer)),
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    let (y, _) = thread2
+        .run_expr::<i32>("test", "let { y } = import! bundle.b in y")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(y, 2);
+}
+
+#[test]
+fn serialize_and_load_compiled_module() {
+    use gluon::query::CompilationBase;
+
+    let _ = env_logger::try_init();
+
+    let thread = new_vm();
+    thread.get_database_mut().add_module(
+        "compiled".into(),
+        r#" let { id } = import! std.function in { x = id 1 + 2 } "#,
+    );
+
+    let mut buffer = Vec::new();
+    futures::executor::block_on(
+        thread.serialize_compiled("compiled", &mut serde_json::Serializer::new(&mut buffer)),
+    )
+    .unwrap_or_else(|err| panic!("{:?}", err.map_left(|err| err.to_string())));
+
+    thread.get_database_mut().reset();
+
+    futures::executor::block_on(thread.load_compiled(
+        "compiled",
+        &mut serde_json::Deserializer::from_slice(&buffer),
+    ))
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    let (x, _) = thread
+        .run_expr::<i32>("test", "let { x } = import! compiled in x")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(x, 3);
+}
+
+#[test]
+fn canonical_symbols_ignore_formatting() {
+    fn compile(expr: &str) -> Vec<u8> {
+        let thread = new_vm();
+        {
+            let mut db = thread.get_database_mut();
+            db.set_canonical_symbols(true);
+            db.set_emit_debug_info(false);
+            db.set_implicit_prelude(false);
+        }
+        let mut buffer = Vec::new();
+        futures::executor::block_on(thread.compile_to_bytecode(
+            "test",
+            expr,
+            &mut serde_json::Serializer::new(&mut buffer),
+        ))
+        .unwrap_or_else(|err| panic!("{:?}", err.map_left(|err| err.to_string())));
+        buffer
+    }
+
+    let expr1 = "let f x = x #Int+ 1\n{ f }";
+    let expr2 = "\n\nlet f   x =\n    x #Int+ 1\n\n{ f }\n";
+    assert_eq!(
+        String::from_utf8(compile(expr1)).unwrap(),
+        String::from_utf8(compile(expr2)).unwrap()
+    );
+}
@@ -9,7 +9,11 @@ mod support;
 use crate::support::*;
 
 use gluon::{
-    base::{pos::BytePos, source::Source, types::Type},
+    base::{
+        pos::BytePos,
+        source::Source,
+        types::{Type, TypeExt},
+    },
     vm,
     vm::{
         api::{FunctionRef, Hole, OpaqueValue, ValueRef, IO},
@@ -643,6 +647,35 @@ fn access_types_by_path() {
     assert!(result.is_ok(), "{}", result.unwrap_err());
 }
 
+#[test]
+fn find_type_info_through_reexported_alias() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let text = r#" type OtherType = { x : Int } in { OtherType } "#;
+    load_script(&vm, "test_b", text).unwrap_or_else(|err| panic!("{}", err));
+    let text = r#"
+        let b = import! test_b
+        type MyType = b.OtherType
+        { MyType }
+        "#;
+    load_script(&vm, "test_a", text).unwrap_or_else(|err| panic!("{}", err));
+
+    let alias = vm
+        .get_database()
+        .find_type_info("test_a.MyType")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(alias.name.declared_name(), "OtherType");
+    assert_eq!(
+        alias
+            .unresolved_type()
+            .row_iter()
+            .map(|field| field.name.declared_name().to_string())
+            .collect::<Vec<_>>(),
+        ["x"]
+    );
+}
+
 #[test]
 fn opaque_value_type_mismatch() {
     let _ = ::env_logger::try_init();
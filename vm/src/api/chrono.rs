@@ -0,0 +1,84 @@
+//! Marshalling of `chrono` date times.
+//!
+//! `DateTime<Utc>` is represented in gluon as the record `{ secs : Int, nanos : Int }` where
+//! `secs` is the number of seconds since the unix epoch and `nanos` the number of nanoseconds
+//! since the last whole second.
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::base::{
+    symbol::Symbol,
+    types::{ArcType, Field, Type},
+};
+
+use crate::{
+    api::{ActiveThread, Getable, Pushable, ValueRef, VmType},
+    thread::Thread,
+    types::VmInt,
+    Error, Result, Variants,
+};
+
+const NANOS_PER_SEC: VmInt = 1_000_000_000;
+
+fn timestamp_from_value(value: Variants) -> Result<DateTime<Utc>> {
+    let (secs, nanos) = match value.as_ref() {
+        ValueRef::Data(data) => match (data.get(0), data.get(1)) {
+            (Some(ValueRef::Int(secs)), Some(ValueRef::Int(nanos))) => (secs, nanos),
+            _ => ice!("Timestamp record does not contain two Int fields"),
+        },
+        _ => ice!("ValueRef is not a Timestamp record: {:?}", value),
+    };
+    if nanos < 0 || nanos >= NANOS_PER_SEC {
+        return Err(Error::Message(format!(
+            "Timestamp nanoseconds must be in the range 0..{}, got {}",
+            NANOS_PER_SEC, nanos
+        )));
+    }
+    Utc.timestamp_opt(secs, nanos as u32)
+        .single()
+        .ok_or_else(|| Error::Message(format!("Timestamp `{}` is out of range", secs)))
+}
+
+impl VmType for DateTime<Utc> {
+    type Type = Self;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        let type_cache = vm.global_env().type_cache();
+        type_cache.record(
+            Vec::new(),
+            vec![
+                Field::new(Symbol::from("secs"), Type::int()),
+                Field::new(Symbol::from("nanos"), Type::int()),
+            ],
+        )
+    }
+}
+
+impl<'vm> Pushable<'vm> for DateTime<Utc> {
+    fn vm_push(self, context: &mut ActiveThread<'vm>) -> Result<()> {
+        let thread = context.thread();
+        let field_names = [
+            thread.global_env().intern("secs")?,
+            thread.global_env().intern("nanos")?,
+        ];
+        self.timestamp().vm_push(context)?;
+        (self.timestamp_subsec_nanos() as VmInt).vm_push(context)?;
+        context.context().push_new_record(2, &field_names)?;
+        Ok(())
+    }
+}
+
+impl<'vm, 'value> Getable<'vm, 'value> for DateTime<Utc> {
+    type Proxy = DateTime<Utc>;
+
+    fn from_value(_vm: &'vm Thread, value: Variants<'value>) -> Self {
+        timestamp_from_value(value).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn to_proxy(_vm: &'vm Thread, value: Variants<'value>) -> Result<Self::Proxy> {
+        timestamp_from_value(value)
+    }
+
+    fn from_proxy(_vm: &'vm Thread, proxy: &'value mut Self::Proxy) -> Self {
+        *proxy
+    }
+}
@@ -10,6 +10,7 @@ use futures::{
     task::{self, Poll},
 };
 
+use crate::base::metadata::{Attribute, Metadata};
 use crate::base::symbol::Symbol;
 use crate::base::types::ArcType;
 
@@ -24,6 +25,22 @@ use crate::{Error, Result, Variants};
 
 pub type GluonFunction = extern "C" fn(&Thread) -> Status;
 
+/// Adds the `#[lossy_arguments(..)]` attribute for a function taking `conversions` to `metadata`.
+/// The typechecker uses it to reject lossy arguments when strict conversions are enabled.
+fn lossy_arguments_metadata(metadata: &mut Metadata, conversions: &[Option<&'static str>]) {
+    if conversions.iter().any(|conversion| conversion.is_some()) {
+        let arguments = conversions
+            .iter()
+            .map(|conversion| conversion.unwrap_or("_"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        metadata.attributes.push(Attribute {
+            name: "lossy_arguments".into(),
+            arguments: Some(arguments),
+        });
+    }
+}
+
 pub struct Primitive<F> {
     /// Exposed for macros
     #[doc(hidden)]
@@ -71,6 +88,9 @@ impl<'vm, F: VmType> VmType for Primitive<F> {
     fn make_type(vm: &Thread) -> ArcType {
         F::make_type(vm)
     }
+    fn make_metadata(metadata: &mut Metadata) {
+        F::make_metadata(metadata)
+    }
 }
 
 impl<'vm, F> Pushable<'vm> for Primitive<F>
@@ -363,6 +383,10 @@ where
         let args = vec![$(make_type::<$args>(vm)),*];
         vm.global_env().type_cache().function(args, make_type::<$ret_ty>(vm))
     }
+
+    fn make_metadata(metadata: &mut Metadata) {
+        lossy_arguments_metadata(metadata, &[$(<$args as VmType>::LOSSY_CONVERSION),*]);
+    }
 }
 
 vm_function_impl!([fn] $($args),* -> $ret, $ret_ty);
@@ -402,6 +426,10 @@ where
     fn make_type(vm: &Thread) -> ArcType {
         <fn ($($args),*) -> $ret_ty>::make_type(vm)
     }
+
+    fn make_metadata(metadata: &mut Metadata) {
+        <fn ($($args),*) -> $ret_ty>::make_metadata(metadata)
+    }
 }
 
 impl<T, $($args,)* $ret> Function<T, fn($($args),*) -> $ret_ty>
@@ -4,16 +4,18 @@ use std::{
     borrow::Borrow,
     cell::Ref,
     cmp::Ordering,
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap},
     ffi::{OsStr, OsString},
     fmt,
+    hash::{BuildHasher, Hash},
     marker::PhantomData,
-    ops::Deref,
+    ops::{Deref, Range, RangeInclusive},
     path::{Path, PathBuf},
     result::Result as StdResult,
 };
 
 use crate::base::{
+    metadata::Metadata,
     scoped_map::ScopedMap,
     symbol::{Symbol, Symbols},
     types::{self, ArcType, Field, Type},
@@ -69,6 +71,8 @@ mod opaque;
 pub mod record;
 pub mod scoped;
 
+#[cfg(feature = "chrono")]
+mod chrono;
 #[cfg(feature = "serde")]
 pub mod de;
 #[cfg(feature = "serde")]
@@ -235,6 +239,19 @@ impl<'a> Data<'a> {
         }
     }
 
+    /// Retrieves the index of the field `name` which can then be passed to `get_variant`.
+    /// Records of the same type always store a field at the same index.
+    #[doc(hidden)]
+    pub fn lookup_field_index(&self, thread: &Thread, name: &str) -> Option<usize> {
+        match &self.0 {
+            DataInner::Tag(_) => None,
+            DataInner::Data(data) => {
+                let name = thread.global_env().intern(name).ok()?;
+                data.field_map().get(&name).map(|&index| index as usize)
+            }
+        }
+    }
+
     /// Retrieves the field `name` from this record.
     pub fn lookup_field(&self, thread: &Thread, name: &str) -> Option<Variants<'a>> {
         match &self.0 {
@@ -455,10 +472,18 @@ pub trait VmType {
         })
     }
 
+    /// Adds information about `Self` which can't be expressed by its gluon type to `metadata`.
+    /// Functions use this to mark the arguments which are converted with `LOSSY_CONVERSION`.
+    fn make_metadata(_metadata: &mut Metadata) {}
+
     /// How many extra arguments a function returning this type requires.
     /// Used for abstract types which when used in return position should act like they still need
     /// more arguments before they are called
     const EXTRA_ARGS: VmIndex = 0;
+
+    /// The name of the rust type if converting a gluon value to `Self` may lose information
+    /// (`2147483648` does not fit in an `i32`)
+    const LOSSY_CONVERSION: Option<&'static str> = None;
 }
 
 /// Trait which allows a possibly asynchronous rust value to be pushed to the virtual machine
@@ -565,6 +590,20 @@ pub trait Getable<'vm, 'value>: Sized {
 
     fn to_proxy(vm: &'vm Thread, value: Variants<'value>) -> Result<Self::Proxy>;
     fn from_proxy(vm: &'vm Thread, proxy: &'value mut Self::Proxy) -> Self;
+
+    /// Converts each of the elements of an array. All elements are of the same type so
+    /// implementations may override this to validate the representation of the first element
+    /// only instead of each element individually.
+    #[doc(hidden)]
+    fn from_values<I>(vm: &'vm Thread, values: I) -> Vec<Self>
+    where
+        I: IntoIterator<Item = Variants<'value>>,
+    {
+        values
+            .into_iter()
+            .map(|value| Self::from_value(vm, value))
+            .collect()
+    }
 }
 
 pub fn convert<'vm, T, U>(thread: &'vm Thread, t: T) -> Result<U>
@@ -809,6 +848,69 @@ where
     }
 }
 
+/// Wrapper type which pushes `value` by calling `push` and has the gluon type of `Ty`.
+///
+/// Useful to marshal types which can't implement `Pushable` directly (due to the orphan rules)
+/// without defining a wrapper type. Constructed with `with_push`.
+pub struct PushWith<T, F, Ty: ?Sized> {
+    pub value: T,
+    pub push: F,
+    _marker: PhantomData<fn() -> Ty>,
+}
+
+/// Creates a value which is pushed by calling `push` with `value` and which has the same type as
+/// `Ty` in gluon.
+///
+/// ```rust
+/// extern crate gluon_vm;
+///
+/// use std::time::Duration;
+///
+/// use gluon_vm::api::{with_push, Pushable};
+/// use gluon_vm::types::VmInt;
+///
+/// fn main() {
+///     let duration = Duration::new(3, 500);
+///     let _value = with_push::<(VmInt, VmInt), _, _>(duration, |duration, context| {
+///         (duration.as_secs() as VmInt, duration.subsec_nanos() as VmInt).vm_push(context)
+///     });
+/// }
+/// ```
+pub fn with_push<'vm, Ty, T, F>(value: T, push: F) -> PushWith<T, F, Ty>
+where
+    F: FnOnce(T, &mut ActiveThread<'vm>) -> Result<()>,
+    Ty: ?Sized + VmType,
+{
+    PushWith {
+        value,
+        push,
+        _marker: PhantomData,
+    }
+}
+
+impl<T, F, Ty> VmType for PushWith<T, F, Ty>
+where
+    Ty: ?Sized + VmType,
+{
+    type Type = Ty::Type;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        Ty::make_type(vm)
+    }
+
+    const EXTRA_ARGS: VmIndex = Ty::EXTRA_ARGS;
+}
+
+impl<'vm, T, F, Ty> Pushable<'vm> for PushWith<T, F, Ty>
+where
+    F: FnOnce(T, &mut ActiveThread<'vm>) -> Result<()>,
+    Ty: ?Sized + VmType,
+{
+    fn vm_push(self, context: &mut ActiveThread<'vm>) -> Result<()> {
+        (self.push)(self.value, context)
+    }
+}
+
 impl VmType for () {
     type Type = Self;
 }
@@ -853,6 +955,12 @@ macro_rules! int_impls {
         $(
         impl VmType for $id {
             type Type = VmInt;
+
+            const LOSSY_CONVERSION: Option<&'static str> = if $id::MIN as i128 > VmInt::MIN as i128 {
+                Some(stringify!($id))
+            } else {
+                None
+            };
         }
         impl<'vm> Pushable<'vm> for $id {
             #[inline]
@@ -902,6 +1010,8 @@ impl<'vm, 'value> Getable<'vm, 'value> for f64 {
 
 impl VmType for f32 {
     type Type = Self;
+
+    const LOSSY_CONVERSION: Option<&'static str> = Some("f32");
 }
 impl<'vm> Pushable<'vm> for f32 {
     #[inline]
@@ -1208,7 +1318,10 @@ where
     impl_getable_simple!();
 
     fn from_value(vm: &'vm Thread, value: Variants<'value>) -> Vec<T> {
-        Collect::<GetableIter<T>>::from_value(vm, value).collect()
+        match value.as_ref() {
+            ValueRef::Array(data) => T::from_values(vm, data.as_ref().iter()),
+            _ => panic!("Expected array"),
+        }
     }
 }
 
@@ -1293,6 +1406,47 @@ where
     }
 }
 
+impl<K, V, S> VmType for HashMap<K, V, S>
+where
+    K: VmType,
+    K::Type: Sized,
+    V: VmType,
+    V::Type: Sized,
+{
+    type Type = BTreeMap<K::Type, V::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        BTreeMap::<K, V>::make_type(vm)
+    }
+}
+
+impl<'vm, K, V, S> Pushable<'vm> for HashMap<K, V, S>
+where
+    K: Borrow<str> + VmType,
+    K::Type: Sized,
+    V: for<'vm2> Pushable<'vm2> + VmType,
+    V::Type: Sized,
+{
+    fn vm_push(self, context: &mut ActiveThread<'vm>) -> Result<()> {
+        to_gluon_map(self, context)
+    }
+}
+
+impl<'vm, 'value, K, V, S> Getable<'vm, 'value> for HashMap<K, V, S>
+where
+    K: Getable<'vm, 'value> + Eq + Hash + Ord,
+    V: Getable<'vm, 'value>,
+    S: BuildHasher + Default,
+{
+    impl_getable_simple!();
+
+    fn from_value(vm: &'vm Thread, value: Variants<'value>) -> Self {
+        let mut map = HashMap::default();
+        from_gluon_map(&mut map, vm, value);
+        map
+    }
+}
+
 fn to_gluon_map<'vm, K, V>(
     map_iter: impl IntoIterator<Item = (K, V)>,
     context: &mut ActiveThread<'vm>,
@@ -1696,6 +1850,103 @@ macro_rules! define_tuples {
 }
 define_tuples! { A B C D E F G H I J K L }
 
+// Ranges are represented as the record `{ start : T, end : T }`, whether `end` is inclusive is
+// only known on the Rust side
+fn make_range_type<T: VmType>(vm: &Thread) -> ArcType {
+    let type_cache = vm.global_env().type_cache();
+    type_cache.record(
+        Vec::new(),
+        vec![
+            Field::new(Symbol::from("start"), T::make_type(vm)),
+            Field::new(Symbol::from("end"), T::make_type(vm)),
+        ],
+    )
+}
+
+fn push_range<'vm, T: Pushable<'vm>>(
+    start: T,
+    end: T,
+    context: &mut ActiveThread<'vm>,
+) -> Result<()> {
+    let thread = context.thread();
+    let field_names = [
+        thread.global_env().intern("start")?,
+        thread.global_env().intern("end")?,
+    ];
+    start.vm_push(context)?;
+    end.vm_push(context)?;
+    context.context().push_new_record(2, &field_names)?;
+    Ok(())
+}
+
+fn range_from_value<'vm, 'value, T: Getable<'vm, 'value>>(
+    vm: &'vm Thread,
+    value: Variants<'value>,
+) -> (T, T) {
+    match value.as_ref() {
+        ValueRef::Data(data) => {
+            match (data.lookup_field(vm, "start"), data.lookup_field(vm, "end")) {
+                (Some(start), Some(end)) => (T::from_value(vm, start), T::from_value(vm, end)),
+                _ => ice!("Range record does not contain `start` and `end` fields"),
+            }
+        }
+        _ => ice!("ValueRef is not a Range record: {:?}", value),
+    }
+}
+
+impl<T: VmType> VmType for Range<T>
+where
+    T::Type: Sized,
+{
+    type Type = Range<T::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        make_range_type::<T>(vm)
+    }
+}
+
+impl<'vm, T: Pushable<'vm>> Pushable<'vm> for Range<T> {
+    fn vm_push(self, context: &mut ActiveThread<'vm>) -> Result<()> {
+        push_range(self.start, self.end, context)
+    }
+}
+
+impl<'vm, 'value, T: Getable<'vm, 'value>> Getable<'vm, 'value> for Range<T> {
+    impl_getable_simple!();
+
+    fn from_value(vm: &'vm Thread, value: Variants<'value>) -> Self {
+        let (start, end) = range_from_value(vm, value);
+        start..end
+    }
+}
+
+impl<T: VmType> VmType for RangeInclusive<T>
+where
+    T::Type: Sized,
+{
+    type Type = RangeInclusive<T::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        make_range_type::<T>(vm)
+    }
+}
+
+impl<'vm, T: Pushable<'vm>> Pushable<'vm> for RangeInclusive<T> {
+    fn vm_push(self, context: &mut ActiveThread<'vm>) -> Result<()> {
+        let (start, end) = self.into_inner();
+        push_range(start, end, context)
+    }
+}
+
+impl<'vm, 'value, T: Getable<'vm, 'value>> Getable<'vm, 'value> for RangeInclusive<T> {
+    impl_getable_simple!();
+
+    fn from_value(vm: &'vm Thread, value: Variants<'value>) -> Self {
+        let (start, end) = range_from_value(vm, value);
+        start..=end
+    }
+}
+
 pub struct Map<K, V>(PhantomData<(K, V)>);
 
 impl<K: VmType, V: VmType> VmType for Map<K, V>
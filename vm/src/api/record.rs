@@ -1,8 +1,11 @@
 use std::any::Any;
 
+use std::sync::Arc;
+
 use frunk_core::hlist::{h_cons, HCons, HList, HNil};
 
 use crate::base::{
+    metadata::Metadata,
     symbol::Symbol,
     types::{self, Alias, AliasData, ArcType, Type},
 };
@@ -34,6 +37,7 @@ pub trait FieldTypes: HList {
 pub trait FieldValues: HList {
     type Type: Any;
     fn field_values(vm: &Thread, fields: &mut Vec<types::Field<Symbol, ArcType>>);
+    fn field_metadata(metadata: &mut Metadata);
 }
 
 pub trait PushableFieldList<'vm>: HList {
@@ -145,6 +149,7 @@ where
 impl FieldValues for HNil {
     type Type = ();
     fn field_values(_: &Thread, _: &mut Vec<types::Field<Symbol, ArcType>>) {}
+    fn field_metadata(_: &mut Metadata) {}
 }
 
 impl<F: Field, H: VmType, T> FieldValues for HCons<(F, H), T>
@@ -160,6 +165,16 @@ where
         fields.push(types::Field::new(name, H::make_type(vm)));
         T::field_values(vm, fields);
     }
+    fn field_metadata(metadata: &mut Metadata) {
+        let mut field_metadata = Metadata::default();
+        H::make_metadata(&mut field_metadata);
+        if field_metadata.has_data() {
+            metadata
+                .module
+                .insert(F::name().into(), Arc::new(field_metadata));
+        }
+        T::field_metadata(metadata);
+    }
 }
 
 impl<'vm, F: Field, H: Pushable<'vm>, T> PushableFieldList<'vm> for HCons<(F, H), T>
@@ -200,6 +215,9 @@ impl<T: FieldTypes, U: FieldValues> VmType for Record<T, U> {
         let type_cache = vm.global_env().type_cache();
         type_cache.record(type_fields, fields)
     }
+    fn make_metadata(metadata: &mut Metadata) {
+        U::field_metadata(metadata)
+    }
 }
 impl<'vm, T, U> Pushable<'vm> for Record<T, U>
 where
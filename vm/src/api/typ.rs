@@ -3,15 +3,15 @@
 //! _This module requires Gluon to be built with the `serde` feature._
 
 use crate::base::symbol::{Symbol, Symbols};
-use crate::base::types::{ArcType, Field, Type, TypeCache, TypeExt};
+use crate::base::types::{ArcType, Field, Type, TypeCache, TypePtr};
 
 use crate::api::VmType;
 use crate::thread::Thread;
 use crate::{Error as VmError, Result};
 
 use crate::serde::de::{
-    self, DeserializeOwned, DeserializeSeed, EnumAccess, Error, IntoDeserializer, MapAccess,
-    SeqAccess, VariantAccess, Visitor,
+    self, Deserialize, DeserializeSeed, EnumAccess, Error, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
 };
 
 /// Generates a Gluon expression for a type that is the Gluon equivalent to`T`.
@@ -33,18 +33,26 @@ use crate::serde::de::{
 /// type Address = { street: String, city: String }
 /// { Address }
 /// ```
+///
+/// Fields and variants are named by their `serde` names. The derives of `gluon_codegen` only use
+/// the names given with `#[gluon(rename = "...")]` and `#[gluon(tag = "...")]` so a renamed field
+/// or variant needs both attributes.
 pub fn make_source<T>(thread: &Thread) -> Result<String>
 where
-    T: DeserializeOwned,
+    T: Deserialize<'static>,
 {
     let (name, typ) = from_rust::<T>(thread)?;
+    // Print `std.types.Option` as `Option` which the implicit prelude brings into scope
+    let arena = ::pretty::Arena::<()>::new();
     Ok(format!(
         r#"
 type {0} = {1}
 {{ {0} }}
 "#,
         name,
-        typ.pretty(&::pretty::Arena::<()>::new())
+        typ.display(80)
+            .symbol_text(&|s: &Symbol| s.declared_name())
+            .pretty(&arena)
             .nest(4)
             .1
             .pretty(80)
@@ -54,7 +62,7 @@ type {0} = {1}
 /// Deserializes `T` from a gluon value assuming that `value` is of type `typ`.
 pub fn from_rust<T>(thread: &Thread) -> Result<(Symbol, ArcType)>
 where
-    T: DeserializeOwned,
+    T: Deserialize<'static>,
 {
     let mut symbols = Symbols::new();
     from_rust_with_symbols::<T>(&mut symbols, thread)
@@ -65,7 +73,7 @@ pub fn from_rust_with_symbols<T>(
     thread: &Thread,
 ) -> Result<(Symbol, ArcType)>
 where
-    T: DeserializeOwned,
+    T: Deserialize<'static>,
 {
     let type_cache = thread.global_env().type_cache();
     let mut deserializer = Deserializer::from_value(&type_cache, thread, symbols);
@@ -91,25 +99,25 @@ where
     ))
 }
 
-struct State<'de> {
-    cache: &'de TypeCache<Symbol, ArcType<Symbol>>,
-    thread: &'de Thread,
-    symbols: &'de mut Symbols,
+struct State<'s> {
+    cache: &'s TypeCache<Symbol, ArcType<Symbol>>,
+    thread: &'s Thread,
+    symbols: &'s mut Symbols,
 }
 
-struct Deserializer<'de> {
-    state: State<'de>,
+struct Deserializer<'s> {
+    state: State<'s>,
     typ: Option<ArcType>,
     variant: Option<Field<Symbol, ArcType>>,
     variant_index: usize,
     name: &'static str,
 }
 
-impl<'de> Deserializer<'de> {
+impl<'s> Deserializer<'s> {
     fn from_value(
-        cache: &'de TypeCache<Symbol, ArcType<Symbol>>,
-        thread: &'de Thread,
-        symbols: &'de mut Symbols,
+        cache: &'s TypeCache<Symbol, ArcType<Symbol>>,
+        thread: &'s Thread,
+        symbols: &'s mut Symbols,
     ) -> Self {
         Deserializer {
             state: State {
@@ -125,7 +133,9 @@ impl<'de> Deserializer<'de> {
     }
 }
 
-impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+// The types only borrow `'static` data so any `'de` lifetime can be used, letting types which
+// borrow from the deserializer (such as `&'a str`) be converted as well
+impl<'de, 's, 'a> de::Deserializer<'de> for &'a mut Deserializer<'s> {
     type Error = VmError;
 
     fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
@@ -238,7 +248,7 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         self.typ = Some(self.state.cache.string());
-        visitor.visit_str("")
+        visitor.visit_borrowed_str("")
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
@@ -253,7 +263,7 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         self.typ = Some(Type::array(self.state.cache.byte()));
-        visitor.visit_bytes(b"")
+        visitor.visit_borrowed_bytes(b"")
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
@@ -337,14 +347,16 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
     fn deserialize_tuple_struct<V>(
         self,
-        _name: &'static str,
-        _len: usize,
+        name: &'static str,
+        len: usize,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        // Represented as a tuple, the same as the derived `VmType` of a tuple struct
+        self.name = name;
+        self.deserialize_tuple(len, visitor)
     }
 
     fn deserialize_map<V>(self, _visitor: V) -> Result<V::Value>
@@ -408,14 +420,14 @@ impl<'de, 't, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     }
 }
 
-struct SeqDeserializer<'de: 't, 't> {
-    deserializer: &'t mut Deserializer<'de>,
+struct SeqDeserializer<'s: 't, 't> {
+    deserializer: &'t mut Deserializer<'s>,
     types: Vec<ArcType>,
     len: usize,
 }
 
-impl<'de, 't> SeqDeserializer<'de, 't> {
-    fn new(deserializer: &'t mut Deserializer<'de>, len: usize) -> Self {
+impl<'s, 't> SeqDeserializer<'s, 't> {
+    fn new(deserializer: &'t mut Deserializer<'s>, len: usize) -> Self {
         SeqDeserializer {
             deserializer,
             len,
@@ -424,7 +436,7 @@ impl<'de, 't> SeqDeserializer<'de, 't> {
     }
 }
 
-impl<'de, 'a, 't> SeqAccess<'de> for &'a mut SeqDeserializer<'de, 't> {
+impl<'de, 's, 'a, 't> SeqAccess<'de> for &'a mut SeqDeserializer<'s, 't> {
     type Error = VmError;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -442,14 +454,14 @@ impl<'de, 'a, 't> SeqAccess<'de> for &'a mut SeqDeserializer<'de, 't> {
     }
 }
 
-struct MapDeserializer<'de: 't, 't, I> {
-    deserializer: &'t mut Deserializer<'de>,
+struct MapDeserializer<'s: 't, 't, I> {
+    deserializer: &'t mut Deserializer<'s>,
     iter: I,
     types: Vec<Field<Symbol, ArcType>>,
 }
 
-impl<'de, 't, I> MapDeserializer<'de, 't, I> {
-    fn new(deserializer: &'t mut Deserializer<'de>, iter: I) -> Self {
+impl<'s, 't, I> MapDeserializer<'s, 't, I> {
+    fn new(deserializer: &'t mut Deserializer<'s>, iter: I) -> Self {
         MapDeserializer {
             deserializer,
             iter,
@@ -458,7 +470,7 @@ impl<'de, 't, I> MapDeserializer<'de, 't, I> {
     }
 }
 
-impl<'de, 't, I> MapAccess<'de> for MapDeserializer<'de, 't, I>
+impl<'de, 's, 't, I> MapAccess<'de> for MapDeserializer<'s, 't, I>
 where
     I: Iterator<Item = &'static str> + Clone,
 {
@@ -492,18 +504,18 @@ where
     }
 }
 
-struct Enum<'a, 'de: 'a> {
-    de: &'a mut Deserializer<'de>,
+struct Enum<'a, 's: 'a> {
+    de: &'a mut Deserializer<'s>,
     variant: &'static str,
 }
 
-impl<'a, 'de> Enum<'a, 'de> {
-    fn new(de: &'a mut Deserializer<'de>, variant: &'static str) -> Self {
+impl<'a, 's> Enum<'a, 's> {
+    fn new(de: &'a mut Deserializer<'s>, variant: &'static str) -> Self {
         Enum { de, variant }
     }
 }
 
-impl<'a, 'de> EnumAccess<'de> for Enum<'a, 'de> {
+impl<'a, 'de, 's> EnumAccess<'de> for Enum<'a, 's> {
     type Error = VmError;
     type Variant = Self;
 
@@ -516,7 +528,7 @@ impl<'a, 'de> EnumAccess<'de> for Enum<'a, 'de> {
     }
 }
 
-impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
+impl<'de, 's, 'a> VariantAccess<'de> for Enum<'a, 's> {
     type Error = VmError;
 
     fn unit_variant(self) -> Result<()> {
@@ -642,6 +654,25 @@ mod tests {
         );
     }
 
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Point(f64, f64);
+
+    #[test]
+    fn tuple_struct_type() {
+        let mut symbols = Symbols::new();
+        let thread = RootedThread::new();
+        let (name, typ) = from_rust_with_symbols::<Point>(&mut symbols, &thread).unwrap();
+        assert_eq!(name.declared_name(), "Point");
+        assert_eq!(
+            typ,
+            thread
+                .global_env()
+                .type_cache()
+                .tuple(&mut symbols, vec![Type::float(), Type::float()])
+        );
+    }
+
     #[derive(Deserialize)]
     struct MyArray(Vec<f64>);
 
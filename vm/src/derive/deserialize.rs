@@ -9,6 +9,14 @@ use crate::macros::Error;
 
 use crate::derive::*;
 
+/// The modules imported by the generated code
+pub(super) const IMPORTS: [&str; 4] = [
+    "std.json.de",
+    "std.functor",
+    "std.applicative",
+    "std.alternative",
+];
+
 pub fn generate<'ast>(
     mut arena: ast::ArenaRef<'_, 'ast, Symbol>,
     symbols: &mut Symbols,
@@ -87,17 +95,18 @@ pub fn generate<'ast>(
         _ => return Err(Error::message("Unable to derive Deserialize for this type")),
     };
 
+    let [json_de, functor, applicative, alternative] = IMPORTS;
     let serialization_import = arena.generate_import_(
         span,
         symbols,
         &["ValueDeserializer"],
         &["deserializer", "field"],
         true,
-        "std.json.de",
+        json_de,
     );
-    let functor_import = arena.generate_import(span, symbols, &[], &["map"], "std.functor");
-    let applicative_import = arena.generate_import(span, symbols, &[], &["<*>"], "std.applicative");
-    let alternative_import = arena.generate_import(span, symbols, &[], &["<|>"], "std.alternative");
+    let functor_import = arena.generate_import(span, symbols, &[], &["map"], functor);
+    let applicative_import = arena.generate_import(span, symbols, &[], &["<*>"], applicative);
+    let alternative_import = arena.generate_import(span, symbols, &[], &["<|>"], alternative);
 
     let deserializer_binding = ValueBinding {
         name: pos::spanned(span, Pattern::Ident(deserializer_fn.clone())),
@@ -0,0 +1,225 @@
+use crate::base::{
+    ast::{
+        self, Alternative, Argument, AstType, Expr, ExprField, Pattern, TypeBinding, TypedIdent,
+        ValueBinding,
+    },
+    pos,
+    symbol::{Symbol, Symbols},
+    types::{
+        ctor_args, fold_type, remove_forall, row_iter, Generic, KindedIdent, Type, TypeContext,
+    },
+};
+
+use crate::macros::Error;
+
+use crate::derive::*;
+
+/// Returns `Ok(true)` if `typ` is the type parameter `param` itself and `Ok(false)` if `param`
+/// does not occur in `typ`. Other uses of `param` can't be mapped over so they are an error.
+fn is_mapped(param: &Generic<Symbol>, typ: &AstType<Symbol>) -> Result<bool, Error> {
+    match **typ {
+        Type::Generic(ref gen) if gen.id == param.id => Ok(true),
+        _ => {
+            let contains_param = fold_type(
+                typ,
+                |typ, found| {
+                    found
+                        || match **typ {
+                            Type::Generic(ref gen) => gen.id == param.id,
+                            _ => false,
+                        }
+                },
+                false,
+            );
+            if contains_param {
+                Err(Error::message(format!(
+                    "Unable to derive Functor, `{}` is only mapped over when it is the type of \
+                     a field or constructor argument",
+                    param.id.declared_name()
+                )))
+            } else {
+                Ok(false)
+            }
+        }
+    }
+}
+
+pub fn generate<'ast>(
+    arena: ast::ArenaRef<'_, 'ast, Symbol>,
+    symbols: &mut Symbols,
+    bind: &TypeBinding<'ast, Symbol>,
+) -> Result<ValueBinding<'ast, Symbol>, Error> {
+    let span = bind.name.span;
+
+    let (param, params) = bind.alias.value.params().split_last().ok_or_else(|| {
+        Error::message("Functor can only be derived for types with at least one type parameter")
+    })?;
+
+    let map = TypedIdent::new(symbols.simple_symbol("map"));
+    let f = Symbol::from("f");
+    let x = Symbol::from("x");
+
+    let apply_f = |expr| arena.app(span, f.clone(), Some(expr));
+
+    let map_expr = match **remove_forall(bind.alias.value.unresolved_type()) {
+        Type::Record(ref row) => {
+            let field_symbols: Vec<_> = row_iter(row)
+                .map(|field| {
+                    TypedIdent::new(Symbol::from(format!(
+                        "{}_field",
+                        field.name.declared_name()
+                    )))
+                })
+                .collect();
+
+            let exprs = row_iter(row)
+                .zip(&field_symbols)
+                .map(|(field, symbol)| {
+                    let value = ident(span, symbol.name.clone());
+                    Ok(ExprField {
+                        metadata: Default::default(),
+                        name: field.name.clone(),
+                        value: Some(if is_mapped(param, &field.typ)? {
+                            apply_f(value)
+                        } else {
+                            value
+                        }),
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            let expr = pos::spanned(
+                span,
+                Expr::Record {
+                    typ: Type::hole(),
+                    types: &mut [],
+                    exprs: arena.alloc_extend(exprs),
+                    base: None,
+                },
+            );
+
+            Expr::Match(
+                arena.alloc(ident(span, x.clone())),
+                arena.alloc_extend(Some(Alternative {
+                    pattern: arena.generate_record_pattern(span, row, field_symbols),
+                    expr,
+                })),
+            )
+        }
+        Type::Variant(ref row) => {
+            let alts = row_iter(row)
+                .map(|variant| {
+                    let pattern_args: Vec<_> = ctor_args(&variant.typ)
+                        .enumerate()
+                        .map(|(i, _)| TypedIdent::new(Symbol::from(format!("arg_{}", i))))
+                        .collect();
+
+                    let args = ctor_args(&variant.typ)
+                        .zip(&pattern_args)
+                        .map(|(typ, arg)| {
+                            let value = ident(span, arg.name.clone());
+                            Ok(if is_mapped(param, typ)? {
+                                apply_f(value)
+                            } else {
+                                value
+                            })
+                        })
+                        .collect::<Result<Vec<_>, Error>>()?;
+
+                    Ok(Alternative {
+                        pattern: pos::spanned(
+                            span,
+                            Pattern::Constructor(
+                                TypedIdent::new(variant.name.value.clone()),
+                                arena.alloc_extend(
+                                    pattern_args
+                                        .into_iter()
+                                        .map(|arg| pos::spanned(span, Pattern::Ident(arg))),
+                                ),
+                            ),
+                        ),
+                        expr: arena.app(span, variant.name.value.clone(), args),
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            Expr::Match(
+                arena.alloc(ident(span, x.clone())),
+                arena.alloc_extend(alts),
+            )
+        }
+        _ => return Err(Error::message("Unable to derive Functor for this type")),
+    };
+
+    let functor_record_expr =
+        Expr::rec_let_bindings(
+            arena,
+            vec![ValueBinding {
+                name: pos::spanned(span, Pattern::Ident(map.clone())),
+                args: arena.alloc_extend([f, x].iter().map(|arg| {
+                    Argument::explicit(pos::spanned(span, TypedIdent::new(arg.clone())))
+                })),
+                expr: pos::spanned(span, map_expr),
+                metadata: Default::default(),
+                typ: None,
+                resolved_type: Type::hole(),
+            }],
+            pos::spanned(
+                span,
+                Expr::Record {
+                    typ: Type::hole(),
+                    types: &mut [],
+                    exprs: arena.alloc_extend(Some(ExprField {
+                        metadata: Default::default(),
+                        name: pos::spanned(span, map.name.clone()),
+                        value: Some(ident(span, map.name.clone())),
+                    })),
+                    base: None,
+                },
+            ),
+        );
+
+    // `Functor` is implemented for the type applied to every parameter except the last
+    // (`Functor (Either e)`)
+    let type_constructor = {
+        let name = arena
+            .clone()
+            .ident(KindedIdent::new(bind.alias.value.name.clone()));
+        if params.is_empty() {
+            name
+        } else {
+            let args = arena.clone().alloc_extend(
+                params
+                    .iter()
+                    .cloned()
+                    .map(|param| arena.clone().generic(param)),
+            );
+            TypeContext::app(&mut arena.clone(), name, args)
+        }
+    };
+    let mut typ = TypeContext::app(
+        &mut arena.clone(),
+        arena
+            .clone()
+            .ident(KindedIdent::new(symbols.simple_symbol("Functor"))),
+        arena.clone().alloc_extend(Some(type_constructor)),
+    );
+    crate::base::types::walk_type_mut(&mut typ, &mut |typ: &mut AstType<_>| {
+        *typ.span_mut() = span;
+    });
+
+    Ok(ValueBinding {
+        name: pos::spanned(
+            span,
+            Pattern::Ident(TypedIdent::new(symbols.simple_symbol(format!(
+                "functor_{}",
+                bind.alias.value.name.declared_name()
+            )))),
+        ),
+        args: &mut [],
+        expr: pos::spanned(span, functor_record_expr),
+        metadata: Default::default(),
+        typ: Some(typ),
+        resolved_type: Type::hole(),
+    })
+}
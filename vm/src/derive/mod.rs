@@ -13,6 +13,7 @@ use crate::macros::Error;
 
 mod deserialize;
 mod eq;
+mod functor;
 mod serialize;
 mod show;
 
@@ -29,6 +30,7 @@ pub fn generate<'ast>(
             .map(|arg| {
                 Ok(match arg {
                     "Eq" => eq::generate(arena, symbols, bind),
+                    "Functor" => functor::generate(arena, symbols, bind),
                     "Show" => show::generate(arena, symbols, bind),
                     "Deserialize" => deserialize::generate(arena, symbols, bind),
                     "Serialize" => serialize::generate(arena, symbols, bind),
@@ -45,6 +47,22 @@ pub fn generate<'ast>(
     }
 }
 
+/// Returns the modules which the code generated by `derive` imports
+pub fn imported_modules(derive: &Attribute) -> Vec<&'static str> {
+    let args = match derive.arguments {
+        Some(ref args) => args,
+        None => return Vec::new(),
+    };
+    args.split(',')
+        .flat_map(|arg| match arg.trim() {
+            "Deserialize" => &deserialize::IMPORTS[..],
+            "Serialize" => &serialize::IMPORTS[..],
+            _ => &[][..],
+        })
+        .cloned()
+        .collect()
+}
+
 impl<'ast> ArenaExt<'ast> for ast::ArenaRef<'_, 'ast, Symbol> {
     fn alloc<T>(self, value: T) -> &'ast mut T
     where
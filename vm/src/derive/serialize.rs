@@ -12,6 +12,16 @@ use crate::macros::Error;
 
 use crate::derive::*;
 
+/// The modules imported by the generated code
+pub(super) const IMPORTS: [&str; 6] = [
+    "std.json.ser",
+    "std.functor",
+    "std.applicative",
+    "std.map",
+    "std.semigroup",
+    "std.result",
+];
+
 pub fn generate<'ast>(
     mut arena: ast::ArenaRef<'_, 'ast, Symbol>,
     symbols: &mut Symbols,
@@ -154,20 +164,20 @@ pub fn generate<'ast>(
         _ => return Err(Error::message("Unable to derive Deserialize for this type")),
     };
 
+    let [json_ser, functor, applicative, map, semigroup, result] = IMPORTS;
     let serialization_import = arena.generate_import_(
         span,
         symbols,
         &["ValueSerializer", "Value"],
         &["serialize"],
         true,
-        "std.json.ser",
+        json_ser,
     );
-    let functor_import = arena.generate_import(span, symbols, &[], &["map"], "std.functor");
-    let applicative_import = arena.generate_import(span, symbols, &[], &["<*>"], "std.applicative");
-    let map_import =
-        arena.generate_import_(span, symbols, &[], &["singleton", "empty"], true, "std.map");
-    let semigroup_import = arena.generate_import(span, symbols, &[], &["<>"], "std.semigroup");
-    let result_import = arena.generate_import_(span, symbols, &[], &[], true, "std.result");
+    let functor_import = arena.generate_import(span, symbols, &[], &["map"], functor);
+    let applicative_import = arena.generate_import(span, symbols, &[], &["<*>"], applicative);
+    let map_import = arena.generate_import_(span, symbols, &[], &["singleton", "empty"], true, map);
+    let semigroup_import = arena.generate_import(span, symbols, &[], &["<>"], semigroup);
+    let result_import = arena.generate_import_(span, symbols, &[], &[], true, result);
 
     let serialize_ = TypedIdent::new(symbols.simple_symbol("serialize_"));
     let serializer_binding = ValueBinding {
@@ -69,9 +69,14 @@ mod interner;
 mod source_map;
 mod value;
 
-use std::{self as real_std, fmt, marker::PhantomData};
+use std::{self as real_std, fmt, marker::PhantomData, sync::Arc};
 
-use crate::base::{metadata::Metadata, source::FileId, symbol::Symbol, types::ArcType};
+use crate::base::{
+    metadata::{Attribute, Comment, CommentType, Metadata},
+    source::FileId,
+    symbol::Symbol,
+    types::ArcType,
+};
 use crate::{
     api::{ValueRef, VmType},
     gc::CloneUnrooted,
@@ -156,8 +161,8 @@ quick_error! {
         Dead {
             display("The gluon thread is dead")
         }
-        UndefinedBinding(symbol: String) {
-            display("Binding `{}` is not defined", symbol)
+        UndefinedBinding(symbol: String, suggestions: Vec<String>) {
+            display("{}", UndefinedBinding { symbol, suggestions })
         }
         UndefinedField(typ: ArcType, field: String) {
             display("Type `{}` does not have the field `{}`", typ, field)
@@ -188,6 +193,12 @@ quick_error! {
         Interrupted {
             display("Thread was interrupted")
         }
+        FuelExhausted {
+            display("Thread exhausted its instruction fuel")
+        }
+        IndexOutOfBounds(index: usize, len: usize) {
+            display("Index `{}` is out of bounds for an array of length {}", index, len)
+        }
         Panic(err: String, stacktrace: Option<Stacktrace>) {
             display("{}", Panic { err, stacktrace })
         }
@@ -196,10 +207,44 @@ quick_error! {
 
 impl base::error::AsDiagnostic for Error {
     fn as_diagnostic(&self, _map: &base::source::CodeMap) -> Diagnostic<FileId> {
-        Diagnostic::error().with_message(self.to_string())
+        match self {
+            Error::UndefinedBinding(symbol, suggestions) if !suggestions.is_empty() => {
+                Diagnostic::error()
+                    .with_message(format!("Binding `{}` is not defined", symbol))
+                    .with_notes(vec![suggestions_note(suggestions)])
+            }
+            _ => Diagnostic::error().with_message(self.to_string()),
+        }
     }
 }
 
+struct UndefinedBinding<'a> {
+    symbol: &'a String,
+    suggestions: &'a [String],
+}
+
+impl<'a> fmt::Display for UndefinedBinding<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let UndefinedBinding {
+            symbol,
+            suggestions,
+        } = *self;
+        write!(f, "Binding `{}` is not defined", symbol)?;
+        if !suggestions.is_empty() {
+            write!(f, "\n{}", suggestions_note(suggestions))?;
+        }
+        Ok(())
+    }
+}
+
+fn suggestions_note(suggestions: &[String]) -> String {
+    let suggestions = suggestions
+        .iter()
+        .map(|suggestion| format!("`{}`", suggestion))
+        .collect::<Vec<_>>();
+    format!("Did you mean {}?", suggestions.join(", "))
+}
+
 struct Panic<'a> {
     err: &'a String,
     stacktrace: &'a Option<Stacktrace>,
@@ -255,9 +300,72 @@ impl ExternModule {
         Ok(ExternModule {
             value: value.marshal(thread)?,
             typ: T::make_forall_type(thread),
-            metadata,
+            metadata: make_metadata::<T>(metadata),
         })
     }
+
+    /// Creates a module whose type, as seen by gluon code, is `typ` instead of the type derived
+    /// from `T`. This can be used to hide a generic parameter of a native function behind a
+    /// concrete type.
+    ///
+    /// Returns an error unless `typ` is a specialization of the derived type.
+    pub fn with_type<'vm, T>(
+        thread: &'vm Thread,
+        value: T,
+        typ: ArcType,
+        metadata: Metadata,
+    ) -> Result<ExternModule>
+    where
+        T: VmType + api::Pushable<'vm> + Send + Sync,
+    {
+        use crate::check::check_signature;
+
+        let actual = T::make_forall_type(thread);
+        if !check_signature(&thread.get_env(), &typ, &actual) {
+            return Err(Error::WrongType(typ, actual));
+        }
+        Ok(ExternModule {
+            value: value.marshal(thread)?,
+            typ,
+            metadata: make_metadata::<T>(metadata),
+        })
+    }
+
+    /// Attaches `doc` as the documentation of `field` (a `.` separated path into the module).
+    ///
+    /// ```ignore
+    /// ExternModule::new(thread, record! { new_struct => primitive!(1, new_struct) })?
+    ///     .doc("new_struct", "Creates a new struct")
+    /// ```
+    pub fn doc(mut self, field: &str, doc: &str) -> Self {
+        self.field_metadata(field).comment = Some(Comment {
+            typ: CommentType::Line,
+            content: doc.into(),
+        });
+        self
+    }
+
+    /// Attaches the attribute `#[name(arguments)]` to `field` (a `.` separated path into the
+    /// module).
+    pub fn attribute(mut self, field: &str, name: &str, arguments: Option<&str>) -> Self {
+        self.field_metadata(field).attributes.push(Attribute {
+            name: name.into(),
+            arguments: arguments.map(|arguments| arguments.into()),
+        });
+        self
+    }
+
+    fn field_metadata(&mut self, field: &str) -> &mut Metadata {
+        field.split('.').fold(&mut self.metadata, |metadata, name| {
+            Arc::make_mut(metadata.module.entry(name.into()).or_default())
+        })
+    }
+}
+
+fn make_metadata<T: ?Sized + VmType>(metadata: Metadata) -> Metadata {
+    let mut derived = Metadata::default();
+    T::make_metadata(&mut derived);
+    metadata.merge(derived)
 }
 
 /// Internal types and functions exposed to the main `gluon` crate
@@ -3,26 +3,33 @@ use std::{
     any::{Any, TypeId},
     error::Error as StdError,
     fmt, mem,
+    ops::RangeInclusive,
     pin::Pin,
     sync::{Arc, RwLock},
 };
 
 use {
-    codespan_reporting::diagnostic::Diagnostic,
+    codespan_reporting::diagnostic::{Diagnostic, Label},
     downcast_rs::{impl_downcast, Downcast},
-    futures::{prelude::*, task::Spawn},
+    futures::{
+        channel::oneshot,
+        future::{self, Either, Shared},
+        prelude::*,
+        task::Spawn,
+    },
 };
 
 use gluon_codegen::Trace;
 
 use crate::base::{
-    ast::{self, Expr, MutVisitor, SpannedExpr},
+    ast::{self, Expr, MutVisitor, SpannedExpr, ValueBinding, Visitor},
     error::{AsDiagnostic, Errors as BaseErrors, Salvage, SalvageResult},
     fnv::FnvMap,
     pos,
-    pos::{BytePos, Spanned},
+    pos::{BytePos, Span, Spanned},
     source::FileId,
     symbol::{Symbol, Symbols},
+    types::ArcType,
 };
 
 use crate::{
@@ -36,8 +43,44 @@ pub type Errors = BaseErrors<SpannedError>;
 pub type MacroResult<'ast> = Result<SpannedExpr<'ast, Symbol>, Error>;
 pub type SalvageMacroResult<'ast> = SalvageResult<SpannedExpr<'ast, Symbol>, Error>;
 
+/// The expression a macro expands to, along with the bindings it emits into the surrounding scope
+pub struct MacroExpansion<'ast> {
+    pub expr: SpannedExpr<'ast, Symbol>,
+    bindings: Vec<ValueBinding<'ast, Symbol>>,
+    sibling_bindings: Vec<ValueBinding<'ast, Symbol>>,
+}
+
+impl<'ast> MacroExpansion<'ast> {
+    pub fn new(expr: SpannedExpr<'ast, Symbol>) -> Self {
+        MacroExpansion {
+            expr,
+            bindings: Vec::new(),
+            sibling_bindings: Vec::new(),
+        }
+    }
+
+    /// Emits `bind` so that it is bound in the scope of the expression which the macro expands
+    /// to. This lets a macro generate helper functions alongside its expansion.
+    pub fn emit_binding(&mut self, bind: ValueBinding<'ast, Symbol>) {
+        self.bindings.push(bind);
+    }
+
+    /// Emits `bind` so that it is bound next to the binding whose right-hand side is the macro
+    /// (`let x = gen! ...`), making it visible to the rest of the enclosing block. If the macro is
+    /// not the right-hand side of a `let` an error is reported at the macro call.
+    pub fn emit_sibling_binding(&mut self, bind: ValueBinding<'ast, Symbol>) {
+        self.sibling_bindings.push(bind);
+    }
+}
+
+impl<'ast> From<SpannedExpr<'ast, Symbol>> for MacroExpansion<'ast> {
+    fn from(expr: SpannedExpr<'ast, Symbol>) -> Self {
+        MacroExpansion::new(expr)
+    }
+}
+
 pub enum LazyMacroResult<'ast> {
-    Done(SpannedExpr<'ast, Symbol>),
+    Done(MacroExpansion<'ast>),
     Lazy(
         Box<
             dyn for<'a> FnOnce() -> Pin<
@@ -49,16 +92,25 @@ pub enum LazyMacroResult<'ast> {
 }
 
 impl<'ast> LazyMacroResult<'ast> {
-    async fn compute(self) -> SalvageMacroResult<'ast> {
+    async fn compute(self) -> SalvageResult<MacroExpansion<'ast>, Error> {
         match self {
             Self::Done(r) => Ok(r),
-            Self::Lazy(f) => f().await,
+            Self::Lazy(f) => f()
+                .await
+                .map(MacroExpansion::new)
+                .map_err(|salvage| salvage.map(MacroExpansion::new)),
         }
     }
 }
 
 impl<'ast> From<SpannedExpr<'ast, Symbol>> for LazyMacroResult<'ast> {
     fn from(r: SpannedExpr<'ast, Symbol>) -> Self {
+        Self::Done(r.into())
+    }
+}
+
+impl<'ast> From<MacroExpansion<'ast>> for LazyMacroResult<'ast> {
+    fn from(r: MacroExpansion<'ast>) -> Self {
         Self::Done(r)
     }
 }
@@ -120,7 +172,7 @@ where
     T: Clone + PartialEq + std::hash::Hash + AsDiagnostic + StdError + Send + Sync + 'static,
 {
     fn clone_error(&self) -> Error {
-        Error(Box::new(self.clone()))
+        Error::new(self.clone())
     }
     fn eq_error(&self, other: &dyn MacroError) -> bool {
         other
@@ -133,41 +185,61 @@ where
 }
 
 #[derive(Debug)]
-pub struct Error(Box<dyn MacroError>);
+pub struct Error {
+    error: Box<dyn MacroError>,
+    notes: Vec<(Span<BytePos>, String)>,
+}
 
 impl StdError for Error {
     #[allow(deprecated)]
     fn description(&self) -> &str {
-        self.0.description()
+        self.error.description()
     }
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
-        self.0.source()
+        self.error.source()
     }
 }
 
 impl AsDiagnostic for Error {
     fn as_diagnostic(&self, map: &base::source::CodeMap) -> Diagnostic<FileId> {
-        self.0.as_diagnostic(map)
+        let mut diagnostic = self.error.as_diagnostic(map);
+        if !self.notes.is_empty() && diagnostic.labels.is_empty() {
+            // Filled in with the span of the error (see `AsDiagnostic for Spanned`)
+            diagnostic
+                .labels
+                .push(Label::primary(FileId::default(), 0..0));
+        }
+        for (span, message) in &self.notes {
+            if let Some(range) = span.to_range(map) {
+                diagnostic
+                    .labels
+                    .push(Label::secondary(span.start(), range).with_message(message.clone()));
+            }
+        }
+        diagnostic
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.0.fmt(f)
+        self.error.fmt(f)
     }
 }
 
 impl Clone for Error {
     fn clone(&self) -> Self {
-        self.0.clone_error()
+        let mut error = self.error.clone_error();
+        error.notes = self.notes.clone();
+        error
     }
 }
 
 impl Eq for Error {}
 
+/// Notes do not affect the identity of an error so they are ignored
 impl PartialEq for Error {
     fn eq(&self, other: &Self) -> bool {
-        self.0.eq_error(&*other.0)
+        self.error.eq_error(&*other.error)
     }
 }
 
@@ -176,7 +248,7 @@ impl std::hash::Hash for Error {
     where
         H: std::hash::Hasher,
     {
-        self.0.hash_error(state)
+        self.error.hash_error(state)
     }
 }
 
@@ -185,7 +257,25 @@ impl Error {
     where
         E: MacroError,
     {
-        Self(Box::new(err))
+        Self {
+            error: Box::new(err),
+            notes: Vec::new(),
+        }
+    }
+
+    /// Adds a note which points at `span`, for instance at the definition of the macro which
+    /// reported the error. Notes are shown as secondary labels of the error.
+    ///
+    /// `span` may lie in any file of the `CodeMap` the error is emitted with, not only in the
+    /// module containing the macro call, and the label is shown in that file.
+    pub fn with_note(mut self, span: Span<BytePos>, message: impl Into<String>) -> Self {
+        self.notes.push((span, message.into()));
+        self
+    }
+
+    /// Returns the notes added with `with_note`
+    pub fn notes(&self) -> &[(Span<BytePos>, String)] {
+        &self.notes
     }
 
     pub fn message(s: impl Into<String>) -> Error {
@@ -213,11 +303,20 @@ impl Error {
         Self::new(StringError(s.into()))
     }
 
+    /// Returns the wrapped error if it is a `T`
+    pub fn downcast_ref<T>(&self) -> Option<&T>
+    where
+        T: MacroError,
+    {
+        self.error.downcast_ref()
+    }
+
     pub fn downcast<T>(self) -> Result<Box<T>, Self>
     where
         T: MacroError,
     {
-        self.0.downcast().map_err(Self)
+        let Error { error, notes } = self;
+        error.downcast().map_err(|error| Error { error, notes })
     }
 }
 
@@ -330,12 +429,48 @@ pub trait MacroUserdata: Send {
     fn fork(&self, thread: RootedThread) -> Box<dyn Any>;
 }
 
+/// A capability (see `Macro::get_capability`) of macros which can report the symbols they read
+/// from the surrounding scope without being expanded. Tools can use it to compute the dependencies
+/// of an expression before running any macros. `module` is the module which contains the
+/// invocation, if it is known.
+pub trait MacroDependencies: Send + Sync {
+    fn referenced_symbols(&self, module: Option<&str>, args: &[SpannedExpr<Symbol>])
+        -> Vec<Symbol>;
+}
+
+/// A capability (see `Macro::get_capability`) of macros which can describe what an invocation
+/// produces without being expanded, for example to show as hover text in an editor. `module` is
+/// the module which contains the invocation, if it is known.
+pub trait MacroDoc: Send + Sync {
+    fn describe(&self, module: Option<&str>, args: &[SpannedExpr<Symbol>]) -> Option<String>;
+}
+
+/// A transformation of the AST which runs either before any macros are expanded or after all of
+/// them have been (see `MacroEnv::add_pre_expansion_transform` and
+/// `MacroEnv::add_post_expansion_transform`).
+pub trait AstTransform: Send + Sync {
+    fn transform<'ast>(
+        &self,
+        symbols: &mut Symbols,
+        arena: ast::ArenaRef<'_, 'ast, Symbol>,
+        expr: &mut SpannedExpr<'ast, Symbol>,
+    );
+}
+
+#[derive(Default, Trace)]
+#[gluon(gluon_vm)]
+#[gluon_trace(skip)]
+struct AstTransforms(RwLock<Vec<Arc<dyn AstTransform>>>);
+
 /// Type containing macros bound to symbols which can be applied on an AST expression to transform
 /// it.
 #[derive(Trace, Default)]
 #[gluon(gluon_vm)]
 pub struct MacroEnv {
     macros: RwLock<FnvMap<String, Arc<dyn Macro>>>,
+    literal_suffixes: RwLock<FnvMap<String, Arc<dyn Macro>>>,
+    pre_expansion: AstTransforms,
+    post_expansion: AstTransforms,
 }
 
 impl MacroEnv {
@@ -343,6 +478,9 @@ impl MacroEnv {
     pub fn new() -> MacroEnv {
         MacroEnv {
             macros: RwLock::new(FnvMap::default()),
+            literal_suffixes: RwLock::new(FnvMap::default()),
+            pre_expansion: AstTransforms::default(),
+            post_expansion: AstTransforms::default(),
         }
     }
 
@@ -359,6 +497,73 @@ impl MacroEnv {
         self.macros.read().unwrap().get(name).cloned()
     }
 
+    /// Returns true if a macro is bound to `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.macros.read().unwrap().contains_key(name)
+    }
+
+    /// Removes the macro bound to `name`, returning it if it existed
+    pub fn remove(&self, name: &str) -> Option<Arc<dyn Macro>> {
+        self.macros.write().unwrap().remove(name)
+    }
+
+    /// Inserts a `Macro` which expands any literal directly followed by `suffix` (`5px`, `3.0s`,
+    /// `"abc"re`). The macro is called with the literal as its only argument.
+    ///
+    /// Suffixes of integer literals may not start with `b` as that is used by byte literals.
+    pub fn insert_literal_suffix<M>(&self, suffix: String, mac: M)
+    where
+        M: Macro + 'static,
+    {
+        self.literal_suffixes
+            .write()
+            .unwrap()
+            .insert(suffix, Arc::new(mac));
+    }
+
+    /// Adds a transformation which is run on every expression before its macros are expanded.
+    /// Transformations run in the order they were added.
+    pub fn add_pre_expansion_transform<T>(&self, transform: T)
+    where
+        T: AstTransform + 'static,
+    {
+        self.pre_expansion
+            .0
+            .write()
+            .unwrap()
+            .push(Arc::new(transform));
+    }
+
+    fn pre_expansion_transforms(&self) -> Vec<Arc<dyn AstTransform>> {
+        self.pre_expansion.0.read().unwrap().clone()
+    }
+
+    /// Adds a transformation which is run on every expression after all of its macros have been
+    /// expanded. The expression still contains the `MacroExpansion` nodes of the expanded macros.
+    ///
+    /// Transformations run in the order they were added, after every pre-expansion transformation
+    /// and only if no macro reported an error. Macro calls inserted by the transformation are
+    /// not expanded.
+    pub fn add_post_expansion_transform<T>(&self, transform: T)
+    where
+        T: AstTransform + 'static,
+    {
+        self.post_expansion
+            .0
+            .write()
+            .unwrap()
+            .push(Arc::new(transform));
+    }
+
+    fn post_expansion_transforms(&self) -> Vec<Arc<dyn AstTransform>> {
+        self.post_expansion.0.read().unwrap().clone()
+    }
+
+    /// Retrieves the macro handling literals with `suffix`
+    pub fn get_literal_suffix(&self, suffix: &str) -> Option<Arc<dyn Macro>> {
+        self.literal_suffixes.read().unwrap().get(suffix).cloned()
+    }
+
     pub fn get_capabilities<T>(&self, thread: &Thread) -> Vec<T>
     where
         T: Any,
@@ -382,6 +587,9 @@ impl MacroEnv {
 
     pub fn clear(&self) {
         self.macros.write().unwrap().clear();
+        self.literal_suffixes.write().unwrap().clear();
+        self.pre_expansion.0.write().unwrap().clear();
+        self.post_expansion.0.write().unwrap().clear();
     }
 
     /// Runs the macros in this `MacroEnv` on `expr` using `env` as the context of the expansion
@@ -400,6 +608,8 @@ impl MacroEnv {
     }
 }
 
+const DEFAULT_MAX_DEPTH: usize = 128;
+
 pub struct MacroExpander<'a> {
     pub state: FnvMap<String, Box<dyn Any + Send>>,
     pub vm: &'a Thread,
@@ -407,6 +617,13 @@ pub struct MacroExpander<'a> {
     pub userdata: &'a mut (dyn MacroUserdata + 'a),
     pub spawn: Option<&'a (dyn Spawn + Send + Sync + 'a)>,
     macros: &'a MacroEnv,
+    cancel: Option<Shared<oneshot::Receiver<()>>>,
+    expected_type: Option<ArcType>,
+    module: Option<String>,
+    /// The number of `run_once` calls which are currently expanding, which grows when macros
+    /// expand the expressions they produce
+    depth: usize,
+    max_depth: usize,
 }
 
 impl<'a> MacroExpander<'a> {
@@ -419,23 +636,140 @@ impl<'a> MacroExpander<'a> {
             vm,
             state: FnvMap::default(),
             macros: vm.get_macros(),
+            cancel: None,
+            expected_type: None,
+            module: None,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
             userdata,
             spawn,
             errors: Errors::new(),
         }
     }
 
+    /// Stops the expansion once `cancel` receives a value. Every macro which has not finished
+    /// expanding at that point reports an error instead.
+    pub fn cancel_on(mut self, cancel: oneshot::Receiver<()>) -> Self {
+        self.cancel = Some(cancel.shared());
+        self
+    }
+
+    /// Sets the type which the expression being expanded is expected to have.
+    pub fn with_expected_type(mut self, expected_type: Option<ArcType>) -> Self {
+        self.expected_type = expected_type;
+        self
+    }
+
+    /// Returns the type which the expanded expression is expected to have, if it is known.
+    ///
+    /// Since macros are expanded before typechecking this is only the expected type of the
+    /// expression as a whole (as given to `run_expr` or `typecheck_expected`). Macros nested
+    /// inside the expression see the same type, not the type expected at their position.
+    pub fn expected_type(&self) -> Option<&ArcType> {
+        self.expected_type.as_ref()
+    }
+
+    /// Sets how deeply macros may expand the expressions produced by other macros (by calling
+    /// `run_once`) before the expansion is aborted with an error. This stops macros which
+    /// (indirectly) expand to themselves from expanding forever.
+    /// (default: 128)
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the name of the module which the expression being expanded belongs to.
+    pub fn with_module(mut self, module: impl Into<String>) -> Self {
+        self.module = Some(module.into());
+        self
+    }
+
+    /// Returns the name of the module which the expression being expanded belongs to, if it is
+    /// known.
+    pub fn module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+
+    /// Returns the state stored under `key`, inserting `T::default()` if there is none. This lets
+    /// separate invocations of macros share state during an expansion, for instance a counter for
+    /// generating fresh names.
+    ///
+    /// Panics if the state stored under `key` is not a `T`.
+    pub fn state_entry<T>(&mut self, key: &str) -> &mut T
+    where
+        T: Default + Any + Send,
+    {
+        self.state
+            .entry(key.into())
+            .or_insert_with(|| Box::new(T::default()))
+            .downcast_mut()
+            .unwrap_or_else(|| {
+                panic!(
+                    "The macro state `{}` is not of type `{}`",
+                    key,
+                    std::any::type_name::<T>()
+                )
+            })
+    }
+
+    /// Returns the state stored under `key` if it exists and is a `T`
+    pub fn get_state<T>(&self, key: &str) -> Option<&T>
+    where
+        T: Any,
+    {
+        self.state.get(key).and_then(|state| state.downcast_ref())
+    }
+
     pub fn fork(&self, userdata: &'a mut (dyn MacroUserdata + 'a)) -> MacroExpander<'a> {
         MacroExpander {
             vm: self.vm,
             state: FnvMap::default(),
             macros: self.macros,
+            cancel: self.cancel.clone(),
+            expected_type: None,
+            module: self.module.clone(),
+            depth: 0,
+            max_depth: self.max_depth,
             userdata,
             spawn: self.spawn,
             errors: Errors::new(),
         }
     }
 
+    /// Checks that a macro called at `call_span` were given a number of arguments within
+    /// `expected`. If it were not, an error stating the expected and actual number of arguments
+    /// is reported and `Err(())` is returned.
+    pub fn expect_args(
+        &mut self,
+        call_span: Span<BytePos>,
+        args: &[SpannedExpr<Symbol>],
+        expected: RangeInclusive<usize>,
+    ) -> Result<(), ()> {
+        if expected.contains(&args.len()) {
+            return Ok(());
+        }
+        let plural = |count: usize| if count == 1 { "argument" } else { "arguments" };
+        let expected = if expected.start() == expected.end() {
+            format!("{} {}", expected.start(), plural(*expected.end()))
+        } else {
+            format!(
+                "{} to {} {}",
+                expected.start(),
+                expected.end(),
+                plural(*expected.end())
+            )
+        };
+        self.errors.push(pos::spanned(
+            call_span,
+            Error::message(format!(
+                "Expected the macro to be called with {} but it was called with {}",
+                expected,
+                args.len()
+            )),
+        ));
+        Err(())
+    }
+
     pub fn finish(self) -> Result<(), Errors> {
         if self.errors.has_errors() {
             Err(self.errors)
@@ -444,13 +778,43 @@ impl<'a> MacroExpander<'a> {
         }
     }
 
+    /// Returns the symbols which the macros in `expr` read from the scope, as reported by their
+    /// `MacroDependencies` capability, along with the modules imported by `derive` attributes.
+    /// No macro is expanded and macros which do not implement the capability contribute nothing.
+    pub fn collect_dependencies(&self, expr: &SpannedExpr<Symbol>) -> Vec<Symbol> {
+        let mut visitor = DependencyVisitor {
+            expander: self,
+            symbols: Vec::new(),
+        };
+        visitor.visit_expr(expr);
+        visitor.symbols
+    }
+
+    /// Returns a human readable description of what invoking the macro bound to `name` with
+    /// `args` would produce, if the macro implements the `MacroDoc` capability. The macro is not
+    /// expanded.
+    pub fn describe_macro(&self, name: &str, args: &[SpannedExpr<Symbol>]) -> Option<String> {
+        let name = name.trim_end_matches('!');
+        let mac = self.macros.get(name)?;
+        mac.get_capability::<Arc<dyn MacroDoc>>(self.vm, &mac)?
+            .describe(self.module(), args)
+    }
+
     pub async fn run<'ast>(
         &mut self,
         symbols: &mut Symbols,
         mut arena: ast::OwnedArena<'ast, Symbol>,
         expr: &'ast mut SpannedExpr<'ast, Symbol>,
     ) {
+        for transform in self.macros.pre_expansion_transforms() {
+            transform.transform(symbols, arena.borrow(), expr);
+        }
         self.run_once(symbols, &mut arena, expr).await; // FIXME
+        if !self.errors.has_errors() {
+            for transform in self.macros.post_expansion_transforms() {
+                transform.transform(symbols, arena.borrow(), expr);
+            }
+        }
     }
 
     pub async fn run_once<'ast>(
@@ -459,6 +823,18 @@ impl<'a> MacroExpander<'a> {
         arena: &mut ast::OwnedArena<'ast, Symbol>,
         expr: &mut SpannedExpr<'ast, Symbol>,
     ) {
+        if self.depth >= self.max_depth {
+            self.errors.push(pos::spanned(
+                expr.span,
+                Error::message(format!(
+                    "macro expansion depth exceeded (limit: {})",
+                    self.max_depth
+                )),
+            ));
+            *expr = pos::spanned(expr.span, Expr::Error(None));
+            return;
+        }
+
         let mut visitor = MacroVisitor {
             expander: self,
             symbols,
@@ -467,7 +843,23 @@ impl<'a> MacroExpander<'a> {
         };
         visitor.visit_expr(expr);
         let MacroVisitor { exprs, symbols, .. } = visitor;
-        self.expand(symbols, arena, exprs).await
+        self.depth += 1;
+        let siblings = self.expand(symbols, arena, exprs).await;
+        self.depth -= 1;
+        if !siblings.is_empty() {
+            let mut visitor = SiblingVisitor { arena, siblings };
+            visitor.visit_expr(expr);
+            self.errors
+                .extend(visitor.siblings.into_iter().map(|siblings| {
+                    pos::spanned(
+                        siblings.span,
+                        Error::message(
+                            "Bindings emitted by this macro can only be bound when the macro is \
+                            the right-hand side of a `let`",
+                        ),
+                    )
+                }));
+        }
     }
 
     async fn expand<'ast>(
@@ -475,15 +867,20 @@ impl<'a> MacroExpander<'a> {
         symbols: &mut Symbols,
         arena: &mut ast::OwnedArena<'ast, Symbol>,
         mut exprs: Vec<(&'_ mut SpannedExpr<'ast, Symbol>, Arc<dyn Macro>)>,
-    ) {
+    ) -> Vec<SiblingBindings<'ast>> {
+        let mut siblings = Vec::new();
         let mut futures = Vec::with_capacity(exprs.len());
+        let mut spans = Vec::with_capacity(exprs.len());
         for (expr, mac) in exprs.drain(..) {
             let result = match &mut expr.value {
                 Expr::App { args, .. } => mac.expand(self, symbols, arena, args).await,
                 _ => unreachable!("{:?}", expr),
             };
             match result {
-                Ok(result) => futures.push(result.compute().map(move |result| (expr, result))),
+                Ok(result) => {
+                    spans.push(expr.span);
+                    futures.push(result.compute().map(move |result| (expr, result)))
+                }
                 Err(err) => {
                     self.errors.push(pos::spanned(expr.span, err));
                     replace_expr(arena, expr, Expr::Error(None));
@@ -492,21 +889,67 @@ impl<'a> MacroExpander<'a> {
         }
 
         // Index each expansion future so we can keep any returned errors in a consistent order
+        let mut finished = vec![false; spans.len()];
         let mut stream = futures
             .into_iter()
             .enumerate()
             .map(|(index, future)| future.map(move |x| (index, x)))
             .collect::<futures::stream::FuturesUnordered<_>>();
         let mut unordered_errors = Vec::new();
-        while let Some((index, (expr, result))) = stream.next().await {
+        let mut cancelled = match &self.cancel {
+            // Dropping the sender does not cancel the expansion
+            Some(cancel) => cancel
+                .clone()
+                .then(|result| match result {
+                    Ok(()) => future::ready(()).left_future(),
+                    Err(_) => future::pending().right_future(),
+                })
+                .boxed(),
+            None => future::pending().boxed(),
+        };
+        loop {
+            let next = match future::select(stream.next(), cancelled).await {
+                Either::Left((next, cancel)) => {
+                    cancelled = cancel;
+                    next
+                }
+                Either::Right(((), _)) => {
+                    unordered_errors.extend(
+                        spans
+                            .iter()
+                            .enumerate()
+                            .filter(|&(index, _)| !finished[index])
+                            .map(|(index, &span)| {
+                                let err = Error::message("macro expansion cancelled");
+                                (index, pos::spanned(span, err))
+                            }),
+                    );
+                    break;
+                }
+            };
+            let (index, (expr, result)) = match next {
+                Some(next) => next,
+                None => break,
+            };
+            finished[index] = true;
             let expr = { expr };
-            let new_expr = match result {
-                Ok(replacement) => replacement.value,
+            let expansion = match result {
+                Ok(expansion) => expansion,
                 Err(Salvage { error, value }) => {
                     unordered_errors.push((index, pos::spanned(expr.span, error)));
-                    value.map_or_else(|| Expr::Error(None), |e| e.value)
+                    value.unwrap_or_else(|| {
+                        MacroExpansion::new(pos::spanned(expr.span, Expr::Error(None)))
+                    })
                 }
             };
+            if !expansion.sibling_bindings.is_empty() {
+                siblings.push(SiblingBindings {
+                    site: expr_site(expr),
+                    span: expr.span,
+                    bindings: expansion.sibling_bindings,
+                });
+            }
+            let new_expr = let_emitted_bindings(arena, expansion.bindings, expansion.expr);
 
             replace_expr(arena, expr, new_expr);
         }
@@ -514,9 +957,26 @@ impl<'a> MacroExpander<'a> {
         unordered_errors.sort_by_key(|&(index, _)| index);
         self.errors
             .extend(unordered_errors.into_iter().map(|(_, err)| err));
+        siblings
     }
 }
 
+/// Binds `bindings` around `expr`. Only functions may be recursive (the compiler only supports
+/// records and variants otherwise) so every other binding gets its own, non-recursive `let`.
+fn let_emitted_bindings<'ast>(
+    arena: &ast::OwnedArena<'ast, Symbol>,
+    bindings: Vec<ValueBinding<'ast, Symbol>>,
+    expr: SpannedExpr<'ast, Symbol>,
+) -> Expr<'ast, Symbol> {
+    let (functions, values): (Vec<_>, Vec<_>) =
+        bindings.into_iter().partition(|bind| !bind.args.is_empty());
+    let span = expr.span;
+    let expr = values.into_iter().rev().fold(expr, |expr, bind| {
+        pos::spanned(span, Expr::let_binding(arena.borrow(), bind, expr))
+    });
+    Expr::rec_let_bindings(arena.borrow(), functions, expr)
+}
+
 fn replace_expr<'ast>(
     arena: &ast::OwnedArena<'ast, Symbol>,
     expr: &mut SpannedExpr<'ast, Symbol>,
@@ -533,6 +993,104 @@ fn replace_expr<'ast>(
     );
 }
 
+/// Identifies the place in the AST which `expr` is stored at. Expanding a macro replaces the call
+/// in place so the site of a call stays the same after the expansion.
+fn expr_site(expr: &SpannedExpr<Symbol>) -> usize {
+    expr as *const SpannedExpr<Symbol> as usize
+}
+
+/// The sibling bindings emitted by the macro which were called at `site`
+struct SiblingBindings<'ast> {
+    site: usize,
+    span: Span<BytePos>,
+    bindings: Vec<ValueBinding<'ast, Symbol>>,
+}
+
+/// Binds the sibling bindings emitted by macros after the `let` bindings the macros are the
+/// right-hand side of. Bindings which could not be placed are left in `siblings`.
+struct SiblingVisitor<'d, 'ast> {
+    arena: &'d ast::OwnedArena<'ast, Symbol>,
+    siblings: Vec<SiblingBindings<'ast>>,
+}
+
+impl<'e, 'ast> MutVisitor<'e, 'ast> for SiblingVisitor<'_, 'ast> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'e mut SpannedExpr<'ast, Symbol>) {
+        if let Expr::LetBindings(binds, body) = &mut expr.value {
+            let mut generated_bindings = Vec::new();
+            for bind in &**binds {
+                if let Expr::MacroExpansion { .. } = bind.expr.value {
+                    let site = expr_site(&bind.expr);
+                    if let Some(index) = self
+                        .siblings
+                        .iter()
+                        .position(|siblings| siblings.site == site)
+                    {
+                        generated_bindings.extend(self.siblings.swap_remove(index).bindings);
+                    }
+                }
+            }
+            if !generated_bindings.is_empty() {
+                let next_expr = mem::take(*body);
+                body.value = let_emitted_bindings(self.arena, generated_bindings, next_expr);
+            }
+        }
+        ast::walk_mut_expr(self, expr);
+    }
+}
+
+struct DependencyVisitor<'a, 'e> {
+    expander: &'e MacroExpander<'a>,
+    symbols: Vec<Symbol>,
+}
+
+impl<'a, 'ast> Visitor<'a, 'ast> for DependencyVisitor<'_, '_> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+        match &expr.value {
+            Expr::App { func, args, .. } => match &func.value {
+                Expr::Ident(id) if id.name.as_str().ends_with('!') => {
+                    let name = id.name.as_str();
+                    let dependencies =
+                        self.expander
+                            .macros
+                            .get(&name[..name.len() - 1])
+                            .and_then(|mac| {
+                                mac.get_capability::<Arc<dyn MacroDependencies>>(
+                                    self.expander.vm,
+                                    &mac,
+                                )
+                            });
+                    if let Some(dependencies) = dependencies {
+                        self.symbols
+                            .extend(dependencies.referenced_symbols(self.expander.module(), args));
+                    }
+                }
+                _ => (),
+            },
+            Expr::TypeBindings(binds, _) => {
+                for bind in &**binds {
+                    for derive in bind
+                        .metadata
+                        .attributes()
+                        .filter(|attr| attr.name == "derive")
+                    {
+                        self.symbols.extend(
+                            crate::derive::imported_modules(derive)
+                                .into_iter()
+                                .map(|module| Symbol::from(format!("@{}", module))),
+                        );
+                    }
+                }
+            }
+            _ => (),
+        }
+        ast::walk_expr(self, expr)
+    }
+}
+
 struct MacroVisitor<'a: 'b, 'b, 'c, 'd, 'e, 'ast> {
     expander: &'b mut MacroExpander<'a>,
     symbols: &'c mut Symbols,
@@ -544,6 +1102,7 @@ impl<'a, 'b, 'c, 'e, 'ast> MutVisitor<'e, 'ast> for MacroVisitor<'a, 'b, 'c, '_,
     type Ident = Symbol;
 
     fn visit_expr(&mut self, expr: &'e mut SpannedExpr<'ast, Symbol>) {
+        let mut unknown_suffix = false;
         let replacement = match &mut expr.value {
             Expr::App {
                 implicit_args,
@@ -565,6 +1124,24 @@ impl<'a, 'b, 'c, 'e, 'ast> MutVisitor<'e, 'ast> for MacroVisitor<'a, 'b, 'c, '_,
                         None => None,
                     }
                 }
+                // A suffixed literal (`5px`) is parsed as the suffix applied to the literal
+                Expr::Ident(ref id) => match ast::literal_suffix(id.name.as_str()) {
+                    Some(suffix) => {
+                        let mac = self.expander.macros.get_literal_suffix(suffix);
+                        if mac.is_none() {
+                            unknown_suffix = true;
+                            self.expander.errors.push(pos::spanned(
+                                func.span,
+                                Error::message(format!(
+                                    "No macro is registered for the literal suffix `{}`",
+                                    suffix
+                                )),
+                            ));
+                        }
+                        mac
+                    }
+                    None => None,
+                },
                 _ => None,
             },
             Expr::TypeBindings(binds, body) => {
@@ -604,6 +1181,8 @@ impl<'a, 'b, 'c, 'e, 'ast> MutVisitor<'e, 'ast> for MacroVisitor<'a, 'b, 'c, '_,
         };
         if let Some(future) = replacement {
             self.exprs.push((expr, future));
+        } else if unknown_suffix {
+            replace_expr(self.arena, expr, Expr::Error(None));
         } else {
             ast::walk_mut_expr(self, expr);
         }
@@ -948,6 +948,16 @@ impl Thread {
         self.owned_context().gc.set_memory_limit(memory_limit)
     }
 
+    /// Limits the number of instructions this thread may execute before failing with
+    /// `Error::FuelExhausted`. `None` removes the limit.
+    pub fn set_fuel(&self, fuel: Option<u64>) {
+        self.context().set_fuel(fuel)
+    }
+
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.context().remaining_fuel()
+    }
+
     pub fn interrupt(&self) {
         self.interrupt.store(true, atomic::Ordering::Relaxed)
     }
@@ -1464,6 +1474,8 @@ struct Hook {
     flags: HookFlags,
     // The index of the last executed instruction
     previous_instruction_index: usize,
+    // The number of instructions which may still be executed before the thread fails
+    fuel: Option<u64>,
 }
 
 type PollFnInner<'a> = Box<
@@ -1514,6 +1526,7 @@ impl Context {
                 function: None,
                 flags: HookFlags::empty(),
                 previous_instruction_index: usize::max_value(),
+                fuel: None,
             },
             poll_fns: Vec::new(),
         }
@@ -1593,6 +1606,17 @@ impl Context {
         self.stack.set_max_stack_size(limit);
     }
 
+    /// Limits the number of instructions this context may execute before failing with
+    /// `Error::FuelExhausted`. `None` removes the limit.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.hook.fuel = fuel;
+    }
+
+    /// Returns the number of instructions which may still be executed, if a limit is set
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.hook.fuel
+    }
+
     pub fn stacktrace(&self, frame_level: usize) -> crate::stack::Stacktrace {
         self.stack.stacktrace(frame_level)
     }
@@ -2100,6 +2124,13 @@ impl<'b, 'gc> ExecuteContext<'b, 'gc> {
 
             debug_instruction(&self.stack, instruction_index, instr);
 
+            if let Some(fuel) = &mut self.hook.fuel {
+                if *fuel == 0 {
+                    return Err(Error::FuelExhausted).into();
+                }
+                *fuel -= 1;
+            }
+
             if !self.hook.flags.is_empty() && self.hook.flags.contains(HookFlags::LINE_FLAG) {
                 ready!(self.run_hook(&function, instruction_index))?;
             }
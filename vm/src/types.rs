@@ -218,6 +218,27 @@ impl Instruction {
     }
 }
 
+/// A literal which is embedded in the instructions of a compiled function
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constant {
+    Int(VmInt),
+    Byte(u8),
+    Float(f64),
+    String(String),
+}
+
+impl Constant {
+    /// Returns the gluon type of the literal
+    pub fn typ(&self) -> ArcType {
+        match self {
+            Constant::Int(_) => Type::int(),
+            Constant::Byte(_) => Type::byte(),
+            Constant::Float(_) => Type::float(),
+            Constant::String(_) => Type::string(),
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 #[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
 #[cfg_attr(
@@ -160,6 +160,29 @@ unsafe impl Trace for BytecodeFunction {
     impl_trace_fields! { self, gc; inner_functions }
 }
 
+impl BytecodeFunction {
+    /// Returns the literals used by this function and all functions defined inside it, in the
+    /// order they appear in the instructions
+    pub fn constants(&self) -> Vec<Constant> {
+        let mut constants = Vec::new();
+        self.collect_constants(&mut constants);
+        constants
+    }
+
+    fn collect_constants(&self, constants: &mut Vec<Constant>) {
+        constants.extend(self.instructions.iter().filter_map(|instr| match *instr {
+            PushInt(i) => Some(Constant::Int(i)),
+            PushByte(b) => Some(Constant::Byte(b)),
+            PushFloat(f) => Some(Constant::Float(f.into())),
+            PushString(index) => Some(Constant::String((*self.strings[index as usize]).to_owned())),
+            _ => None,
+        }));
+        for function in &self.inner_functions {
+            function.collect_constants(constants);
+        }
+    }
+}
+
 #[derive(Debug, Trace)]
 #[gluon(gluon_vm)]
 #[repr(C)]
@@ -294,6 +294,11 @@ pub trait VmEnv:
     OptimizeEnv + CompilerEnv<Type = ArcType> + MetadataEnv + PrimitiveEnv + Trace
 {
     fn get_global(&self, name: &str) -> Option<RootedGlobal>;
+
+    /// Returns paths similar to `name` which may be suggested when `name` is not defined
+    fn binding_suggestions(&self, _name: &str) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 pub struct VmEnvInstance<'a> {
@@ -388,6 +393,14 @@ impl<'a> VmEnv for VmEnvInstance<'a> {
             .filter_map(|env| env.get_global(name))
             .next()
     }
+
+    fn binding_suggestions(&self, name: &str) -> Vec<String> {
+        self.vm_envs
+            .iter()
+            .map(|env| env.binding_suggestions(name))
+            .find(|suggestions| !suggestions.is_empty())
+            .unwrap_or_default()
+    }
 }
 
 impl<'a> VmEnvInstance<'a> {
@@ -401,9 +414,13 @@ impl<'a> VmEnvInstance<'a> {
         let (_, typ) = self
             .get_binding(name.module().as_str())
             .map_err(|mut err| {
-                if let Error::UndefinedBinding(module) = &mut err {
+                if let Error::UndefinedBinding(module, suggestions) = &mut err {
                     module.clear();
                     module.push_str(name.as_str());
+                    for suggestion in suggestions {
+                        suggestion.push('.');
+                        suggestion.push_str(name.name().as_str());
+                    }
                 }
                 err
             })?;
@@ -448,7 +465,9 @@ impl<'a> VmEnvInstance<'a> {
 
         let (remaining_fields, global) = self
             .get_scoped_global(name)
-            .ok_or_else(|| Error::UndefinedBinding(name.into()))?;
+            .ok_or_else(|| {
+                Error::UndefinedBinding(name.into(), self.binding_suggestions(name))
+            })?;
 
         if remaining_fields.as_str().is_empty() {
             // No fields left